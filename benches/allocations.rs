@@ -0,0 +1,113 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Measures the time and the number of allocations of common workloads. Run with
+//! `cargo bench --bench allocations`.
+//!
+//! Entries are stored in a slab, so growing a cache allocates in chunks rather than once per
+//! entry, and a cache which is full or presized doesn't allocate at all.
+
+extern crate lru;
+extern crate stats_alloc;
+
+use lru::LruCache;
+use stats_alloc::{Region, StatsAlloc, INSTRUMENTED_SYSTEM};
+use std::alloc::System;
+use std::time::Instant;
+
+#[global_allocator]
+static GLOBAL: &StatsAlloc<System> = &INSTRUMENTED_SYSTEM;
+
+const ENTRIES: u64 = 100_000;
+
+// Keeps the optimizer from removing the computation of `value`, like `std::hint::black_box`
+//  which is newer than the minimum supported Rust version
+fn black_box<T>(value: T) -> T {
+    unsafe {
+        let result = std::ptr::read_volatile(&value);
+        std::mem::forget(value);
+        result
+    }
+}
+
+// Runs `f`, which performs `ops` operations, and prints its time and allocations per operation
+fn bench<F: FnOnce()>(name: &str, ops: u64, f: F) {
+    let region = Region::new(GLOBAL);
+    let start = Instant::now();
+    f();
+    let elapsed = start.elapsed();
+    let change = region.change();
+    println!(
+        "{:<28} {:>8.1} ns/op {:>10} allocations {:>8.4} allocations/op",
+        name,
+        elapsed.as_nanos() as f64 / ops as f64,
+        change.allocations,
+        change.allocations as f64 / ops as f64,
+    );
+}
+
+fn main() {
+    bench("fill unbounded", ENTRIES, || {
+        let mut cache = LruCache::unbounded();
+        for i in 0..ENTRIES {
+            cache.put(i, i);
+        }
+        black_box(cache);
+    });
+
+    bench("fill with_capacity", ENTRIES, || {
+        let mut cache = LruCache::with_capacity(ENTRIES as usize);
+        for i in 0..ENTRIES {
+            cache.put(i, i);
+        }
+        black_box(cache);
+    });
+
+    let mut cache = LruCache::new(ENTRIES as usize);
+    for i in 0..ENTRIES {
+        cache.put(i, i);
+    }
+    bench("evict at capacity", 10 * ENTRIES, || {
+        for i in ENTRIES..11 * ENTRIES {
+            black_box(cache.put(i, i));
+        }
+    });
+
+    bench("pop and put", 10 * ENTRIES, || {
+        for i in 11 * ENTRIES..21 * ENTRIES {
+            black_box(cache.pop_lru());
+            black_box(cache.put(i, i));
+        }
+    });
+
+    bench("get", 10 * ENTRIES, || {
+        for i in 0..10 * ENTRIES {
+            black_box(cache.get(&(20 * ENTRIES + i % ENTRIES)));
+        }
+    });
+
+    bench("iter", 10 * ENTRIES, || {
+        for _ in 0..10 {
+            black_box(cache.iter().map(|(_, v)| *v).sum::<u64>());
+        }
+    });
+}
@@ -0,0 +1,437 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Procedural macros for the `lru` crate. Use them through `lru` with its `macros` feature
+//! enabled rather than depending on this crate directly.
+//!
+//! The macros only rely on the compiler's `proc_macro` API, so that they build with the same
+//! minimum Rust version as `lru` itself.
+
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use std::iter::FromIterator;
+
+type Error = (Span, &'static str);
+
+/// Memoizes a function in an `lru::LruCache`, keyed by a tuple of its arguments.
+///
+/// The attribute takes the capacity of the cache as `cap = <expression>`, where the expression
+/// is a constant `usize`. By default, each thread has its own cache. With the additional `sync`
+/// argument, all threads share a single cache behind a mutex, which is not held while the
+/// function runs, so threads which miss on the same arguments at the same time may each call it.
+///
+/// Functions returning a `Result` only have their `Ok` values cached, so errors are retried on
+/// the next call. A return type counts as a `Result` if it is written as a path ending in
+/// `Result<...>`, such as `io::Result<T>`.
+///
+/// The arguments must be `Clone + Hash + Eq + 'static` and bound to plain identifiers, and the
+/// cached value must be `Clone + 'static`. Generic, `const`, `async`, `unsafe` and `extern`
+/// functions and methods taking `self` are not supported.
+///
+/// # Example
+///
+/// ```ignore
+/// use lru::lru_memoize;
+///
+/// #[lru_memoize(cap = 100)]
+/// fn fibonacci(n: u64) -> u64 {
+///     if n < 2 {
+///         n
+///     } else {
+///         fibonacci(n - 1) + fibonacci(n - 2)
+///     }
+/// }
+///
+/// #[lru_memoize(cap = 100, sync)]
+/// fn parse(input: String) -> Result<u32, std::num::ParseIntError> {
+///     input.parse()
+/// }
+///
+/// assert_eq!(fibonacci(90), 2880067194370816120);
+/// assert!(parse("12".to_string()).is_ok());
+/// assert!(parse("twelve".to_string()).is_err());
+/// ```
+#[proc_macro_attribute]
+pub fn lru_memoize(attr: TokenStream, item: TokenStream) -> TokenStream {
+    match expand(attr, item.clone()) {
+        Ok(tokens) => tokens,
+        Err((span, message)) => {
+            // keep the function around so that its callers do not report errors as well
+            let mut tokens = compile_error(span, message);
+            tokens.extend(item);
+            tokens
+        }
+    }
+}
+
+struct Args {
+    cap: TokenStream,
+    sync: bool,
+}
+
+struct Function {
+    // attributes, visibility and anything else before `fn`
+    prefix: Vec<TokenTree>,
+    fn_token: TokenTree,
+    name: Ident,
+    args: Group,
+    // the identifier and type of each argument
+    params: Vec<(Ident, Vec<TokenTree>)>,
+    ret: Vec<TokenTree>,
+    body: Group,
+}
+
+fn expand(attr: TokenStream, item: TokenStream) -> Result<TokenStream, Error> {
+    let args = parse_args(attr)?;
+    let func = parse_fn(item)?;
+    let ok_type = result_ok_type(&func.ret);
+
+    let mut body = TokenStream::new();
+
+    // the original function, which is called on cache misses
+    body.extend(code("fn __lru_memoize_inner"));
+    body.extend(Some(TokenTree::Group(func.args.clone())));
+    body.extend(return_type(&func.ret));
+    body.extend(Some(TokenTree::Group(func.body.clone())));
+
+    let mut key = TokenStream::new();
+    for (_, ty) in &func.params {
+        key.extend(ty.iter().cloned());
+        key.extend(code(","));
+    }
+    body.extend(code("type __LruMemoizeKey ="));
+    body.extend(group(Delimiter::Parenthesis, key));
+    body.extend(code(";"));
+
+    body.extend(code("type __LruMemoizeValue ="));
+    match ok_type {
+        Some(ref ty) => body.extend(ty.iter().cloned()),
+        None if func.ret.is_empty() => body.extend(code("()")),
+        None => body.extend(func.ret.iter().cloned()),
+    }
+    body.extend(code(";"));
+
+    body.extend(code("const __LRU_MEMOIZE_CAP: usize ="));
+    body.extend(args.cap);
+    body.extend(code(";"));
+
+    body.extend(code(if args.sync {
+        SYNC_CACHE
+    } else {
+        THREAD_LOCAL_CACHE
+    }));
+
+    let names: Vec<String> = func
+        .params
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect();
+    let clones: String = names
+        .iter()
+        .map(|name| format!("::std::clone::Clone::clone(&{}),", name))
+        .collect();
+    let (hit, store) = if ok_type.is_some() {
+        ("::std::result::Result::Ok(value)", STORE_OK)
+    } else {
+        ("value", STORE)
+    };
+    body.extend(code(&format!(
+        "let __lru_memoize_key: __LruMemoizeKey = ({clones});
+        if let ::std::option::Option::Some(value) =
+            __lru_memoize_with(|cache| cache.get(&__lru_memoize_key).cloned())
+        {{
+            return {hit};
+        }}
+        let __lru_memoize_result = __lru_memoize_inner({args});
+        {store}
+        __lru_memoize_result",
+        clones = clones,
+        hit = hit,
+        args = names.join(","),
+        store = store,
+    )));
+
+    // the memoized function, with the same signature minus any `mut` on its arguments
+    let mut params = TokenStream::new();
+    for (name, ty) in func.params {
+        params.extend(Some(TokenTree::Ident(name)));
+        params.extend(code(":"));
+        params.extend(ty);
+        params.extend(code(","));
+    }
+    let mut tokens = TokenStream::from_iter(func.prefix);
+    tokens.extend(Some(func.fn_token));
+    tokens.extend(Some(TokenTree::Ident(func.name)));
+    tokens.extend(group(Delimiter::Parenthesis, params));
+    tokens.extend(return_type(&func.ret));
+    tokens.extend(group(Delimiter::Brace, body));
+    Ok(tokens)
+}
+
+// Runs a closure on the cache of the current thread
+const THREAD_LOCAL_CACHE: &str = "
+    fn __lru_memoize_with<R>(
+        f: impl FnOnce(&mut ::lru::LruCache<__LruMemoizeKey, __LruMemoizeValue>) -> R,
+    ) -> R {
+        ::std::thread_local! {
+            static CACHE: ::std::cell::RefCell<
+                ::lru::LruCache<__LruMemoizeKey, __LruMemoizeValue>,
+            > = ::std::cell::RefCell::new(::lru::LruCache::new(__LRU_MEMOIZE_CAP));
+        }
+        CACHE.with(|cache| f(&mut cache.borrow_mut()))
+    }
+";
+
+// Runs a closure on the cache shared by all threads
+const SYNC_CACHE: &str = "
+    fn __lru_memoize_with<R>(
+        f: impl FnOnce(&mut ::lru::LruCache<__LruMemoizeKey, __LruMemoizeValue>) -> R,
+    ) -> R {
+        static CACHE: ::std::sync::Mutex<
+            ::std::option::Option<::lru::LruCache<__LruMemoizeKey, __LruMemoizeValue>>,
+        > = ::std::sync::Mutex::new(::std::option::Option::None);
+        let mut cache = CACHE.lock().unwrap_or_else(::std::sync::PoisonError::into_inner);
+        f(cache.get_or_insert_with(|| ::lru::LruCache::new(__LRU_MEMOIZE_CAP)))
+    }
+";
+
+const STORE: &str = "
+    let value = ::std::clone::Clone::clone(&__lru_memoize_result);
+    __lru_memoize_with(move |cache| {
+        cache.put(__lru_memoize_key, value);
+    });
+";
+
+const STORE_OK: &str = "
+    if let ::std::result::Result::Ok(ref value) = __lru_memoize_result {
+        let value = ::std::clone::Clone::clone(value);
+        __lru_memoize_with(move |cache| {
+            cache.put(__lru_memoize_key, value);
+        });
+    }
+";
+
+fn parse_args(attr: TokenStream) -> Result<Args, Error> {
+    let mut cap = None;
+    let mut sync = false;
+    for arg in split_commas(attr.into_iter().collect()) {
+        let name = match arg[0] {
+            TokenTree::Ident(ref ident) => ident.to_string(),
+            ref other => return Err((other.span(), "expected `cap = <expression>` or `sync`")),
+        };
+        match (name.as_str(), arg.get(1)) {
+            ("cap", Some(TokenTree::Punct(ref eq))) if eq.as_char() == '=' && arg.len() > 2 => {
+                cap = Some(TokenStream::from_iter(arg[2..].iter().cloned()));
+            }
+            ("sync", None) => sync = true,
+            _ => return Err((arg[0].span(), "expected `cap = <expression>` or `sync`")),
+        }
+    }
+    match cap {
+        Some(cap) => Ok(Args { cap, sync }),
+        None => Err((Span::call_site(), "missing `cap = <expression>`")),
+    }
+}
+
+fn parse_fn(item: TokenStream) -> Result<Function, Error> {
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+    let fn_index = tokens.iter().position(|tt| is_ident(tt, "fn")).ok_or((
+        Span::call_site(),
+        "`lru_memoize` can only be applied to functions",
+    ))?;
+    for tt in &tokens[..fn_index] {
+        if ["const", "async", "unsafe", "extern"]
+            .iter()
+            .any(|qualifier| is_ident(tt, qualifier))
+        {
+            return Err((
+                tt.span(),
+                "`lru_memoize` does not support `const`, `async`, `unsafe` or `extern` functions",
+            ));
+        }
+    }
+
+    let body = match tokens.pop() {
+        Some(TokenTree::Group(ref body)) if body.delimiter() == Delimiter::Brace => body.clone(),
+        _ => return Err((Span::call_site(), "expected a function body")),
+    };
+    let mut rest = tokens.split_off(fn_index).into_iter();
+    let fn_token = rest.next().expect("`fn` was found");
+    let name = match rest.next() {
+        Some(TokenTree::Ident(name)) => name,
+        _ => return Err((fn_token.span(), "expected a function name")),
+    };
+    let args = match rest.next() {
+        Some(TokenTree::Group(ref args)) if args.delimiter() == Delimiter::Parenthesis => {
+            args.clone()
+        }
+        Some(ref tt) if is_punct(tt, '<') => {
+            return Err((
+                tt.span(),
+                "`lru_memoize` does not support generic functions",
+            ))
+        }
+        _ => return Err((name.span(), "expected function arguments")),
+    };
+
+    let mut ret: Vec<TokenTree> = rest.collect();
+    if !ret.is_empty() {
+        if ret.len() < 3 || !is_punct(&ret[0], '-') || !is_punct(&ret[1], '>') {
+            return Err((ret[0].span(), "expected a return type"));
+        }
+        if let Some(tt) = ret.iter().find(|tt| is_ident(tt, "where")) {
+            return Err((
+                tt.span(),
+                "`lru_memoize` does not support generic functions",
+            ));
+        }
+        ret.drain(..2);
+    }
+
+    let mut params = Vec::new();
+    for arg in split_commas(args.stream().into_iter().collect()) {
+        let name_index = if is_ident(&arg[0], "mut") { 1 } else { 0 };
+        match (arg.get(name_index), arg.get(name_index + 1)) {
+            (Some(TokenTree::Ident(name)), Some(colon))
+                if is_punct(colon, ':') && arg.len() > name_index + 2 =>
+            {
+                if name.to_string() == "self" {
+                    return Err((name.span(), "`lru_memoize` does not support methods"));
+                }
+                params.push((name.clone(), arg[name_index + 2..].to_vec()));
+            }
+            _ if arg.iter().any(|tt| is_ident(tt, "self")) => {
+                return Err((arg[0].span(), "`lru_memoize` does not support methods"));
+            }
+            _ => {
+                return Err((
+                    arg[0].span(),
+                    "`lru_memoize` only supports arguments bound to identifiers",
+                ))
+            }
+        }
+    }
+
+    Ok(Function {
+        prefix: tokens,
+        fn_token,
+        name,
+        args,
+        params,
+        ret,
+        body,
+    })
+}
+
+// Returns the `T` of a return type written as `...Result<T, ...>`
+fn result_ok_type(ret: &[TokenTree]) -> Option<Vec<TokenTree>> {
+    let open = ret.iter().position(|tt| is_punct(tt, '<'))?;
+    if open == 0 || !is_ident(&ret[open - 1], "Result") || !is_punct(ret.last()?, '>') {
+        return None;
+    }
+    split_commas(ret[open + 1..ret.len() - 1].to_vec())
+        .into_iter()
+        .next()
+}
+
+// Splits tokens at the commas which are not nested in groups or angle brackets. A trailing comma
+// does not produce an empty part.
+fn split_commas(tokens: Vec<TokenTree>) -> Vec<Vec<TokenTree>> {
+    let mut parts = vec![Vec::new()];
+    let mut depth = 0usize;
+    let mut arrow = false;
+    for tt in tokens {
+        if let TokenTree::Punct(ref punct) = tt {
+            match punct.as_char() {
+                '<' => depth += 1,
+                // the `>` of `->` does not close an angle bracket
+                '>' if !arrow => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    parts.push(Vec::new());
+                    arrow = false;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        arrow = match tt {
+            TokenTree::Punct(ref punct) => {
+                punct.as_char() == '-' && punct.spacing() == Spacing::Joint
+            }
+            _ => false,
+        };
+        parts.last_mut().expect("parts is never empty").push(tt);
+    }
+    if parts.last().map_or(false, Vec::is_empty) {
+        parts.pop();
+    }
+    parts
+}
+
+fn return_type(ret: &[TokenTree]) -> TokenStream {
+    if ret.is_empty() {
+        return TokenStream::new();
+    }
+    let mut tokens = code("->");
+    tokens.extend(ret.iter().cloned());
+    tokens
+}
+
+fn is_ident(tt: &TokenTree, name: &str) -> bool {
+    match *tt {
+        TokenTree::Ident(ref ident) => ident.to_string() == name,
+        _ => false,
+    }
+}
+
+fn is_punct(tt: &TokenTree, ch: char) -> bool {
+    match *tt {
+        TokenTree::Punct(ref punct) => punct.as_char() == ch,
+        _ => false,
+    }
+}
+
+fn code(code: &str) -> TokenStream {
+    code.parse().expect("generated code is valid")
+}
+
+fn group(delimiter: Delimiter, tokens: TokenStream) -> TokenStream {
+    TokenStream::from(TokenTree::Group(Group::new(delimiter, tokens)))
+}
+
+fn compile_error(span: Span, message: &str) -> TokenStream {
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut message = Literal::string(message);
+    message.set_span(span);
+    let mut args = Group::new(
+        Delimiter::Brace,
+        TokenStream::from(TokenTree::Literal(message)),
+    );
+    args.set_span(span);
+    TokenStream::from_iter(vec![
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(args),
+    ])
+}
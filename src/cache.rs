@@ -0,0 +1,7865 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! `LruCache` and the other caches which need a global allocator, which are only available with the
+//! `alloc` feature.
+
+use alloc::alloc::Layout;
+use alloc::borrow::{Borrow, ToOwned};
+use alloc::vec::Vec;
+use allocator_api2::alloc::{Allocator, Global};
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::fmt::{Debug, Formatter};
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::iter::{FusedIterator, Rev};
+use core::marker::PhantomData;
+use core::mem::{self, replace};
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+#[cfg(target_has_atomic = "ptr")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "hashbrown")]
+use hashbrown::{HashMap, HashSet};
+#[cfg(not(feature = "hashbrown"))]
+use std::collections::{HashMap, HashSet};
+
+#[cfg(all(not(feature = "no_std"), target_has_atomic = "ptr"))]
+mod block_reader;
+#[cfg(not(feature = "no_std"))]
+mod journal;
+mod memoize;
+mod negative;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod slab;
+#[cfg(not(feature = "no_std"))]
+mod snapshot;
+#[cfg(all(not(feature = "no_std"), target_has_atomic = "ptr"))]
+mod tiered;
+mod two_level;
+#[cfg(not(feature = "no_std"))]
+mod write_back;
+mod write_through;
+
+use self::slab::{handle_reserve, RawSlab, Slab};
+
+#[cfg(all(not(feature = "no_std"), target_has_atomic = "ptr"))]
+pub use self::block_reader::{LruBlockReader, DEFAULT_PREFETCH};
+#[cfg(not(feature = "no_std"))]
+pub use self::journal::{JournalError, JournaledCache, DEFAULT_COMPACT_AFTER};
+pub use self::memoize::{MaybeCached, Memoized};
+pub use self::negative::{Lookup, NegativeCache};
+#[cfg(not(feature = "no_std"))]
+pub use self::snapshot::{Codec, LimiterKind, SnapshotError, SnapshotInfo, SnapshotLimiter};
+#[cfg(all(not(feature = "no_std"), target_has_atomic = "ptr"))]
+pub use self::tiered::{SpillError, TieredCache};
+pub use self::two_level::{CacheLevel, InclusionPolicy, TwoLevel};
+#[cfg(not(feature = "no_std"))]
+pub use self::write_back::{FlushError, Flusher, WriteBackCache};
+pub use self::write_through::{BackingStore, WriteThroughCache};
+pub use allocator_api2::collections::TryReserveError;
+use allocator_api2::collections::TryReserveErrorKind;
+
+// This type exists to allow a "blanket" Borrow impl for KeyRef without conflicting with the
+//  stdlib blanket impl
+#[repr(transparent)]
+struct KeyWrapper<K: ?Sized>(K);
+impl<K: ?Sized> KeyWrapper<K> {
+    fn from_ref(key: &K) -> &Self {
+        // safety: KeyWrapper is transparent, so casting the ref like this is allowable
+        unsafe { &*(key as *const K as *const KeyWrapper<K>) }
+    }
+}
+impl<K: ?Sized + Hash> Hash for KeyWrapper<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+impl<K: ?Sized + PartialEq> PartialEq for KeyWrapper<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq(&other.0)
+    }
+}
+impl<K: ?Sized + Eq> Eq for KeyWrapper<K> {}
+
+// Struct used to hold a key value pair. Also contains the slab indices of the previous and next
+// entries so we can maintain the entries in a linked list ordered by their use.
+struct LruEntry<K, V> {
+    key: mem::MaybeUninit<K>,
+    val: mem::MaybeUninit<V>,
+    prev: u32,
+    next: u32,
+}
+
+// The index which links to the root, which is allocated outside of the slab
+const ROOT: u32 = u32::MAX;
+impl<K, V> LruEntry<K, V> {
+    fn new(key: K, val: V) -> Self {
+        LruEntry {
+            key: mem::MaybeUninit::new(key),
+            val: mem::MaybeUninit::new(val),
+            prev: ROOT,
+            next: ROOT,
+        }
+    }
+
+    fn new_sigil() -> Self {
+        LruEntry {
+            key: mem::MaybeUninit::uninit(),
+            val: mem::MaybeUninit::uninit(),
+            prev: ROOT,
+            next: ROOT,
+        }
+    }
+}
+
+#[cfg(feature = "hashbrown")]
+pub type DefaultHasher = hashbrown::hash_map::DefaultHashBuilder;
+#[cfg(not(feature = "hashbrown"))]
+pub type DefaultHasher = std::collections::hash_map::RandomState;
+
+// Struct used to wrap entries to compare/hash by key
+struct EntryWrapper<K, V>(NonNull<LruEntry<K, V>>);
+impl<K, V> EntryWrapper<K, V> {
+    fn key(&self) -> &K {
+        unsafe { self.0.as_ref().key.assume_init_ref() }
+    }
+}
+impl<K: Hash, V> Hash for EntryWrapper<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+impl<K: PartialEq, V> PartialEq for EntryWrapper<K, V> {
+    fn eq(&self, other: &EntryWrapper<K, V>) -> bool {
+        self.key().eq(other.key())
+    }
+}
+impl<K: Eq, V> Eq for EntryWrapper<K, V> {}
+impl<K, V, Q> Borrow<KeyWrapper<Q>> for EntryWrapper<K, V>
+where
+    K: Borrow<Q>,
+    Q: ?Sized,
+{
+    fn borrow(&self) -> &KeyWrapper<Q> {
+        KeyWrapper::from_ref(self.key().borrow())
+    }
+}
+/// Specifies how new entries should be added to `LruCache`s. Used as a return by `Limiter::on_add`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AddBehavior {
+    /// Accepts the new entry by adding it to the list without evicting any existing entries.
+    Accept,
+    /// Accepts the new entry by evicting the LRU entry. If the new element is about to make the
+    /// cache oversized, this should be preferred over `Accept` because the cache then stores the
+    /// new entry in the node of the evicted one, without allocating.
+    /// Note: `Evict` is only relevant for `Limiter::on_add` calls. For `Limiter::on_update` calls,
+    /// it behaves exactly like `Accept`.
+    Evict,
+    /// Rejects the new entry because it is too big for the cache's capacity.
+    /// Note: `Limiter::on_remove` will not be called for the entry if it is rejected. Therefore, a
+    /// rejected `Limiter::on_add` should leave the limiter's internal state unchanged.
+    Reject,
+}
+/// A trait for implementing limiters, which constrain the maximum cache of an `LruCache`. Limiters
+/// may limit the number of elements, the total memory usage of the elements, or other metrics.
+///
+/// `A` is the allocator of the cache. Limiters which implement the trait for any `A: Allocator`
+/// can be used with caches created by `new_in` or `with_limiter_and_hasher_in`.
+///
+/// # Example
+///
+/// ```
+/// // This example implements a simple sum limiter
+///
+/// use std::cell::RefCell;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use lru::{AddBehavior, Limiter, LruCache};
+///
+/// /// A limiter for the sum of the cache values
+/// struct SumLimited {
+///     limit: usize,
+///     current: RefCell<usize>
+/// }
+///
+/// impl<K, S> Limiter<K, usize, S> for SumLimited {
+///     fn is_oversized(&self, cache: &LruCache<K, usize, impl Limiter<K, usize, S>, S>) -> bool {
+///         *self.current.borrow() > self.limit
+///     }
+///
+///     fn on_add(&self, cache: &LruCache<K, usize, impl Limiter<K, usize, S>, S>, key: &K, value: &usize) -> AddBehavior {
+///         if *value > self.limit {
+///             AddBehavior::Reject
+///         } else {
+///             let mut current = self.current.borrow_mut();
+///             *current += value;
+///             if *current > self.limit {
+///                 AddBehavior::Evict
+///             } else {
+///                 AddBehavior::Accept
+///             }
+///         }
+///     }
+///
+///     fn on_update(&self, cache: &LruCache<K, usize, impl Limiter<K, usize, S>, S>, old_key: &K, old_value: &usize, new_key: Option<&K>, new_value: Option<&usize>) -> AddBehavior {
+///         let mut current = self.current.borrow_mut();
+///         let mut next = *current;
+///         if let Some(new_value) = new_value {
+///             next -= old_value;
+///             next += new_value;
+///         }
+///         if next > self.limit {
+///             AddBehavior::Reject
+///         } else {
+///             *current = next;
+///             AddBehavior::Accept
+///         }
+///     }
+///
+///     fn on_remove(&self, cache: &LruCache<K, usize, impl Limiter<K, usize, S>, S>, key: &K, value: &usize) {
+///         *self.current.borrow_mut() -= value;
+///     }
+/// }
+/// ```
+pub trait Limiter<K, V, S, A: Allocator = Global> {
+    /// Returns true if the cache is currently too big.
+    fn is_oversized(&self, cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>) -> bool;
+
+    /// Called when a new element is added to the cache to update any aggregations used by the
+    /// limiter and to specify what to do with the element. See `AddBehavior` for the meanings of
+    /// the different return values.
+    fn on_add(
+        &self,
+        cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        value: &V,
+    ) -> AddBehavior;
+
+    /// Called when an element is updated in the cache update any aggregations used by the
+    /// limiter and to specify what to do with the element. See `AddBehavior` for the meanings of
+    /// the different return values.
+    ///
+    /// Note that if the key xor the value is being updated, the other will be `None`.
+    #[allow(unused_variables)]
+    fn on_update(
+        &self,
+        cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        old_key: &K,
+        old_value: &V,
+        new_key: Option<&K>,
+        new_value: Option<&V>,
+    ) -> AddBehavior;
+
+    /// Called when an element is removed from the cache to update any aggregations used by the
+    /// limiter. Note that this function is only called if the element was successfully added in
+    /// the first place (i.e. if the on_add call for the element returned `AddBehavior::Accept` or
+    /// `AddBehavior::Evict`)
+    #[allow(unused_variables)]
+    fn on_remove(
+        &self,
+        cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        value: &V,
+    ) {
+    }
+
+    /// Returns the cost which the limiter accounts for an entry. `LruCache::get_mut_recost` and
+    /// `OccupiedEntry::into_mut_recost` record it before handing out a mutable reference to the
+    /// value, and pass it to `on_recost` once the value was changed in place. Limiters whose
+    /// accounting does not depend on values can keep the default of 0.
+    #[allow(unused_variables)]
+    fn entry_cost(&self, key: &K, value: &V) -> usize {
+        0
+    }
+
+    /// Called when the value of an entry was changed in place through `LruCache::get_mut_recost`
+    /// or `OccupiedEntry::into_mut_recost`, to update any aggregations used by the limiter.
+    /// `old_cost` is what `entry_cost` returned before the change. If the cache is then oversized,
+    /// other entries are evicted. Returning `AddBehavior::Reject` removes the entry itself without
+    /// calling `on_remove`, so the limiter should stop accounting for it. The default accepts the
+    /// change.
+    #[allow(unused_variables)]
+    fn on_recost(
+        &self,
+        cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        old_cost: usize,
+        value: &V,
+    ) -> AddBehavior {
+        AddBehavior::Accept
+    }
+
+    /// Called after an entry was attached to the LRU list, either because it was added or because
+    /// it was moved, e.g. by an access. `entry` points at it in its new position. Together with
+    /// `on_detach`, this lets limiters which keep their own order of the entries, like
+    /// `GroupLimited`, follow the cache's. The default does nothing.
+    #[allow(unused_variables)]
+    fn on_attach<'a>(
+        &self,
+        cache: &'a LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        entry: Cursor<'a, K, V>,
+    ) {
+    }
+
+    /// Called before an entry is detached from the LRU list, either because it is removed or
+    /// because it is about to be moved. `entry` points at it in its old position. The default does
+    /// nothing.
+    #[allow(unused_variables)]
+    fn on_detach<'a>(
+        &self,
+        cache: &'a LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        entry: Cursor<'a, K, V>,
+    ) {
+    }
+
+    /// Called while the cache is oversized to choose which entry to evict next. `lru` is a cursor
+    /// pointing at the least recently used entry, which can be walked towards more recently used
+    /// entries with `Cursor::prev`. Returns a cursor pointing at the entry to evict, or `None` to
+    /// evict the least recently used entry (the default).
+    ///
+    /// This is honoured by every eviction, including those made by `push`, `put`,
+    /// `OccupiedEntry::take_evicted`, `CursorMut`, `LruCache::limiter_mut` and `LruCache::resize`.
+    /// An entry which is currently being inserted or accessed is never evicted: if it is chosen,
+    /// the least recently used of the other entries is evicted instead. Returning a cursor which
+    /// points into another cache panics.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // This example evicts the least recently used entry which is big enough to make the cache
+    /// //  fit again on its own, rather than evicting several smaller entries
+    ///
+    /// use lru::{AddBehavior, CostLimited, Cursor, Limiter, LruCache};
+    ///
+    /// struct BestFit(CostLimited<(fn(&u32) -> usize, fn(&usize) -> usize)>);
+    ///
+    /// impl<S> Limiter<u32, usize, S> for BestFit {
+    ///     fn is_oversized(&self, cache: &LruCache<u32, usize, impl Limiter<u32, usize, S>, S>) -> bool {
+    ///         Limiter::<u32, usize, S>::is_oversized(&self.0, cache)
+    ///     }
+    ///
+    ///     fn on_add(&self, cache: &LruCache<u32, usize, impl Limiter<u32, usize, S>, S>, key: &u32, value: &usize) -> AddBehavior {
+    ///         self.0.on_add(cache, key, value)
+    ///     }
+    ///
+    ///     fn on_update(&self, cache: &LruCache<u32, usize, impl Limiter<u32, usize, S>, S>, old_key: &u32, old_value: &usize, new_key: Option<&u32>, new_value: Option<&usize>) -> AddBehavior {
+    ///         self.0.on_update(cache, old_key, old_value, new_key, new_value)
+    ///     }
+    ///
+    ///     fn on_remove(&self, cache: &LruCache<u32, usize, impl Limiter<u32, usize, S>, S>, key: &u32, value: &usize) {
+    ///         self.0.on_remove(cache, key, value)
+    ///     }
+    ///
+    ///     fn select_victim<'a>(&self, _cache: &'a LruCache<u32, usize, impl Limiter<u32, usize, S>, S>, lru: Cursor<'a, u32, usize>) -> Option<Cursor<'a, u32, usize>> {
+    ///         let overflow = self.0.current() - self.0.limit();
+    ///         let mut cursor = Some(lru);
+    ///         while let Some(entry) = cursor {
+    ///             if *entry.value() >= overflow {
+    ///                 return Some(entry);
+    ///             }
+    ///             cursor = entry.prev();
+    ///         }
+    ///         None
+    ///     }
+    /// }
+    ///
+    /// let mut cache = LruCache::with_limiter(BestFit(CostLimited::with_func(10, (|_| 0, |v| *v))));
+    /// cache.put(1, 2);
+    /// cache.put(2, 2);
+    /// cache.put(3, 5);
+    /// assert_eq!(cache.push(4, 4), Some((3, 5)));
+    /// assert_eq!(cache.len(), 3);
+    /// ```
+    #[allow(unused_variables)]
+    fn select_victim<'a>(
+        &self,
+        cache: &'a LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        lru: Cursor<'a, K, V>,
+    ) -> Option<Cursor<'a, K, V>> {
+        None
+    }
+}
+
+/// A `Limiter` which can create an empty limiter with the same configuration, for another cache
+/// such as the one returned by `LruCache::split_off_lru`.
+pub trait FreshLimiter {
+    /// Returns a limiter with the same limits as this one, which does not account for any entries.
+    fn fresh(&self) -> Self;
+}
+/// A `Limiter` which does not limit the max size of the cache.
+#[derive(Debug, Copy, Clone)]
+pub struct Unlimited;
+impl FreshLimiter for Unlimited {
+    fn fresh(&self) -> Self {
+        Unlimited
+    }
+}
+impl<K, V, S, A: Allocator> Limiter<K, V, S, A> for Unlimited {
+    fn is_oversized(&self, _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>) -> bool {
+        false
+    }
+
+    fn on_add(
+        &self,
+        _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        _key: &K,
+        _value: &V,
+    ) -> AddBehavior {
+        AddBehavior::Accept
+    }
+
+    fn on_update(
+        &self,
+        _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        _old_key: &K,
+        _old_value: &V,
+        _new_key: Option<&K>,
+        _new_value: Option<&V>,
+    ) -> AddBehavior {
+        AddBehavior::Accept
+    }
+}
+/// A `Limiter` which limits the max len of the cache.
+#[derive(Debug, Copy, Clone)]
+pub struct SizeLimited(usize);
+impl SizeLimited {
+    /// Creates a new `SizeLimited` with the given limit
+    pub fn new(limit: usize) -> Self {
+        Self(limit)
+    }
+
+    /// Gets the current limit
+    pub fn limit(&self) -> usize {
+        self.0
+    }
+
+    /// Sets the size limit
+    pub fn set_limit(&mut self, limit: usize) {
+        self.0 = limit;
+    }
+}
+impl FreshLimiter for SizeLimited {
+    fn fresh(&self) -> Self {
+        *self
+    }
+}
+impl<K: Hash + Eq, V, S: BuildHasher, A: Allocator> Limiter<K, V, S, A> for SizeLimited {
+    fn is_oversized(&self, cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>) -> bool {
+        cache.len() > self.0
+    }
+
+    fn on_add(
+        &self,
+        cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        _key: &K,
+        _value: &V,
+    ) -> AddBehavior {
+        if self.0 == 0 {
+            AddBehavior::Reject
+        } else if cache.len() >= self.0 {
+            AddBehavior::Evict
+        } else {
+            AddBehavior::Accept
+        }
+    }
+
+    fn on_update(
+        &self,
+        _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        _old_key: &K,
+        _old_value: &V,
+        _new_key: Option<&K>,
+        _new_value: Option<&V>,
+    ) -> AddBehavior {
+        // updates don't change length. Accept unconditionally
+        AddBehavior::Accept
+    }
+}
+/// A trait for implementing arbitrary cost functions for `CostLimited`. Note that you can create a
+/// `CostFn` from a pair of closures, so it is often not necessary to implement this trait directly.
+///
+/// It is a logic error for the cost of a key or value to change while it is stored in an `LruCache`
+/// (similar to changing the hash of a key while it is in a `HashMap`), except through
+/// `LruCache::get_mut_recost` or `OccupiedEntry::into_mut_recost`, which re-cost the value once it
+/// was changed. The behavior resulting from such a logic error is not specified, but will be
+/// encapsulated to the `LruCache` that observed the logic error and not result in undefined
+/// behavior. This could include panics, incorrect results, aborts, memory leaks, and
+/// non-termination.
+///
+/// # Example
+///
+/// ```
+/// use lru::{CostLimited, LruCache};
+/// let limiter = CostLimited::with_func(100, (
+///     |key: &String| key.len(),
+///     |value: &usize| *value
+/// ));
+/// let mut cache = LruCache::with_limiter(limiter);
+/// cache.put("a".to_string(), 90);  // costs 91
+/// cache.put("b".to_string(), 10);  // costs 11, evicts "a"
+/// assert_eq!(cache.get("a"), None)
+/// ```
+pub trait CostFn<K, V> {
+    /// Gets the cost of a key in the cache
+    fn key_cost(&self, key: &K) -> usize;
+    /// Gets the cost of a value in the cache
+    fn value_cost(&self, value: &V) -> usize;
+}
+impl<K, V, F: Fn(&K) -> usize, G: Fn(&V) -> usize> CostFn<K, V> for (F, G) {
+    fn key_cost(&self, key: &K) -> usize {
+        (self.0)(key)
+    }
+
+    fn value_cost(&self, value: &V) -> usize {
+        (self.1)(value)
+    }
+}
+
+// Counter holding the current total cost of a `CostLimited` or `LocalCostLimited`
+trait CostCounter {
+    fn get(&self) -> usize;
+
+    // Atomically replaces the current cost with `func(current)`, returning the new cost
+    fn update(&self, func: impl FnMut(usize) -> usize) -> usize;
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl CostCounter for AtomicUsize {
+    fn get(&self) -> usize {
+        self.load(Ordering::Acquire)
+    }
+
+    fn update(&self, mut func: impl FnMut(usize) -> usize) -> usize {
+        let mut prev = self.load(Ordering::Acquire);
+        loop {
+            let next = func(prev);
+            match self.compare_exchange_weak(prev, next, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => break next,
+                Err(new_prev) => prev = new_prev,
+            }
+        }
+    }
+}
+impl CostCounter for Cell<usize> {
+    fn get(&self) -> usize {
+        Cell::get(self)
+    }
+
+    fn update(&self, mut func: impl FnMut(usize) -> usize) -> usize {
+        let next = func(Cell::get(self));
+        self.set(next);
+        next
+    }
+}
+
+// The limiter logic shared by `CostLimited` and `LocalCostLimited`
+mod cost {
+    use super::{AddBehavior, CostCounter, CostFn};
+
+    pub const MAX_LIMIT: usize = usize::MAX / 2;
+
+    pub fn check_limit(limit: usize) {
+        if limit > MAX_LIMIT {
+            panic!("Limit ({}) cannot exceed {}", limit, MAX_LIMIT);
+        }
+    }
+
+    fn add_cost(current: usize, cost: usize) -> usize {
+        current.checked_add(cost).expect(
+            "Cost overflowed. This shouldn't be possible because of the MAX_LIMIT. This is a bug",
+        )
+    }
+
+    fn sub_cost(current: usize, cost: usize) -> usize {
+        current
+            .checked_sub(cost)
+            .expect("Key or value cost changed between insertion and removal")
+    }
+
+    fn update_cost(
+        limit: usize,
+        current: &impl CostCounter,
+        func: impl FnMut(usize) -> usize,
+    ) -> AddBehavior {
+        if current.update(func) > limit {
+            AddBehavior::Evict
+        } else {
+            AddBehavior::Accept
+        }
+    }
+
+    pub fn on_add<K, V>(
+        limit: usize,
+        current: &impl CostCounter,
+        cost_func: &impl CostFn<K, V>,
+        key: &K,
+        value: &V,
+    ) -> AddBehavior {
+        let cost = cost_func.key_cost(key) + cost_func.value_cost(value);
+        if cost > limit {
+            return AddBehavior::Reject;
+        }
+        update_cost(limit, current, |current| add_cost(current, cost))
+    }
+
+    pub fn on_update<K, V>(
+        limit: usize,
+        current: &impl CostCounter,
+        cost_func: &impl CostFn<K, V>,
+        old_key: &K,
+        old_value: &V,
+        new_key: Option<&K>,
+        new_value: Option<&V>,
+    ) -> AddBehavior {
+        let mut prev_cost = 0;
+        let mut next_cost = 0;
+        if let Some(new_key) = new_key {
+            prev_cost += cost_func.key_cost(old_key);
+            next_cost += cost_func.key_cost(new_key);
+        }
+        if let Some(new_value) = new_value {
+            prev_cost += cost_func.value_cost(old_value);
+            next_cost += cost_func.value_cost(new_value);
+        }
+        update_cost(limit, current, |current| {
+            add_cost(sub_cost(current, prev_cost), next_cost)
+        })
+    }
+
+    pub fn on_remove<K, V>(
+        limit: usize,
+        current: &impl CostCounter,
+        cost_func: &impl CostFn<K, V>,
+        key: &K,
+        value: &V,
+    ) {
+        let cost = entry_cost(cost_func, key, value);
+        update_cost(limit, current, |current| sub_cost(current, cost));
+    }
+
+    pub fn entry_cost<K, V>(cost_func: &impl CostFn<K, V>, key: &K, value: &V) -> usize {
+        cost_func.key_cost(key) + cost_func.value_cost(value)
+    }
+
+    pub fn on_recost<K, V>(
+        limit: usize,
+        current: &impl CostCounter,
+        cost_func: &impl CostFn<K, V>,
+        key: &K,
+        old_cost: usize,
+        value: &V,
+    ) -> AddBehavior {
+        let cost = entry_cost(cost_func, key, value);
+        if cost > limit {
+            // the entry is removed without `on_remove`, so its old cost is released here
+            update_cost(limit, current, |current| sub_cost(current, old_cost));
+            return AddBehavior::Reject;
+        }
+        update_cost(limit, current, |current| {
+            add_cost(sub_cost(current, old_cost), cost)
+        })
+    }
+}
+/// A `Limiter` which limits the max "cost" of the cache based on an arbitrary cost function.
+///
+/// The current cost is tracked atomically, since `Limiter` methods only take `&self`. If the
+/// cache is only ever used from a single thread, or the target has no atomics, see
+/// `LocalCostLimited`.
+///
+/// `FreshLimiter::fresh` copies its limit and cost function, but not its current cost, for another
+/// cache such as the one returned by `LruCache::split_off_lru`.
+#[cfg(target_has_atomic = "ptr")]
+#[derive(Debug)]
+pub struct CostLimited<F> {
+    limit: usize,
+    current: AtomicUsize,
+    cost_func: F,
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<F: Default> CostLimited<F> {
+    /// Creates a new `CostLimited` with the given limit and the default value of the cost function
+    pub fn new(limit: usize) -> Self {
+        Self::with_func(limit, F::default())
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<F> CostLimited<F> {
+    /// The maximum limit allowed by `CostLimited`
+    pub const MAX_LIMIT: usize = cost::MAX_LIMIT;
+
+    /// Creates a new `CostLimited` with the given limit and cost function
+    pub fn with_func(limit: usize, cost_func: F) -> Self {
+        let mut this = Self {
+            limit: 0,
+            current: AtomicUsize::new(0),
+            cost_func,
+        };
+        this.set_limit(limit);
+        this
+    }
+
+    /// Gets the current limit
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Sets the limit
+    pub fn set_limit(&mut self, limit: usize) {
+        cost::check_limit(limit);
+        self.limit = limit;
+    }
+
+    /// Gets the current total cost of the cache.
+    pub fn current(&self) -> usize {
+        self.current.get()
+    }
+
+    /// Gets a ref to the cost function
+    pub fn cost_func(&self) -> &F {
+        &self.cost_func
+    }
+
+    /// Gets a mutable ref to the cost function
+    pub fn cost_func_mut(&mut self) -> &mut F {
+        &mut self.cost_func
+    }
+
+    /// Consumes the `CostLimited` and returns the inner cost function
+    pub fn into_cost_func(self) -> F {
+        self.cost_func
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<F: Clone> FreshLimiter for CostLimited<F> {
+    fn fresh(&self) -> Self {
+        Self::with_func(self.limit, self.cost_func.clone())
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<K, V, S, A: Allocator, F: CostFn<K, V>> Limiter<K, V, S, A> for CostLimited<F> {
+    fn is_oversized(&self, _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>) -> bool {
+        self.current() > self.limit
+    }
+
+    fn on_add(
+        &self,
+        _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        value: &V,
+    ) -> AddBehavior {
+        cost::on_add(self.limit, &self.current, &self.cost_func, key, value)
+    }
+
+    fn on_update(
+        &self,
+        _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        old_key: &K,
+        old_value: &V,
+        new_key: Option<&K>,
+        new_value: Option<&V>,
+    ) -> AddBehavior {
+        cost::on_update(
+            self.limit,
+            &self.current,
+            &self.cost_func,
+            old_key,
+            old_value,
+            new_key,
+            new_value,
+        )
+    }
+
+    fn on_remove(
+        &self,
+        _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        value: &V,
+    ) {
+        cost::on_remove(self.limit, &self.current, &self.cost_func, key, value)
+    }
+
+    fn entry_cost(&self, key: &K, value: &V) -> usize {
+        cost::entry_cost(&self.cost_func, key, value)
+    }
+
+    fn on_recost(
+        &self,
+        _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        old_cost: usize,
+        value: &V,
+    ) -> AddBehavior {
+        cost::on_recost(
+            self.limit,
+            &self.current,
+            &self.cost_func,
+            key,
+            old_cost,
+            value,
+        )
+    }
+}
+/// A `Limiter` which limits the max "cost" of the cache based on an arbitrary cost function.
+///
+/// Behaves exactly like `CostLimited`, but tracks the current cost in a `Cell` rather than an
+/// atomic. This makes it cheaper in single-threaded hot loops and usable on targets without
+/// atomics, at the cost of the limiter (and therefore the cache) not being `Sync`.
+///
+/// Like for `CostLimited`, `FreshLimiter::fresh` copies the limit and cost function but not the
+/// current cost.
+///
+/// # Example
+///
+/// ```
+/// use lru::{LocalCostLimited, LruCache};
+/// let limiter = LocalCostLimited::with_func(100, (
+///     |key: &String| key.len(),
+///     |value: &usize| *value
+/// ));
+/// let mut cache = LruCache::with_limiter(limiter);
+/// cache.put("a".to_string(), 90);  // costs 91
+/// cache.put("b".to_string(), 10);  // costs 11, evicts "a"
+/// assert_eq!(cache.get("a"), None);
+/// assert_eq!(cache.limiter().current(), 11);
+/// ```
+#[derive(Debug)]
+pub struct LocalCostLimited<F> {
+    limit: usize,
+    current: Cell<usize>,
+    cost_func: F,
+}
+impl<F: Default> LocalCostLimited<F> {
+    /// Creates a new `LocalCostLimited` with the given limit and the default value of the cost
+    /// function
+    pub fn new(limit: usize) -> Self {
+        Self::with_func(limit, F::default())
+    }
+}
+impl<F> LocalCostLimited<F> {
+    /// The maximum limit allowed by `LocalCostLimited`
+    pub const MAX_LIMIT: usize = cost::MAX_LIMIT;
+
+    /// Creates a new `LocalCostLimited` with the given limit and cost function
+    pub fn with_func(limit: usize, cost_func: F) -> Self {
+        let mut this = Self {
+            limit: 0,
+            current: Cell::new(0),
+            cost_func,
+        };
+        this.set_limit(limit);
+        this
+    }
+
+    /// Gets the current limit
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Sets the limit
+    pub fn set_limit(&mut self, limit: usize) {
+        cost::check_limit(limit);
+        self.limit = limit;
+    }
+
+    /// Gets the current total cost of the cache.
+    pub fn current(&self) -> usize {
+        self.current.get()
+    }
+
+    /// Gets a ref to the cost function
+    pub fn cost_func(&self) -> &F {
+        &self.cost_func
+    }
+
+    /// Gets a mutable ref to the cost function
+    pub fn cost_func_mut(&mut self) -> &mut F {
+        &mut self.cost_func
+    }
+
+    /// Consumes the `LocalCostLimited` and returns the inner cost function
+    pub fn into_cost_func(self) -> F {
+        self.cost_func
+    }
+}
+impl<F: Clone> FreshLimiter for LocalCostLimited<F> {
+    fn fresh(&self) -> Self {
+        Self::with_func(self.limit, self.cost_func.clone())
+    }
+}
+impl<K, V, S, A: Allocator, F: CostFn<K, V>> Limiter<K, V, S, A> for LocalCostLimited<F> {
+    fn is_oversized(&self, _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>) -> bool {
+        self.current() > self.limit
+    }
+
+    fn on_add(
+        &self,
+        _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        value: &V,
+    ) -> AddBehavior {
+        cost::on_add(self.limit, &self.current, &self.cost_func, key, value)
+    }
+
+    fn on_update(
+        &self,
+        _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        old_key: &K,
+        old_value: &V,
+        new_key: Option<&K>,
+        new_value: Option<&V>,
+    ) -> AddBehavior {
+        cost::on_update(
+            self.limit,
+            &self.current,
+            &self.cost_func,
+            old_key,
+            old_value,
+            new_key,
+            new_value,
+        )
+    }
+
+    fn on_remove(
+        &self,
+        _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        value: &V,
+    ) {
+        cost::on_remove(self.limit, &self.current, &self.cost_func, key, value)
+    }
+
+    fn entry_cost(&self, key: &K, value: &V) -> usize {
+        cost::entry_cost(&self.cost_func, key, value)
+    }
+
+    fn on_recost(
+        &self,
+        _cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        old_cost: usize,
+        value: &V,
+    ) -> AddBehavior {
+        cost::on_recost(
+            self.limit,
+            &self.current,
+            &self.cost_func,
+            key,
+            old_cost,
+            value,
+        )
+    }
+}
+/// A `Limiter` which gives every group of keys its own quota on top of a global limiter. Useful
+/// for multi-tenant caches, where one tenant must not be able to evict everyone else's entries.
+///
+/// Keys are assigned to groups by the group function. Each group may hold at most `group_limit`
+/// entries: when a group goes over its quota, the least recently used entry *of that group* is
+/// evicted rather than the cache's LRU entry. The global budget is enforced by the inner limiter,
+/// which evicts as usual.
+///
+/// Every group keeps its entries linked in LRU order, following the cache through
+/// `Limiter::on_attach` and `Limiter::on_detach`, so evictions due to a group quota take constant
+/// time. Entries inserted or moved into the middle of the LRU list, e.g. with `CursorMut` or
+/// `OccupiedEntry::move_before`, are linked by walking towards the LRU end to the next entry of the
+/// same group. Limiters which wrap a `GroupLimited` must forward both hooks to it. It is a logic
+/// error for equal keys to be assigned to different groups.
+///
+/// `FreshLimiter::fresh` copies the limits and the group function, but does not count any entries.
+///
+/// # Example
+///
+/// ```
+/// use lru::{GroupLimited, LruCache, SizeLimited};
+/// let limiter = GroupLimited::new(2, SizeLimited::new(4), |key: &(&'static str, u32)| key.0);
+/// let mut cache = LruCache::with_limiter(limiter);
+///
+/// cache.put(("bob", 1), "b1");
+/// cache.put(("alice", 1), "a1");
+/// cache.put(("alice", 2), "a2");
+/// // alice is over her quota, so her oldest entry is evicted rather than bob's
+/// cache.put(("alice", 3), "a3");
+/// assert!(cache.contains(&("bob", 1)));
+/// assert!(!cache.contains(&("alice", 1)));
+/// assert_eq!(cache.limiter().group_len(&"alice"), 2);
+/// ```
+#[derive(Debug)]
+pub struct GroupLimited<G, L, F> {
+    group_limit: usize,
+    limiter: L,
+    group_func: F,
+    groups: RefCell<Groups<G>>,
+}
+
+// The groups of a `GroupLimited`. Groups are numbered so that the entries and the over quota
+//  groups can refer to them without cloning the group
+#[derive(Debug)]
+struct Groups<G> {
+    // ids of the groups which have entries
+    ids: HashMap<G, usize>,
+    // groups by id, including freed ones
+    groups: Vec<Group>,
+    // ids of freed groups, to be reused
+    free: Vec<usize>,
+    // the group of every attached entry, by the index of its node
+    links: HashMap<u32, GroupLink>,
+    // ids of the groups which are over their quota
+    over: HashSet<usize>,
+}
+
+#[derive(Debug)]
+struct Group {
+    // number of entries counted by `on_add`, which may briefly differ from the number of attached
+    //  entries while an entry is being added or removed
+    len: usize,
+    // node indices of the most and least recently used attached entries, or ROOT
+    head: u32,
+    tail: u32,
+}
+
+#[derive(Debug)]
+struct GroupLink {
+    group: usize,
+    // generation of the node, to check that the entry is still there when evicting it
+    generation: u64,
+    // node indices of the next more and less recently used entries of the group, or ROOT
+    prev: u32,
+    next: u32,
+}
+
+impl<G: Hash + Eq> Groups<G> {
+    fn new() -> Self {
+        Groups {
+            ids: HashMap::default(),
+            groups: Vec::new(),
+            free: Vec::new(),
+            links: HashMap::default(),
+            over: HashSet::default(),
+        }
+    }
+
+    // Gets the id of `group`, adding it if it has no entries yet
+    fn id_or_insert(&mut self, group: G) -> usize {
+        if let Some(&id) = self.ids.get(&group) {
+            return id;
+        }
+        let empty = Group {
+            len: 0,
+            head: ROOT,
+            tail: ROOT,
+        };
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.groups[id] = empty;
+                id
+            }
+            None => {
+                self.groups.push(empty);
+                self.groups.len() - 1
+            }
+        };
+        self.ids.insert(group, id);
+        id
+    }
+
+    // Frees the group with the given id once it neither counts nor links any entries
+    fn release(&mut self, id: usize, group: &G) {
+        let Group { len, head, .. } = self.groups[id];
+        if len == 0 && head == ROOT {
+            self.ids.remove(group);
+            self.free.push(id);
+        }
+    }
+}
+
+impl<G: Hash + Eq, L, F> GroupLimited<G, L, F> {
+    /// Creates a new `GroupLimited` which allows at most `group_limit` entries per group, in
+    /// addition to the limits of `limiter`. `group_func` gets the group of a key.
+    pub fn new(group_limit: usize, limiter: L, group_func: F) -> Self {
+        Self {
+            group_limit,
+            limiter,
+            group_func,
+            groups: RefCell::new(Groups::new()),
+        }
+    }
+
+    /// Gets the per-group limit
+    pub fn group_limit(&self) -> usize {
+        self.group_limit
+    }
+
+    /// Sets the per-group limit
+    pub fn set_group_limit(&mut self, group_limit: usize) {
+        self.group_limit = group_limit;
+        let Groups {
+            ids, groups, over, ..
+        } = self.groups.get_mut();
+        *over = ids
+            .values()
+            .copied()
+            .filter(|&id| groups[id].len > group_limit)
+            .collect();
+    }
+
+    /// Gets the number of entries in the cache which belong to the given group
+    pub fn group_len(&self, group: &G) -> usize {
+        let groups = self.groups.borrow();
+        groups.ids.get(group).map_or(0, |&id| groups.groups[id].len)
+    }
+
+    /// Gets a ref to the inner (global) limiter
+    pub fn limiter(&self) -> &L {
+        &self.limiter
+    }
+
+    /// Gets a mutable ref to the inner (global) limiter
+    pub fn limiter_mut(&mut self) -> &mut L {
+        &mut self.limiter
+    }
+
+    /// Gets a ref to the group function
+    pub fn group_func(&self) -> &F {
+        &self.group_func
+    }
+
+    // Stops counting a removed entry in its group
+    fn remove_from_group<K>(&self, key: &K)
+    where
+        F: Fn(&K) -> G,
+    {
+        let group = (self.group_func)(key);
+        let mut groups = self.groups.borrow_mut();
+        let id = *groups
+            .ids
+            .get(&group)
+            .expect("Key group changed between insertion and removal");
+        groups.groups[id].len -= 1;
+        if groups.groups[id].len == self.group_limit {
+            groups.over.remove(&id);
+        }
+        groups.release(id, &group);
+    }
+}
+impl<G: Hash + Eq, L: FreshLimiter, F: Clone> FreshLimiter for GroupLimited<G, L, F> {
+    fn fresh(&self) -> Self {
+        Self::new(
+            self.group_limit,
+            self.limiter.fresh(),
+            self.group_func.clone(),
+        )
+    }
+}
+impl<K, V, S, A, G, L, F> Limiter<K, V, S, A> for GroupLimited<G, L, F>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+    A: Allocator,
+    G: Hash + Eq,
+    L: Limiter<K, V, S, A>,
+    F: Fn(&K) -> G,
+{
+    fn is_oversized(&self, cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>) -> bool {
+        !self.groups.borrow().over.is_empty() || self.limiter.is_oversized(cache)
+    }
+
+    fn on_add(
+        &self,
+        cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        value: &V,
+    ) -> AddBehavior {
+        if self.group_limit == 0 {
+            return AddBehavior::Reject;
+        }
+        let behavior = self.limiter.on_add(cache, key, value);
+        if behavior == AddBehavior::Reject {
+            return behavior;
+        }
+        let mut groups = self.groups.borrow_mut();
+        let id = groups.id_or_insert((self.group_func)(key));
+        groups.groups[id].len += 1;
+        if groups.groups[id].len > self.group_limit {
+            groups.over.insert(id);
+            AddBehavior::Evict
+        } else {
+            behavior
+        }
+    }
+
+    fn on_update(
+        &self,
+        cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        old_key: &K,
+        old_value: &V,
+        new_key: Option<&K>,
+        new_value: Option<&V>,
+    ) -> AddBehavior {
+        // equal keys always share a group, so updates never change the group counts
+        self.limiter
+            .on_update(cache, old_key, old_value, new_key, new_value)
+    }
+
+    fn on_remove(
+        &self,
+        cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        value: &V,
+    ) {
+        self.limiter.on_remove(cache, key, value);
+        self.remove_from_group(key);
+    }
+
+    fn entry_cost(&self, key: &K, value: &V) -> usize {
+        self.limiter.entry_cost(key, value)
+    }
+
+    fn on_recost(
+        &self,
+        cache: &LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        key: &K,
+        old_cost: usize,
+        value: &V,
+    ) -> AddBehavior {
+        let behavior = self.limiter.on_recost(cache, key, old_cost, value);
+        if behavior == AddBehavior::Reject {
+            // the entry is removed without `on_remove`
+            self.remove_from_group(key);
+        }
+        behavior
+    }
+
+    fn on_attach<'a>(
+        &self,
+        cache: &'a LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        entry: Cursor<'a, K, V>,
+    ) {
+        self.limiter.on_attach(cache, entry);
+        let mut groups = self.groups.borrow_mut();
+        let id = match groups.ids.get(&(self.group_func)(entry.key())) {
+            Some(&id) => id,
+            // not counted by this limiter
+            None => return,
+        };
+        let (index, generation, node) = unsafe {
+            (
+                cache.nodes.index(entry.node),
+                cache.nodes.generation(entry.node),
+                entry.node.as_ref(),
+            )
+        };
+
+        // find the entry of the group which becomes the next less recently used one
+        let next = if node.prev == ROOT {
+            groups.groups[id].head
+        } else {
+            let mut next = node.next;
+            while next != ROOT && groups.links.get(&next).map(|link| link.group) != Some(id) {
+                next = unsafe { cache.nodes.ptr(next).as_ref().next };
+            }
+            next
+        };
+        let prev = match groups.links.get(&next) {
+            Some(link) => link.prev,
+            None => groups.groups[id].tail,
+        };
+
+        match groups.links.get_mut(&prev) {
+            Some(link) => link.next = index,
+            None => groups.groups[id].head = index,
+        }
+        match groups.links.get_mut(&next) {
+            Some(link) => link.prev = index,
+            None => groups.groups[id].tail = index,
+        }
+        let link = GroupLink {
+            group: id,
+            generation,
+            prev,
+            next,
+        };
+        groups.links.insert(index, link);
+    }
+
+    fn on_detach<'a>(
+        &self,
+        cache: &'a LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        entry: Cursor<'a, K, V>,
+    ) {
+        self.limiter.on_detach(cache, entry);
+        let mut groups = self.groups.borrow_mut();
+        let index = unsafe { cache.nodes.index(entry.node) };
+        let GroupLink {
+            group, prev, next, ..
+        } = match groups.links.remove(&index) {
+            Some(link) => link,
+            None => return,
+        };
+        match groups.links.get_mut(&prev) {
+            Some(link) => link.next = next,
+            None => groups.groups[group].head = next,
+        }
+        match groups.links.get_mut(&next) {
+            Some(link) => link.prev = prev,
+            None => groups.groups[group].tail = prev,
+        }
+        groups.release(group, &(self.group_func)(entry.key()));
+    }
+
+    fn select_victim<'a>(
+        &self,
+        cache: &'a LruCache<K, V, impl Limiter<K, V, S, A>, S, A>,
+        lru: Cursor<'a, K, V>,
+    ) -> Option<Cursor<'a, K, V>> {
+        let groups = self.groups.borrow();
+        let id = match groups.over.iter().next() {
+            Some(&id) => id,
+            None => return self.limiter.select_victim(cache, lru),
+        };
+        // the least recently used entry of an over quota group
+        let tail = groups.groups[id].tail;
+        let node = cache.nodes.get(tail, groups.links.get(&tail)?.generation)?;
+        Some(Cursor::new(lru.root, node, lru.nodes))
+    }
+}
+
+/// A trait for implementing "keys" into an LruCache entry. Used to customize how to get a ref for
+/// lookup. Note that implementing this trait only allows entry lookup. To support insertion as
+/// well, see `InsertionKey`.
+//noinspection RsSelfConvention
+pub trait Key {
+    /// Type of the ref used for lookup.
+    type Key: ?Sized + Hash + Eq;
+
+    /// Gets this key as a ref.
+    fn as_ref(this: &Self) -> &Self::Key;
+}
+
+/// A trait for implementing keys which support insertion (by conversion into the "real" key type).
+//noinspection RsSelfConvention
+pub trait InsertionKey<K>: Key {
+    /// Converts this key into the "real" key type.
+    fn into_owned(this: Self) -> K;
+}
+/// A wrapper for entry lookup via owned key. Allows efficient insertion without cloning.
+#[derive(Hash, Eq, PartialEq)]
+pub struct OwnedKey<K>(pub K);
+impl<K: Hash + Eq> Key for OwnedKey<K> {
+    type Key = K;
+
+    fn as_ref(this: &Self) -> &Self::Key {
+        &this.0
+    }
+}
+impl<K: Hash + Eq> InsertionKey<K> for OwnedKey<K> {
+    fn into_owned(this: Self) -> K {
+        this.0
+    }
+}
+impl<K: Debug> Debug for OwnedKey<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+/// A wrapper for entry lookup via borrowed ref. Allows efficient lookup without cloning.
+#[derive(Hash, Eq, PartialEq)]
+pub struct BorrowedKey<'a, Q: ?Sized>(pub &'a Q);
+impl<'a, Q: ?Sized + Hash + Eq> Key for BorrowedKey<'a, Q> {
+    type Key = Q;
+
+    fn as_ref(this: &Self) -> &Self::Key {
+        this.0
+    }
+}
+impl<'a, K: Borrow<Q>, Q: ?Sized + Hash + Eq + ToOwned<Owned = K>> InsertionKey<K>
+    for BorrowedKey<'a, Q>
+{
+    fn into_owned(this: Self) -> Q::Owned {
+        this.0.to_owned()
+    }
+}
+impl<'a, Q: ?Sized + Debug> Debug for BorrowedKey<'a, Q> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// Used to store either the OccupiedEntry's creation key or the evicted entry, since these two
+//  cannot coexist
+enum OccupiedExtra<K, V, Q> {
+    Key(Option<Q>),
+    Evicted(Option<(K, V)>),
+}
+/// A view into an occupied entry in an `LruCache`. It is part of the `Entry` enum.
+pub struct OccupiedEntry<
+    'a,
+    K: Hash + Eq,
+    V,
+    Q = OwnedKey<K>,
+    L: Limiter<K, V, S, A> = SizeLimited,
+    S: BuildHasher = DefaultHasher,
+    A: Allocator = Global,
+> {
+    cache: &'a mut LruCache<K, V, L, S, A>,
+    node: NonNull<LruEntry<K, V>>,
+    extra: OccupiedExtra<K, V, Q>,
+}
+impl<'a, K: Hash + Eq, V, Q, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator>
+    OccupiedEntry<'a, K, V, Q, L, S, A>
+{
+    /// Gets a reference to the key in the entry.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry(1).or_insert("a");
+    /// assert_eq!(cache.entry(1).key(), &1);
+    /// ```
+    pub fn key(&self) -> &K {
+        unsafe { self.node.as_ref().key.assume_init_ref() }
+    }
+
+    /// Returns a handle to the entry, which can be used to access it later without hashing its
+    /// key. See `EntryHandle`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// let handle = cache.entry(1).insert("a").handle();
+    /// assert_eq!(cache.get_by_handle(&handle), Some(&"a"));
+    /// ```
+    pub fn handle(&self) -> EntryHandle {
+        let nodes = &self.cache.nodes;
+        EntryHandle {
+            slab: nodes.id(),
+            index: unsafe { nodes.index(self.node) },
+            generation: unsafe { nodes.generation(self.node) },
+        }
+    }
+
+    fn key_mut(&mut self) -> &mut K {
+        unsafe { self.node.as_mut().key.assume_init_mut() }
+    }
+
+    /// Gets a reference to the value in the entry. Unlike `get` does not update the LRU list so the
+    /// key's position will be unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry(1).or_insert("a");
+    /// if let Entry::Occupied(entry) = cache.entry(1) {
+    ///     assert_eq!(entry.peek(), &"a");
+    /// };
+    /// ```
+    pub fn peek(&self) -> &V {
+        unsafe { self.node.as_ref().val.assume_init_ref() }
+    }
+
+    /// Gets a mutable reference to the value in the entry. Unlike `get_mut` does not update the LRU
+    /// list so the key's position will be unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry("a").or_insert(1);
+    /// if let Entry::Occupied(mut entry) = cache.entry("a") {
+    ///     assert_eq!(entry.peek(), &1);
+    ///     *entry.peek_mut() *= 2;
+    ///     assert_eq!(entry.peek(), &2);
+    /// };
+    /// ```
+    pub fn peek_mut(&mut self) -> &mut V {
+        unsafe { self.node.as_mut().val.assume_init_mut() }
+    }
+
+    /// Converts the `OccupiedEntry` into a mutable reference to the value in the entry with a
+    /// lifetime bound to the map itself. Unlike `into_mut` does not update the LRU list so the
+    /// key's position will be unchanged.
+    ///
+    /// If you need multiple references to the `OccupiedEntry`, see `peek_mut`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry("a").or_insert(1);
+    /// if let Entry::Occupied(mut entry) = cache.entry("a") {
+    ///     *entry.into_peek() *= 2;
+    /// }
+    /// assert_eq!(cache.get(&"a"), Some(&2));
+    /// ```
+    pub fn into_peek(mut self) -> &'a mut V {
+        unsafe { self.node.as_mut().val.assume_init_mut() }
+    }
+
+    /// Gets a reference to the value in the entry.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry(1).or_insert("a");
+    /// if let Entry::Occupied(mut entry) = cache.entry(1) {
+    ///     assert_eq!(entry.get(), &"a");
+    /// };
+    /// ```
+    pub fn get(&mut self) -> &V {
+        self.promote();
+        self.peek()
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry("a").or_insert(1);
+    /// if let Entry::Occupied(mut entry) = cache.entry("a") {
+    ///     assert_eq!(entry.get(), &1);
+    ///     *entry.get_mut() *= 2;
+    ///     assert_eq!(entry.get(), &2);
+    /// };
+    /// ```
+    pub fn get_mut(&mut self) -> &mut V {
+        self.promote();
+        self.peek_mut()
+    }
+
+    /// Converts the `OccupiedEntry` into a mutable reference to the value in the entry with a
+    /// lifetime bound to the map itself.
+    ///
+    /// If you need multiple references to the `OccupiedEntry`, see `get_mut`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry("a").or_insert(1);
+    /// if let Entry::Occupied(mut entry) = cache.entry("a") {
+    ///     *entry.into_mut() *= 2;
+    /// }
+    /// assert_eq!(cache.get(&"a"), Some(&2));
+    /// ```
+    pub fn into_mut(mut self) -> &'a mut V {
+        self.promote();
+        self.into_peek()
+    }
+
+    /// Converts the `OccupiedEntry` into a guard which derefs to the value in the entry. Unlike
+    /// `into_mut`, the value is allowed to change its cost while it is borrowed: its cost is
+    /// recorded with `Limiter::entry_cost` when the guard is created, and the value is re-costed
+    /// from it with `Limiter::on_recost` when the guard is dropped. If the limiter then reports the
+    /// cache as oversized, other entries are evicted in LRU order. If the limiter rejects the new
+    /// value, the entry itself is removed from the cache and dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{CostLimited, Entry, LruCache};
+    /// let limiter = CostLimited::with_func(10, (|_: &&str| 0, |value: &Vec<u8>| value.len()));
+    /// let mut cache = LruCache::with_limiter(limiter);
+    ///
+    /// cache.put("a", vec![0; 4]);
+    /// cache.put("b", vec![0; 4]);
+    /// if let Entry::Occupied(entry) = cache.entry("b") {
+    ///     entry.into_mut_recost().extend_from_slice(&[1; 4]);
+    /// }
+    /// assert_eq!(cache.limiter().current(), 8);
+    /// assert!(!cache.contains(&"a"));
+    /// ```
+    pub fn into_mut_recost(mut self) -> impl 'a + DerefMut<Target = V> {
+        self.promote();
+        let (cache, node) = self.into_raw_parts();
+        RecostGuard::new(cache, node)
+    }
+
+    // Runs any pending evictions and splits the entry into its cache and node
+    #[allow(clippy::type_complexity)]
+    fn into_raw_parts(mut self) -> (&'a mut LruCache<K, V, L, S, A>, NonNull<LruEntry<K, V>>) {
+        while self.take_evicted().is_some() {}
+        let node = self.node;
+        let mut this = mem::ManuallyDrop::new(self);
+        // safety: `this` is never dropped, so `cache` and `extra` are each moved out only once
+        let cache = unsafe { ptr::read(&this.cache) };
+        unsafe { ptr::drop_in_place(&mut this.extra) };
+        (cache, node)
+    }
+
+    /// Marks this entry's key as the most recently used one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    /// cache.get(&1);
+    /// cache.get(&2);
+    ///
+    /// // If we do `pop_lru` now, we would pop 3.
+    /// // assert_eq!(cache.pop_lru(), Some((3, "c")));
+    ///
+    /// // By promoting 3, we make sure it isn't popped.
+    /// if let Entry::Occupied(mut entry) = cache.entry(3) {
+    ///     entry.promote();
+    /// }
+    /// assert_eq!(cache.pop_lru(), Some((1, "a")));
+    /// ```
+    pub fn promote(&mut self) {
+        self.cache.detach(self.node.as_ptr());
+        self.cache.attach(self.node.as_ptr());
+    }
+
+    /// Marks this entry's key as the least recently used one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    /// cache.get(&1);
+    /// cache.get(&2);
+    ///
+    /// // If we do `pop_lru` now, we would pop 3.
+    /// // assert_eq!(cache.pop_lru(), Some((3, "c")));
+    ///
+    /// // By demoting 1 and 2, we make sure those are popped first.
+    /// if let Entry::Occupied(mut entry) = cache.entry(2) {
+    ///     entry.demote();
+    /// }
+    /// if let Entry::Occupied(mut entry) = cache.entry(1) {
+    ///     entry.demote();
+    /// }
+    /// assert_eq!(cache.pop_lru(), Some((1, "a")));
+    /// assert_eq!(cache.pop_lru(), Some((2, "b")));
+    /// ```
+    pub fn demote(&mut self) {
+        self.cache.detach(self.node.as_ptr());
+        self.cache.attach_last(self.node.as_ptr());
+    }
+
+    /// Moves this entry right before the entry for `other`, so that it is more recently used than
+    /// `other`. Returns false if `other` is not in the cache. Reordering entries never evicts any.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    ///
+    /// if let Entry::Occupied(mut entry) = cache.entry(3) {
+    ///     assert!(entry.move_before(&1));
+    ///     assert!(!entry.move_before(&4));
+    /// }
+    /// let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, [2, 3, 1]);
+    /// ```
+    pub fn move_before<R>(&mut self, other: &R) -> bool
+    where
+        K: Borrow<R>,
+        R: Hash + Eq + ?Sized,
+    {
+        let other = match self.cache.map.get(KeyWrapper::from_ref(other)) {
+            Some(other) => other.0,
+            None => return false,
+        };
+        if other != self.node {
+            self.cache.detach(self.node.as_ptr());
+            self.cache
+                .attach_next_to(self.node.as_ptr(), other.as_ptr(), false);
+        }
+        true
+    }
+
+    fn replace_node(mut self, node: NonNull<LruEntry<K, V>>) -> Result<Self, Self> {
+        let root = unsafe { self.cache.root.unwrap_unchecked() };
+        if node == root {
+            Err(self)
+        } else {
+            self.node = node;
+            // invalidate any key/evictions
+            self.extra = OccupiedExtra::Key(None);
+            Ok(self)
+        }
+    }
+
+    /// Gets the next (less recently used) entry in the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    ///
+    /// if let Entry::Occupied(entry) = cache.entry(2) {
+    ///     let entry = entry.next().unwrap();
+    ///     assert_eq!(entry.key(), &1);
+    /// };
+    /// ```
+    pub fn next(self) -> Result<Self, Self> {
+        let node = self.cache.node_or_root(unsafe { self.node.as_ref().next });
+        self.replace_node(node)
+    }
+
+    /// Gets the previous (more recently used) entry in the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    ///
+    /// if let Entry::Occupied(entry) = cache.entry(2) {
+    ///     let entry = entry.prev().unwrap();
+    ///     assert_eq!(entry.key(), &3);
+    /// };
+    /// ```
+    pub fn prev(self) -> Result<Self, Self> {
+        let node = self.cache.node_or_root(unsafe { self.node.as_ref().prev });
+        self.replace_node(node)
+    }
+
+    /// Sets the value of the entry, and returns the entry’s old value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    ///
+    /// if let Entry::Occupied(mut entry) = cache.entry(1) {
+    ///     assert_eq!(entry.insert("b"), "a");
+    ///     assert_eq!(entry.get(), &"b");
+    /// };
+    /// ```
+    pub fn insert(&mut self, value: V) -> V {
+        self.try_insert(value)
+            .unwrap_or_else(|_| panic!("Cache does not have sufficient capacity"))
+    }
+
+    /// Trys to set the value of the entry, and returns the entry’s old value. If the new entry is
+    /// rejected by the limiter, returns the rejected value as an `Result::Err`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    ///
+    /// if let Entry::Occupied(mut entry) = cache.entry(1) {
+    ///     assert_eq!(entry.try_insert("b"), Ok("a"));
+    ///     assert_eq!(entry.get(), &"b");
+    /// };
+    /// ```
+    pub fn try_insert(&mut self, value: V) -> Result<V, V> {
+        let behavior =
+            self.cache
+                .limiter
+                .on_update(self.cache, self.key(), self.peek(), None, Some(&value));
+        if behavior == AddBehavior::Reject {
+            return Err(value);
+        }
+        Ok(replace(self.get_mut(), value))
+    }
+
+    /// Takes the value out of the entry, and returns it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    ///
+    /// if let Entry::Occupied(mut entry) = cache.entry(1) {
+    ///     assert_eq!(entry.remove(), "a");
+    /// }
+    /// assert!(!cache.contains(&1));
+    /// ```
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    fn remove_node(&mut self) {
+        self.cache.unlink(self.node);
+        // prevent automatic evictions by setting the extra to Key
+        self.extra = OccupiedExtra::Key(None);
+    }
+
+    /// Takes the key and value out of the entry, and returns them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    ///
+    /// if let Entry::Occupied(mut entry) = cache.entry(1) {
+    ///     assert_eq!(entry.remove_entry(), (1, "a"));
+    /// }
+    /// assert!(!cache.contains(&1));
+    /// ```
+    pub fn remove_entry(mut self) -> (K, V) {
+        self.remove_node();
+        unsafe { self.cache.free_node(self.node) }
+    }
+
+    /// Takes the entry evicted by this entry's insertion, if any. A return value of `None` means
+    /// that this entry was not created by insertion, did not evict another entry, or was already
+    /// taken.
+    ///
+    /// Any evicted entries which remain untaken when the entry is dropped will be dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// let mut entry = cache.entry(3).insert("c");
+    /// assert_eq!(entry.take_evicted(), Some((1, "a")));
+    /// assert_eq!(entry.take_evicted(), None);
+    /// ```
+    pub fn take_evicted(&mut self) -> Option<(K, V)> {
+        match &mut self.extra {
+            OccupiedExtra::Key(_) => return None,
+            OccupiedExtra::Evicted(evicted) => {
+                if let Some(evicted) = evicted.take() {
+                    return Some(evicted);
+                }
+            }
+        }
+        // never evict ourself
+        if let Some(evicted) = self.cache.evict(&[self.node]) {
+            return Some(evicted);
+        }
+        // switch to the key extra so we behave like a fused iterator
+        self.extra = OccupiedExtra::Key(None);
+        None
+    }
+}
+impl<
+        'a,
+        K: Hash + Eq,
+        V,
+        Q: InsertionKey<K>,
+        L: Limiter<K, V, S, A>,
+        S: BuildHasher,
+        A: Allocator,
+    > OccupiedEntry<'a, K, V, Q, L, S, A>
+{
+    /// Replaces the key in the hash map with the key used to create this entry. Panics if the
+    /// key was already consumed by insertion.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// let str1 = Rc::new("abc".to_string());
+    /// let str2 = Rc::new("abc".to_string());
+    ///
+    /// cache.put(str1.clone(), 1);
+    ///
+    /// assert_eq!(Rc::strong_count(&str1), 2);
+    /// assert_eq!(Rc::strong_count(&str2), 1);
+    /// if let Entry::Occupied(mut entry) = cache.entry(str2.clone()) {
+    ///     entry.replace_key();
+    ///     assert_eq!(Rc::strong_count(&str1), 1);
+    ///     assert_eq!(Rc::strong_count(&str2), 2);
+    /// };
+    /// ```
+    pub fn replace_key(self) -> K {
+        self.try_replace_key()
+            .unwrap_or_else(|_| panic!("Cache does not have sufficient capacity"))
+    }
+
+    fn get_key_for_replace(&mut self) -> K {
+        let key = match &mut self.extra {
+            OccupiedExtra::Key(key) => key.take(),
+            OccupiedExtra::Evicted(_) => None,
+        };
+        let key = key.expect("Key was already consumed by insertion");
+        Q::into_owned(key)
+    }
+
+    /// Tries to replace the key in the cache with the key used to create this entry. Panics if the
+    /// key was already consumed by insertion. If the limiter rejects the update, returns the
+    /// rejected key.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// let str1 = Rc::new("abc".to_string());
+    /// let str2 = Rc::new("abc".to_string());
+    ///
+    /// cache.put(str1.clone(), 1);
+    ///
+    /// assert_eq!(Rc::strong_count(&str1), 2);
+    /// assert_eq!(Rc::strong_count(&str2), 1);
+    /// if let Entry::Occupied(mut entry) = cache.entry(str2.clone()) {
+    ///     entry.try_replace_key().unwrap();
+    ///     assert_eq!(Rc::strong_count(&str1), 1);
+    ///     assert_eq!(Rc::strong_count(&str2), 2);
+    /// };
+    /// ```
+    pub fn try_replace_key(mut self) -> Result<K, K> {
+        let key = self.get_key_for_replace();
+        let behavior =
+            self.cache
+                .limiter
+                .on_update(self.cache, self.key(), self.peek(), Some(&key), None);
+        if behavior == AddBehavior::Reject {
+            return Err(key);
+        }
+        Ok(replace(self.key_mut(), key))
+    }
+
+    /// Replaces the entry, returning the old key and value. The new key in the hash map will be
+    /// the key used to create this entry. Panics if the key was already consumed by insertion.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// let str1 = Rc::new("abc".to_string());
+    /// let str2 = Rc::new("abc".to_string());
+    ///
+    /// cache.put(str1.clone(), 1);
+    ///
+    /// assert_eq!(Rc::strong_count(&str1), 2);
+    /// assert_eq!(Rc::strong_count(&str2), 1);
+    /// if let Entry::Occupied(mut entry) = cache.entry(str2.clone()) {
+    ///     entry.replace_entry(5);
+    ///     assert_eq!(Rc::strong_count(&str1), 1);
+    ///     assert_eq!(Rc::strong_count(&str2), 2);
+    /// }
+    /// assert_eq!(cache.get(&str1), Some(&5));
+    /// ```
+    pub fn replace_entry(self, value: V) -> (K, V) {
+        self.try_replace_entry(value)
+            .unwrap_or_else(|_| panic!("Cache does not have sufficient capacity"))
+    }
+
+    /// Tries to replace the entry, returning the old key and value. The new key in the hash map
+    /// will be the key used to create this entry. Panics if the key was already consumed by
+    /// insertion. If the limiter rejects the update, returns the rejected entry.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use lru::{Entry, LruCache};
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// let str1 = Rc::new("abc".to_string());
+    /// let str2 = Rc::new("abc".to_string());
+    ///
+    /// cache.put(str1.clone(), 1);
+    ///
+    /// assert_eq!(Rc::strong_count(&str1), 2);
+    /// assert_eq!(Rc::strong_count(&str2), 1);
+    /// if let Entry::Occupied(mut entry) = cache.entry(str2.clone()) {
+    ///     entry.try_replace_entry(5).unwrap();
+    ///     assert_eq!(Rc::strong_count(&str1), 1);
+    ///     assert_eq!(Rc::strong_count(&str2), 2);
+    /// }
+    /// assert_eq!(cache.get(&str1), Some(&5));
+    /// ```
+    pub fn try_replace_entry(mut self, value: V) -> Result<(K, V), (K, V)> {
+        let key = self.get_key_for_replace();
+        let behavior = self.cache.limiter.on_update(
+            self.cache,
+            self.key(),
+            self.peek(),
+            Some(&key),
+            Some(&value),
+        );
+        if behavior == AddBehavior::Reject {
+            return Err((key, value));
+        }
+        let key = replace(self.key_mut(), key);
+        let value = replace(self.get_mut(), value);
+        Ok((key, value))
+    }
+}
+impl<'a, K: Hash + Eq, V, Q, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator> Drop
+    for OccupiedEntry<'a, K, V, Q, L, S, A>
+{
+    fn drop(&mut self) {
+        while self.take_evicted().is_some() {}
+    }
+}
+impl<
+        'a,
+        K: Hash + Eq + Debug,
+        V: Debug,
+        Q,
+        L: Limiter<K, V, S, A>,
+        S: BuildHasher,
+        A: Allocator,
+    > Debug for OccupiedEntry<'a, K, V, Q, L, S, A>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", self.key())
+            .field("value", self.peek())
+            .finish()
+    }
+}
+
+// Guard returned by the `*_recost` accessors. The entry's cost is recorded before its value is
+//  borrowed, and the value is re-costed from it on drop, so in-place mutations may change it.
+struct RecostGuard<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator> {
+    cache: &'a mut LruCache<K, V, L, S, A>,
+    node: NonNull<LruEntry<K, V>>,
+    old_cost: usize,
+}
+impl<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator>
+    RecostGuard<'a, K, V, L, S, A>
+{
+    fn new(cache: &'a mut LruCache<K, V, L, S, A>, node: NonNull<LruEntry<K, V>>) -> Self {
+        let old_cost = unsafe {
+            let node = node.as_ref();
+            cache
+                .limiter
+                .entry_cost(node.key.assume_init_ref(), node.val.assume_init_ref())
+        };
+        RecostGuard {
+            cache,
+            node,
+            old_cost,
+        }
+    }
+}
+impl<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator> Deref
+    for RecostGuard<'a, K, V, L, S, A>
+{
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        unsafe { self.node.as_ref().val.assume_init_ref() }
+    }
+}
+impl<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator> DerefMut
+    for RecostGuard<'a, K, V, L, S, A>
+{
+    fn deref_mut(&mut self) -> &mut V {
+        unsafe { self.node.as_mut().val.assume_init_mut() }
+    }
+}
+impl<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator> Drop
+    for RecostGuard<'a, K, V, L, S, A>
+{
+    fn drop(&mut self) {
+        let (key, value) = unsafe {
+            let node = self.node.as_ref();
+            (node.key.assume_init_ref(), node.val.assume_init_ref())
+        };
+        let behavior = self
+            .cache
+            .limiter
+            .on_recost(self.cache, key, self.old_cost, value);
+        if behavior == AddBehavior::Reject {
+            // the value no longer fits in the cache at all. The limiter already stopped
+            //  accounting for the entry, so it is dropped without calling on_remove
+            let removed = self.cache.map.remove(KeyWrapper::from_ref(key));
+            debug_assert!(removed);
+            self.cache.detach(self.node.as_ptr());
+            drop(unsafe { self.cache.free_node(self.node) });
+        } else {
+            // an occupied entry with no pending evictions evicts others until the limiter is
+            //  satisfied when dropped, never evicting itself
+            drop(OccupiedEntry::<K, V, OwnedKey<K>, L, S, A> {
+                cache: &mut *self.cache,
+                node: self.node,
+                extra: OccupiedExtra::Evicted(None),
+            });
+        }
+    }
+}
+/// A view into a vacant entry in an `LruCache`. It is part of the `Entry` enum.
+pub struct VacantEntry<
+    'a,
+    K,
+    V,
+    Q = OwnedKey<K>,
+    L = SizeLimited,
+    S = DefaultHasher,
+    A: Allocator = Global,
+> {
+    cache: &'a mut LruCache<K, V, L, S, A>,
+    key: Q,
+}
+impl<'a, K, V, Q: Key, L, S, A: Allocator> VacantEntry<'a, K, V, Q, L, S, A> {
+    /// Gets a reference to the key that would be used when inserting a value through the
+    /// VacantEntry.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::<u8, u8>::new(2);
+    ///
+    /// assert_eq!(cache.entry(1).key(), &1);
+    /// ```
+    pub fn key(&self) -> &Q::Key {
+        Q::as_ref(&self.key)
+    }
+
+    /// Take ownership of the key.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, Entry, OwnedKey};
+    /// let mut cache = LruCache::<u8, u8>::new(2);
+    ///
+    /// if let Entry::Vacant(entry) = cache.entry(1) {
+    ///     assert_eq!(entry.into_key(), OwnedKey(1));
+    /// };
+    /// ```
+    pub fn into_key(self) -> Q {
+        self.key
+    }
+}
+impl<
+        'a,
+        K: Hash + Eq,
+        V,
+        Q: InsertionKey<K>,
+        L: Limiter<K, V, S, A>,
+        S: BuildHasher,
+        A: Allocator,
+    > VacantEntry<'a, K, V, Q, L, S, A>
+{
+    /// Sets the value of the entry with the `VacantEntry`’s key, and returns a mutable reference to
+    /// it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, Entry};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// if let Entry::Vacant(entry) = cache.entry(1) {
+    ///     entry.insert("a");
+    /// }
+    /// assert_eq!(cache.get(&1), Some(&"a"));
+    /// ```
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.insert_entry(value).into_mut()
+    }
+
+    /// Sets the value of the entry with the `VacantEntry`’s key, and returns an `OccupiedEntry`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, Entry};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// if let Entry::Vacant(entry) = cache.entry(1) {
+    ///     entry.insert_entry("a");
+    /// }
+    /// assert_eq!(cache.get(&1), Some(&"a"));
+    /// ```
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, Q, L, S, A> {
+        self.try_insert_entry(value)
+            .unwrap_or_else(|_| panic!("Cache does not have sufficient capacity"))
+    }
+
+    /// Trys to set the value of the entry with the `VacantEntry`’s key, and returns a mutable
+    /// reference to it. If insertion fails because the cache has zero capacity, returns the entry
+    /// which could not be inserted as an Err.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, Entry};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// if let Entry::Vacant(entry) = cache.entry(1) {
+    ///     let res = entry.try_insert("a");
+    ///     assert!(res.is_ok());
+    /// }
+    /// assert_eq!(cache.get(&1), Some(&"a"));
+    ///
+    /// cache.resize(0);
+    ///
+    /// if let Entry::Vacant(entry) = cache.entry(2) {
+    ///     let res = entry.try_insert("b");
+    ///     assert_eq!(res, Err((2, "b")));
+    /// };
+    /// ```
+    pub fn try_insert(self, value: V) -> Result<&'a mut V, (K, V)> {
+        Ok(self.try_insert_entry(value)?.into_mut())
+    }
+
+    /// Trys to set the value of the entry with the `VacantEntry`’s key, and returns an
+    /// `OccupiedEntry`. If insertion fails because the cache has zero capacity, returns the entry
+    /// which could not be inserted as an Err.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, Entry};
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// if let Entry::Vacant(entry) = cache.entry(3) {
+    ///     let res = entry.try_insert_entry("c");
+    ///     assert!(res.is_ok());
+    /// }
+    /// assert_eq!(cache.get(&3), Some(&"c"));
+    ///
+    /// cache.resize(0);
+    ///
+    /// if let Entry::Vacant(entry) = cache.entry(4) {
+    ///     let res = entry.try_insert_entry("d");
+    ///     assert_eq!(res.unwrap_err(), (4, "d"));
+    /// };
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn try_insert_entry(self, value: V) -> Result<OccupiedEntry<'a, K, V, Q, L, S, A>, (K, V)> {
+        let key = Q::into_owned(self.key);
+        let (node, evicted) = {
+            let behavior = self.cache.limiter.on_add(self.cache, &key, &value);
+            match behavior {
+                AddBehavior::Reject => return Err((key, value)),
+                AddBehavior::Evict if !self.cache.is_empty() => {
+                    // if the cache is full, remove the victim entry so we can use it for the new key
+                    let mut node = unsafe { self.cache.victim(&[]).unwrap_unchecked() };
+                    self.cache.unlink(node);
+                    // handles to the evicted entry must not see the new one
+                    unsafe { self.cache.nodes.renew(node) };
+                    let key = replace(unsafe { node.as_mut().key.assume_init_mut() }, key);
+                    let value = replace(unsafe { node.as_mut().val.assume_init_mut() }, value);
+                    let evicted = Some((key, value));
+                    (node, evicted)
+                }
+                _ => {
+                    let node = self.cache.nodes.alloc(LruEntry::new(key, value));
+                    if self.cache.is_empty() {
+                        self.cache.alloc_root();
+                    }
+                    (node, None)
+                }
+            }
+        };
+        self.cache.attach(node.as_ptr());
+        self.cache.map.insert(EntryWrapper(node));
+        Ok(OccupiedEntry {
+            cache: self.cache,
+            node,
+            extra: OccupiedExtra::Evicted(evicted),
+        })
+    }
+}
+impl<'a, K, V, Q: Key, L, S, A: Allocator> Debug for VacantEntry<'a, K, V, Q, L, S, A>
+where
+    Q::Key: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VacantEntry")
+            .field("key", &self.key())
+            .finish()
+    }
+}
+/// A view into a single entry in a map, which may either be vacant or occupied.
+///
+/// This `enum` is constructed from the `LruCache::entry`/`LruCache::entry_ref` methods on
+/// `LruCache`.
+pub enum Entry<
+    'a,
+    K: Hash + Eq,
+    V,
+    Q = OwnedKey<K>,
+    L: Limiter<K, V, S, A> = SizeLimited,
+    S: BuildHasher = DefaultHasher,
+    A: Allocator = Global,
+> {
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V, Q, L, S, A>),
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V, Q, L, S, A>),
+}
+impl<
+        'a,
+        K: Hash + Eq + Borrow<Q::Key>,
+        V,
+        Q: Key,
+        L: Limiter<K, V, S, A>,
+        S: BuildHasher,
+        A: Allocator,
+    > Entry<'a, K, V, Q, L, S, A>
+{
+    /// Returns a reference to this entry's key.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::<u8, u8>::new(2);
+    ///
+    /// assert_eq!(cache.entry(1).key(), &1);
+    /// ```
+    pub fn key(&self) -> &Q::Key {
+        match self {
+            Entry::Occupied(entry) => entry.key().borrow(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns a handle to the entry if it is occupied. See `EntryHandle`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// assert!(cache.entry(1).handle().is_some());
+    /// assert!(cache.entry(2).handle().is_none());
+    /// ```
+    pub fn handle(&self) -> Option<EntryHandle> {
+        match self {
+            Entry::Occupied(entry) => Some(entry.handle()),
+            Entry::Vacant(_) => None,
+        }
+    }
+}
+impl<
+        'a,
+        K: Hash + Eq,
+        V,
+        Q: InsertionKey<K>,
+        L: Limiter<K, V, S, A>,
+        S: BuildHasher,
+        A: Allocator,
+    > Entry<'a, K, V, Q, L, S, A>
+{
+    /// Sets the value of the entry, and returns an `OccupiedEntry`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// let entry = cache.entry(1).insert("a");
+    /// assert_eq!(entry.key(), &1);
+    /// entry.remove();
+    /// assert!(cache.is_empty());
+    /// ```
+    pub fn insert(self, value: V) -> OccupiedEntry<'a, K, V, Q, L, S, A> {
+        self.try_insert(value)
+            .unwrap_or_else(|_| panic!("Cache does not have sufficient capacity"))
+    }
+
+    /// Tries to sets the value of the entry, and returns an `OccupiedEntry`. If the new entry/value
+    /// is rejected by the limiter, returns the rejected entry as an `Result::Err`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// let entry = cache.entry(1).try_insert("a").unwrap();
+    /// assert_eq!(entry.key(), &1);
+    /// entry.remove();
+    /// assert!(cache.is_empty());
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn try_insert(
+        self,
+        value: V,
+    ) -> Result<OccupiedEntry<'a, K, V, Q, L, S, A>, (Option<K>, V)> {
+        match self {
+            Entry::Occupied(mut entry) => {
+                entry.try_insert(value).map_err(|v| (None, v))?;
+                Ok(entry)
+            }
+            Entry::Vacant(entry) => entry.try_insert_entry(value).map_err(|(k, v)| (Some(k), v)),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default if empty, and returns a mutable
+    /// reference to the value in the entry.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry("a").or_insert(1);
+    /// assert_eq!(cache.get(&"a"), Some(&1));
+    ///
+    /// *cache.entry("a").or_insert(10) *= 2;
+    /// assert_eq!(cache.get(&"a"), Some(&2));
+    /// ```
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(move || default)
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default function if empty,
+    /// and returns a mutable reference to the value in the entry.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry("a").or_insert_with(|| 1);
+    /// assert_eq!(cache.get(&"a"), Some(&1));
+    /// ```
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a mut V {
+        self.or_insert_with_key(move |_| default())
+    }
+
+    /// Ensures a value is in the entry by inserting, if empty, the result of the default function.
+    /// This method allows for generating key-derived values for insertion by providing the default
+    /// function a reference to the key that was moved during the .entry(key) method call.
+    ///
+    /// The reference to the moved/to_owned key is provided so that cloning or copying the key is
+    /// unnecessary, unlike with `Entry::or_insert_with`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry("abc").or_insert_with_key(|key| key.len());
+    /// assert_eq!(cache.get(&"abc"), Some(&3));
+    /// ```
+    pub fn or_insert_with_key(self, default: impl FnOnce(&K) -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let entry = VacantEntry {
+                    cache: entry.cache,
+                    key: OwnedKey(Q::into_owned(entry.key)),
+                };
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any potential inserts into the
+    /// map.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry("a")
+    ///     .and_modify(|x| *x += 1)
+    ///     .or_insert(1);
+    /// assert_eq!(cache.get(&"a"), Some(&1));
+    ///
+    /// cache.entry("a")
+    ///     .and_modify(|x| *x += 1)
+    ///     .or_insert(1);
+    /// assert_eq!(cache.get(&"a"), Some(&2));
+    /// ```
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+impl<
+        'a,
+        K: Hash + Eq,
+        V: Default,
+        Q: InsertionKey<K>,
+        L: Limiter<K, V, S, A>,
+        S: BuildHasher,
+        A: Allocator,
+    > Entry<'a, K, V, Q, L, S, A>
+{
+    /// Ensures a value is in the entry by inserting the default value if empty, and returns a
+    /// mutable reference to the value in the entry.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry("a").or_default();
+    /// assert_eq!(cache.get(&"a"), Some(&0));
+    /// ```
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(V::default)
+    }
+}
+impl<
+        'a,
+        K: Hash + Eq + Debug,
+        V: Debug,
+        Q: Key,
+        L: Limiter<K, V, S, A>,
+        S: BuildHasher,
+        A: Allocator,
+    > Debug for Entry<'a, K, V, Q, L, S, A>
+where
+    Q::Key: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Occupied(entry) => f.debug_tuple("Entry").field(entry).finish(),
+            Entry::Vacant(entry) => f.debug_tuple("Entry").field(entry).finish(),
+        }
+    }
+}
+/// A handle to an entry of an `LruCache`, which gives access to the entry without hashing its
+/// key.
+///
+/// Handles are created by `LruCache::put_handle` and `OccupiedEntry::handle`. They don't borrow the
+/// cache, and refer to their entry until it is popped, removed or evicted. From then on the
+/// methods taking the handle return `None`, even if the entry's memory is reused for another one.
+/// Using a handle with a cache other than the one that created it is safe, and also returns
+/// `None`, even once the original cache is dropped. On targets without atomics though, caches
+/// can't tell their handles apart, so a handle used with the wrong cache may refer to any of its
+/// entries.
+///
+/// Handles store the index of the entry's node, so looking them up takes constant time.
+/// `get_by_handle` and `promote_handle` never hash the key. `remove_handle` still hashes it once,
+/// to remove the entry from the cache's hash table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EntryHandle {
+    // the id of the cache's slab
+    slab: usize,
+    index: u32,
+    generation: u64,
+}
+/// Decides how `LruCache::merge` orders the entries of two caches, and which entry is kept for
+/// keys which are in both.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MergePolicy {
+    /// The merged cache's entries become more recently used than all entries of this cache, and
+    /// replace this cache's entries for keys in both caches.
+    PreferOther,
+    /// The merged cache's entries become less recently used than all entries of this cache, and
+    /// are dropped for keys in both caches.
+    PreferSelf,
+}
+/// An LRU Cache
+pub struct LruCache<K, V, L = SizeLimited, S = DefaultHasher, A: Allocator = Global> {
+    #[cfg(feature = "hashbrown")]
+    map: HashSet<EntryWrapper<K, V>, S, A>,
+    #[cfg(not(feature = "hashbrown"))]
+    map: HashSet<EntryWrapper<K, V>, S>,
+    // the entries are stored in a slab, so their memory is reused after they're removed
+    nodes: Slab<LruEntry<K, V>, A>,
+    limiter: L,
+
+    // root is a sigil node to facilitate inserting entries
+    root: Option<NonNull<LruEntry<K, V>>>,
+}
+impl<K: Hash + Eq, V> LruCache<K, V, SizeLimited> {
+    /// Creates a new LRU Cache that holds at most `cap` items.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache: LruCache<isize, &str> = LruCache::new(10);
+    /// ```
+    pub fn new(cap: usize) -> LruCache<K, V> {
+        LruCache::construct(SizeLimited::new(cap), cap, DefaultHasher::default(), Global)
+    }
+
+    /// Creates a new LRU Cache that holds at most `cap` items, with the memory for all of them
+    /// allocated upfront. Unlike `new`, which allocates the memory for entries as they are first
+    /// inserted, filling the cache will not allocate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache: LruCache<isize, &str> = LruCache::with_capacity(10);
+    /// ```
+    pub fn with_capacity(cap: usize) -> LruCache<K, V> {
+        LruCache::with_capacity_and_limiter(cap, SizeLimited::new(cap))
+    }
+}
+impl<K: Hash + Eq, V> LruCache<K, V, Unlimited> {
+    /// Creates a new LRU Cache that never automatically evicts items.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, Unlimited};
+    /// let mut cache: LruCache<isize, &str, Unlimited> = LruCache::unbounded();
+    /// ```
+    pub fn unbounded() -> LruCache<K, V, Unlimited> {
+        LruCache::construct(Unlimited, 0, DefaultHasher::default(), Global)
+    }
+}
+impl<K: Hash + Eq, V, S: BuildHasher> LruCache<K, V, SizeLimited, S> {
+    /// Creates a new LRU Cache that holds at most `cap` items and
+    /// uses the provided hash builder to hash keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, DefaultHasher};
+    ///
+    /// let s = DefaultHasher::default();
+    /// let mut cache: LruCache<isize, &str> = LruCache::with_hasher(10, s);
+    /// ```
+    pub fn with_hasher(cap: usize, hash_builder: S) -> LruCache<K, V, SizeLimited, S> {
+        LruCache::construct(SizeLimited::new(cap), cap, hash_builder, Global)
+    }
+}
+impl<K: Hash + Eq, V, S: BuildHasher, A: Allocator> LruCache<K, V, SizeLimited, S, A> {
+    /// Returns the maximum number of key-value pairs the cache can hold.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// use std::num::NonZeroUsize;
+    /// let mut cache: LruCache<isize, &str> = LruCache::new(2);
+    /// assert_eq!(cache.cap(), 2);
+    /// ```
+    pub fn cap(&self) -> usize {
+        self.limiter.limit()
+    }
+
+    /// Resizes the cache. If the new capacity is smaller than the size of the current
+    /// cache any entries past the new capacity are discarded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// use std::num::NonZeroUsize;
+    /// let mut cache: LruCache<isize, &str> = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.resize(4);
+    /// cache.put(3, "c");
+    /// cache.put(4, "d");
+    ///
+    /// assert_eq!(cache.len(), 4);
+    /// assert_eq!(cache.get(&1), Some(&"a"));
+    /// assert_eq!(cache.get(&2), Some(&"b"));
+    /// assert_eq!(cache.get(&3), Some(&"c"));
+    /// assert_eq!(cache.get(&4), Some(&"d"));
+    /// ```
+    pub fn resize(&mut self, cap: usize) {
+        // return early if capacity doesn't change
+        if cap == self.limiter.limit() {
+            return;
+        }
+        self.limiter_mut().set_limit(cap);
+        self.shrink_to_fit();
+    }
+}
+impl<K: Hash + Eq, V, S: BuildHasher> LruCache<K, V, Unlimited, S> {
+    /// Creates a new LRU Cache that never automatically evicts items and
+    /// uses the provided hash builder to hash keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, DefaultHasher, Unlimited};
+    ///
+    /// let s = DefaultHasher::default();
+    /// let mut cache: LruCache<isize, &str, Unlimited> = LruCache::unbounded_with_hasher(s);
+    /// ```
+    pub fn unbounded_with_hasher(hash_builder: S) -> LruCache<K, V, Unlimited, S> {
+        LruCache::construct(Unlimited, 0, hash_builder, Global)
+    }
+}
+impl<K: Hash + Eq, V, L: Limiter<K, V, DefaultHasher>> LruCache<K, V, L> {
+    /// Creates a new LRU Cache with the given limiter.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, SizeLimited};
+    /// let mut cache = LruCache::<usize, usize>::with_limiter(SizeLimited::new(10));
+    /// ```
+    pub fn with_limiter(limiter: L) -> LruCache<K, V, L> {
+        LruCache::construct(limiter, 0, DefaultHasher::default(), Global)
+    }
+
+    /// Creates a new LRU Cache with the given limiter, with the memory for `capacity` entries
+    /// allocated upfront. Inserting up to `capacity` entries will not allocate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, CostLimited};
+    ///
+    /// let limiter = CostLimited::with_func(1024, (|_: &u32| 0, |value: &Vec<u8>| value.len()));
+    /// let mut cache = LruCache::with_capacity_and_limiter(64, limiter);
+    /// cache.put(1, vec![0; 16]);
+    /// ```
+    pub fn with_capacity_and_limiter(capacity: usize, limiter: L) -> LruCache<K, V, L> {
+        let mut cache = LruCache::construct(limiter, capacity, DefaultHasher::default(), Global);
+        cache.nodes.reserve(capacity);
+        cache.alloc_root();
+        cache
+    }
+}
+impl<K: Hash + Eq, V, L: Limiter<K, V, S>, S: BuildHasher> LruCache<K, V, L, S> {
+    /// Creates a new LRU Cache with the given limiter and uses the provided hash builder to hash
+    /// keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, DefaultHasher, SizeLimited};
+    ///
+    /// let s = DefaultHasher::default();
+    /// let mut cache = LruCache::<usize, usize>::with_limiter_and_hasher(SizeLimited::new(10), s);
+    /// ```
+    pub fn with_limiter_and_hasher(limiter: L, hash_builder: S) -> LruCache<K, V, L, S> {
+        LruCache::construct(limiter, 0, hash_builder, Global)
+    }
+}
+impl<K: Hash + Eq, V, A: Allocator + Clone> LruCache<K, V, SizeLimited, DefaultHasher, A> {
+    /// Creates a new LRU Cache that holds at most `cap` items, with its entries and hash table
+    /// allocated in the given allocator.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::allocator_api2::alloc::Global;
+    /// use lru::LruCache;
+    /// let mut cache: LruCache<isize, &str, _, _, Global> = LruCache::new_in(10, Global);
+    /// ```
+    pub fn new_in(cap: usize, alloc: A) -> LruCache<K, V, SizeLimited, DefaultHasher, A> {
+        LruCache::construct(SizeLimited::new(cap), cap, DefaultHasher::default(), alloc)
+    }
+}
+impl<K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator + Clone>
+    LruCache<K, V, L, S, A>
+{
+    /// Creates a new LRU Cache with the given limiter and hash builder, with its entries and hash
+    /// table allocated in the given allocator. Dropping the cache returns all of its memory to
+    /// the allocator.
+    ///
+    /// Note that without the `hashbrown` feature, the hash table is always allocated in the
+    /// global allocator.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::allocator_api2::alloc::Global;
+    /// use lru::{DefaultHasher, LruCache, SizeLimited};
+    ///
+    /// let s = DefaultHasher::default();
+    /// let mut cache = LruCache::<usize, usize, _, _, _>::with_limiter_and_hasher_in(
+    ///     SizeLimited::new(10),
+    ///     s,
+    ///     Global,
+    /// );
+    /// ```
+    pub fn with_limiter_and_hasher_in(
+        limiter: L,
+        hash_builder: S,
+        alloc: A,
+    ) -> LruCache<K, V, L, S, A> {
+        LruCache::construct(limiter, 0, hash_builder, alloc)
+    }
+
+    /// Creates a new LRU Cache with the given capacity.
+    fn construct(
+        limiter: L,
+        capacity: usize,
+        hash_builder: S,
+        alloc: A,
+    ) -> LruCache<K, V, L, S, A> {
+        #[cfg(feature = "hashbrown")]
+        let map = HashSet::with_capacity_and_hasher_in(capacity, hash_builder, alloc.clone());
+        #[cfg(not(feature = "hashbrown"))]
+        let map = HashSet::with_capacity_and_hasher(capacity, hash_builder);
+        LruCache {
+            map,
+            nodes: Slab::new_in(alloc),
+            limiter,
+            root: None,
+        }
+    }
+}
+impl<K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator>
+    LruCache<K, V, L, S, A>
+{
+    /// Returns a reference to the cache's allocator.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let cache: LruCache<isize, &str> = LruCache::new(2);
+    /// let _ = cache.allocator();
+    /// ```
+    pub fn allocator(&self) -> &A {
+        self.nodes.allocator()
+    }
+
+    /// Gets the given key’s corresponding entry in the map for in-place manipulation.
+    ///
+    /// # Example
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry(1).or_insert("a");
+    /// cache.entry(2).or_default();
+    ///
+    /// assert_eq!(cache.get(&1), Some(&"a"));
+    /// assert_eq!(cache.get(&2), Some(&""))
+    /// ```
+    pub fn entry(&mut self, k: K) -> Entry<'_, K, V, OwnedKey<K>, L, S, A> {
+        self.entry_for(OwnedKey(k))
+    }
+
+    /// Gets the given key’s corresponding entry by reference in the map for in-place manipulation.
+    ///
+    /// # Example
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry_ref(&1).or_insert("a");
+    /// cache.entry_ref(&2).or_default();
+    ///
+    /// assert_eq!(cache.get(&1), Some(&"a"));
+    /// assert_eq!(cache.get(&2), Some(&""))
+    /// ```
+    pub fn entry_ref<'a, 'b, Q: ?Sized + Hash + Eq>(
+        &'a mut self,
+        k: &'b Q,
+    ) -> Entry<'a, K, V, BorrowedKey<'b, Q>, L, S, A>
+    where
+        K: Borrow<Q>,
+    {
+        self.entry_for(BorrowedKey(k))
+    }
+
+    /// Gets the entry for the LRU in the map for in-place manipulation.
+    ///
+    /// # Example
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.entry_ref(&1).or_insert("a");
+    /// cache.entry_ref(&2).or_default();
+    ///
+    /// assert_eq!(cache.entry_lru().unwrap().key(), &1);
+    /// // note: entry_lru doesn't promote by itself. Promotion only happens if you access
+    /// //    the entry's value without using one of the peek methods
+    /// assert_eq!(cache.entry_lru().unwrap().get(), &"a");
+    /// assert_eq!(cache.entry_lru().unwrap().get(), &"");
+    /// ```
+    pub fn entry_lru(&mut self) -> Option<OccupiedEntry<'_, K, V, BorrowedKey<'_, K>, L, S, A>> {
+        let node = self.tail()?;
+        Some(OccupiedEntry {
+            cache: self,
+            node,
+            extra: OccupiedExtra::Key(None),
+        })
+    }
+
+    /// Gets the entry for the MRU in the map for in-place manipulation.
+    ///
+    /// The entry was not created by insertion, so `OccupiedEntry::take_evicted` always returns
+    /// `None` for it, even if it was the entry most recently inserted by `push` or `put`.
+    ///
+    /// # Example
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// let mut entry = cache.entry_mru().unwrap();
+    /// assert_eq!(entry.key(), &2);
+    /// *entry.peek_mut() = "beta";
+    /// assert_eq!(entry.take_evicted(), None);
+    /// drop(entry);
+    /// assert_eq!(cache.peek(&2), Some(&"beta"));
+    /// ```
+    pub fn entry_mru(&mut self) -> Option<OccupiedEntry<'_, K, V, BorrowedKey<'_, K>, L, S, A>> {
+        let node = self.head()?;
+        Some(OccupiedEntry {
+            cache: self,
+            node,
+            extra: OccupiedExtra::Key(None),
+        })
+    }
+
+    pub fn entry_for<Q>(&mut self, k: Q) -> Entry<'_, K, V, Q, L, S, A>
+    where
+        Q: Key,
+        K: Borrow<Q::Key>,
+    {
+        match self
+            .map
+            .get(KeyWrapper::from_ref(Q::as_ref(&k)))
+            .map(|x| x.0)
+        {
+            None => Entry::Vacant(VacantEntry {
+                cache: self,
+                key: k,
+            }),
+            Some(node) => Entry::Occupied(OccupiedEntry {
+                cache: self,
+                node,
+                extra: OccupiedExtra::Key(Some(k)),
+            }),
+        }
+    }
+
+    /// Gets a cursor pointing at the most recently used entry, or at the ghost position if the
+    /// cache is empty. See `CursorMut`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// let mut cursor = cache.cursor_mru();
+    /// assert_eq!(cursor.key(), Some(&2));
+    /// cursor.move_next();
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// ```
+    pub fn cursor_mru(&mut self) -> CursorMut<'_, K, V, L, S, A> {
+        let node = self.head();
+        CursorMut { cache: self, node }
+    }
+
+    /// Gets a cursor pointing at the least recently used entry, or at the ghost position if the
+    /// cache is empty. See `CursorMut`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// let mut cursor = cache.cursor_lru();
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// cursor.move_prev();
+    /// assert_eq!(cursor.key(), Some(&2));
+    /// ```
+    pub fn cursor_lru(&mut self) -> CursorMut<'_, K, V, L, S, A> {
+        let node = self.tail();
+        CursorMut { cache: self, node }
+    }
+
+    /// Gets a cursor pointing at the entry for the given key, or `None` if the key is not in the
+    /// cache. Does not update the LRU list. See `CursorMut`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    ///
+    /// let mut cursor = cache.cursor_at(&2).unwrap();
+    /// cursor.move_next();
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// assert!(cache.cursor_at(&4).is_none());
+    /// ```
+    pub fn cursor_at<Q>(&mut self, k: &Q) -> Option<CursorMut<'_, K, V, L, S, A>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let node = self.map.get(KeyWrapper::from_ref(k)).map(|x| x.0)?;
+        Some(CursorMut {
+            cache: self,
+            node: Some(node),
+        })
+    }
+
+    /// Puts a key-value pair into cache. If the key already exists in the cache, then it updates
+    /// the key's value and returns the old value. Otherwise, `None` is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// assert_eq!(None, cache.put(1, "a"));
+    /// assert_eq!(None, cache.put(2, "b"));
+    /// assert_eq!(Some("b"), cache.put(2, "beta"));
+    ///
+    /// assert_eq!(cache.get(&1), Some(&"a"));
+    /// assert_eq!(cache.get(&2), Some(&"beta"));
+    /// ```
+    pub fn put(&mut self, k: K, v: V) -> Option<V> {
+        Some(match self.entry(k) {
+            Entry::Occupied(mut entry) => entry.insert(v),
+            Entry::Vacant(entry) => entry.try_insert(v).err()?.1,
+        })
+    }
+
+    /// Puts a key-value pair into the cache as the least recently used entry. If the key already
+    /// exists in the cache, then it updates the key's value, moves it to the tail of the LRU list
+    /// and returns the old value. Otherwise, `None` is returned.
+    ///
+    /// This is useful to restore entries which should be older than the ones already in the cache.
+    /// If the cache is full, the least recently used entry is evicted before the new one becomes
+    /// the least recently used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put_lru(2, "b");
+    /// assert_eq!(cache.peek_lru(), Some((&2, &"b")));
+    ///
+    /// cache.put_lru(3, "c");
+    /// assert_eq!(cache.peek_lru(), Some((&3, &"c")));
+    /// assert!(!cache.contains(&2));
+    /// ```
+    pub fn put_lru(&mut self, k: K, v: V) -> Option<V> {
+        CursorMut {
+            cache: self,
+            node: None,
+        }
+        .put(k, v, false)
+    }
+
+    /// Puts a key-value pair into the cache right after the entry for `anchor`, so that it is less
+    /// recently used than `anchor`. If the key already exists in the cache, then it updates the
+    /// key's value, moves it next to `anchor` and returns the old value. Otherwise, `None` is
+    /// returned. If `anchor` is not in the cache, the key-value pair is returned as an error.
+    ///
+    /// The entry for `anchor` is never evicted to make room for the new one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(3, "c");
+    /// assert_eq!(cache.put_after(&3, 2, "b"), Ok(None));
+    /// assert_eq!(cache.put_after(&4, 5, "e"), Err((5, "e")));
+    ///
+    /// let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, [3, 2, 1]);
+    /// ```
+    pub fn put_after<Q>(&mut self, anchor: &Q, k: K, v: V) -> Result<Option<V>, (K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.cursor_at(anchor) {
+            Some(mut cursor) => Ok(cursor.put(k, v, true)),
+            None => Err((k, v)),
+        }
+    }
+
+    /// Puts a key-value pair into cache like `put`, and returns a handle to the entry. Returns
+    /// `None` if the entry could not be inserted because the cache has zero capacity.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// let handle = cache.put_handle(1, "a").unwrap();
+    /// cache.put(2, "b");
+    /// assert_eq!(cache.get_by_handle(&handle), Some(&"a"));
+    /// ```
+    pub fn put_handle(&mut self, k: K, v: V) -> Option<EntryHandle> {
+        match self.entry(k) {
+            Entry::Occupied(mut entry) => {
+                entry.insert(v);
+                Some(entry.handle())
+            }
+            Entry::Vacant(entry) => entry.try_insert_entry(v).ok().map(|entry| entry.handle()),
+        }
+    }
+
+    /// Returns a reference to the value of the entry the handle refers to, or `None` if the entry
+    /// is no longer in the cache. Moves the entry to the head of the LRU list, like `get`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// let handle = cache.put_handle(1, "a").unwrap();
+    /// cache.put(2, "b");
+    /// assert_eq!(cache.get_by_handle(&handle), Some(&"a"));
+    /// cache.put(3, "c");
+    /// assert_eq!(cache.get_by_handle(&handle), Some(&"a"));
+    /// cache.pop(&1);
+    /// assert_eq!(cache.get_by_handle(&handle), None);
+    /// ```
+    pub fn get_by_handle(&mut self, handle: &EntryHandle) -> Option<&V> {
+        let node = self.handle_node(handle)?;
+        self.detach(node.as_ptr());
+        self.attach(node.as_ptr());
+        Some(unsafe { (*node.as_ptr()).val.assume_init_ref() })
+    }
+
+    /// Moves the entry the handle refers to to the head of the LRU list, like `promote`. Returns
+    /// false if the entry is no longer in the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// let handle = cache.put_handle(1, "a").unwrap();
+    /// cache.put(2, "b");
+    /// assert!(cache.promote_handle(&handle));
+    /// cache.put(3, "c");
+    /// assert_eq!(cache.peek_lru(), Some((&1, &"a")));
+    /// ```
+    pub fn promote_handle(&mut self, handle: &EntryHandle) -> bool {
+        match self.handle_node(handle) {
+            Some(node) => {
+                self.detach(node.as_ptr());
+                self.attach(node.as_ptr());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the entry the handle refers to and returns its key and value, or `None` if the
+    /// entry is no longer in the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// let handle = cache.put_handle(1, "a").unwrap();
+    /// assert_eq!(cache.remove_handle(&handle), Some((1, "a")));
+    /// assert_eq!(cache.remove_handle(&handle), None);
+    /// ```
+    pub fn remove_handle(&mut self, handle: &EntryHandle) -> Option<(K, V)> {
+        let node = self.handle_node(handle)?;
+        self.unlink(node);
+        Some(unsafe { self.free_node(node) })
+    }
+
+    // Returns the node the handle refers to, if its entry is still in the cache
+    fn handle_node(&self, handle: &EntryHandle) -> Option<NonNull<LruEntry<K, V>>> {
+        if handle.slab != self.nodes.id() {
+            return None;
+        }
+        self.nodes.get(handle.index, handle.generation)
+    }
+
+    /// Pushes a key-value pair into the cache. If an entry with key `k` already exists in
+    /// the cache or another cache entry is removed (due to the lru's capacity),
+    /// then it returns the old entry's key-value pair. Otherwise, returns `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// assert_eq!(None, cache.push(1, "a"));
+    /// assert_eq!(None, cache.push(2, "b"));
+    ///
+    /// // This push call returns (2, "b") because that was previously 2's entry in the cache.
+    /// assert_eq!(Some((2, "b")), cache.push(2, "beta"));
+    ///
+    /// // This push call returns (1, "a") because the cache is at capacity and 1's entry was the lru entry.
+    /// assert_eq!(Some((1, "a")), cache.push(3, "alpha"));
+    ///
+    /// assert_eq!(cache.get(&1), None);
+    /// assert_eq!(cache.get(&2), Some(&"beta"));
+    /// assert_eq!(cache.get(&3), Some(&"alpha"));
+    /// ```
+    pub fn push(&mut self, k: K, v: V) -> Option<(K, V)> {
+        Some(match self.entry(k) {
+            Entry::Occupied(entry) => entry.replace_entry(v),
+            Entry::Vacant(entry) => match entry.try_insert_entry(v) {
+                Ok(mut entry) => entry.take_evicted()?,
+                Err(rejected) => rejected,
+            },
+        })
+    }
+
+    /// Returns a reference to the value of the key in the cache or `None` if it is not
+    /// present in the cache. Moves the key to the head of the LRU list if it exists.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(2, "c");
+    /// cache.put(3, "d");
+    ///
+    /// assert_eq!(cache.get(&1), None);
+    /// assert_eq!(cache.get(&2), Some(&"c"));
+    /// assert_eq!(cache.get(&3), Some(&"d"));
+    /// ```
+    pub fn get<'a, Q>(&'a mut self, k: &Q) -> Option<&'a V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Some(self.get_mut(k)?)
+    }
+
+    /// Returns a mutable reference to the value of the key in the cache or `None` if it
+    /// is not present in the cache. Moves the key to the head of the LRU list if it exists.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put("apple", 8);
+    /// cache.put("banana", 4);
+    /// cache.put("banana", 6);
+    /// cache.put("pear", 2);
+    ///
+    /// assert_eq!(cache.get_mut(&"apple"), None);
+    /// assert_eq!(cache.get_mut(&"banana"), Some(&mut 6));
+    /// assert_eq!(cache.get_mut(&"pear"), Some(&mut 2));
+    /// ```
+    pub fn get_mut<'a, Q>(&'a mut self, k: &Q) -> Option<&'a mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.entry_ref(k) {
+            Entry::Occupied(entry) => Some(entry.into_mut()),
+            Entry::Vacant(_) => None,
+        }
+    }
+
+    /// Returns a guard which derefs to the value of the key in the cache or `None` if it is not
+    /// present in the cache. Moves the key to the head of the LRU list if it exists.
+    ///
+    /// Unlike `get_mut`, the value is allowed to change its cost (as seen by the limiter) while
+    /// it is borrowed. The cost is re-evaluated when the guard is dropped, evicting other entries
+    /// if the cache became oversized or removing the entry itself if the limiter rejects it. See
+    /// `OccupiedEntry::into_mut_recost` for details.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{CostLimited, LruCache};
+    /// let limiter = CostLimited::with_func(10, (|_: &&str| 0, |value: &Vec<u8>| value.len()));
+    /// let mut cache = LruCache::with_limiter(limiter);
+    ///
+    /// cache.put("a", vec![0; 4]);
+    /// cache.put("b", vec![0; 4]);
+    /// cache.get_mut_recost(&"a").unwrap().extend_from_slice(&[1; 4]);
+    /// assert_eq!(cache.limiter().current(), 8);
+    /// assert_eq!(cache.get(&"b"), None);
+    ///
+    /// cache.get_mut_recost(&"a").unwrap().extend_from_slice(&[2; 4]);
+    /// assert_eq!(cache.limiter().current(), 0);
+    /// assert!(cache.is_empty());
+    /// ```
+    pub fn get_mut_recost<'a, Q>(&'a mut self, k: &Q) -> Option<impl 'a + DerefMut<Target = V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.entry_ref(k) {
+            Entry::Occupied(mut entry) => {
+                entry.promote();
+                let (cache, node) = entry.into_raw_parts();
+                Some(RecostGuard::new(cache, node))
+            }
+            Entry::Vacant(_) => None,
+        }
+    }
+
+    /// Returns a reference to the value of the key in the cache if it is
+    /// present in the cache and moves the key to the head of the LRU list.
+    /// If the key does not exist the provided `FnOnce` is used to populate
+    /// the list and a reference is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(2, "c");
+    /// cache.put(3, "d");
+    ///
+    /// assert_eq!(cache.get_or_insert(2, ||"a"), &"c");
+    /// assert_eq!(cache.get_or_insert(3, ||"a"), &"d");
+    /// assert_eq!(cache.get_or_insert(1, ||"a"), &"a");
+    /// assert_eq!(cache.get_or_insert(1, ||"b"), &"a");
+    /// ```
+    pub fn get_or_insert<F>(&mut self, k: K, f: F) -> &V
+    where
+        F: FnOnce() -> V,
+    {
+        self.get_or_insert_mut(k, f)
+    }
+
+    /// Returns a reference to the value of the key in the cache if it is
+    /// present in the cache and moves the key to the head of the LRU list.
+    /// If the key does not exist the provided `FnOnce` is used to populate
+    /// the list and a reference is returned. If the cache has zero total
+    /// capacity, returns the entry which could not be inserted as an Err.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(2, "c");
+    /// cache.put(3, "d");
+    ///
+    /// assert_eq!(cache.try_get_or_insert(2, ||"a"), Ok(&"c"));
+    /// assert_eq!(cache.try_get_or_insert(3, ||"a"), Ok(&"d"));
+    /// assert_eq!(cache.try_get_or_insert(1, ||"a"), Ok(&"a"));
+    /// assert_eq!(cache.try_get_or_insert(1, ||"b"), Ok(&"a"));
+    /// ```
+    pub fn try_get_or_insert<F>(&mut self, k: K, f: F) -> Result<&V, (K, V)>
+    where
+        F: FnOnce() -> V,
+    {
+        Ok(self.try_get_or_insert_mut(k, f)?)
+    }
+
+    /// Returns a mutable reference to the value of the key in the cache if it is
+    /// present in the cache and moves the key to the head of the LRU list.
+    /// If the key does not exist the provided `FnOnce` is used to populate
+    /// the list and a mutable reference is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// let v = cache.get_or_insert_mut(2, ||"c");
+    /// assert_eq!(v, &"b");
+    /// *v = "d";
+    /// assert_eq!(cache.get_or_insert_mut(2, ||"e"), &mut "d");
+    /// assert_eq!(cache.get_or_insert_mut(3, ||"f"), &mut "f");
+    /// assert_eq!(cache.get_or_insert_mut(3, ||"e"), &mut "f");
+    /// ```
+    pub fn get_or_insert_mut<F>(&mut self, k: K, f: F) -> &mut V
+    where
+        F: FnOnce() -> V,
+    {
+        self.try_get_or_insert_mut(k, f)
+            .unwrap_or_else(|_| panic!("Cache has zero capacity"))
+    }
+
+    /// Returns a mutable reference to the value of the key in the cache if it is
+    /// present in the cache and moves the key to the head of the LRU list.
+    /// If the key does not exist the provided `FnOnce` is used to populate
+    /// the list and a mutable reference is returned. If the cache has zero total
+    /// capacity, returns the entry which could not be inserted as an Err.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// let v = cache.try_get_or_insert_mut(2, ||"c").unwrap();
+    /// assert_eq!(v, &"b");
+    /// *v = "d";
+    /// assert_eq!(cache.try_get_or_insert_mut(2, ||"e"), Ok(&mut "d"));
+    /// assert_eq!(cache.try_get_or_insert_mut(3, ||"f"), Ok(&mut "f"));
+    /// assert_eq!(cache.try_get_or_insert_mut(3, ||"e"), Ok(&mut "f"));
+    /// ```
+    pub fn try_get_or_insert_mut<F>(&mut self, k: K, f: F) -> Result<&mut V, (K, V)>
+    where
+        F: FnOnce() -> V,
+    {
+        match self.entry(k) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry.try_insert(f()),
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key in the cache or `None` if it is
+    /// not present in the cache. Unlike `get`, `peek` does not update the LRU list so the key's
+    /// position will be unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// assert_eq!(cache.peek(&1), Some(&"a"));
+    /// assert_eq!(cache.peek(&2), Some(&"b"));
+    /// ```
+    pub fn peek<'a, Q>(&'a self, k: &Q) -> Option<&'a V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map
+            .get(KeyWrapper::from_ref(k))
+            .map(|node| unsafe { &*node.0.as_ref().val.as_ptr() })
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key in the cache or `None`
+    /// if it is not present in the cache. Unlike `get_mut`, `peek_mut` does not update the LRU
+    /// list so the key's position will be unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// assert_eq!(cache.peek_mut(&1), Some(&mut "a"));
+    /// assert_eq!(cache.peek_mut(&2), Some(&mut "b"));
+    /// ```
+    pub fn peek_mut<'a, Q>(&'a mut self, k: &Q) -> Option<&'a mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.entry_ref(k) {
+            Entry::Occupied(entry) => Some(entry.into_peek()),
+            Entry::Vacant(_) => None,
+        }
+    }
+
+    /// Returns the value corresponding to the least recently used item or `None` if the
+    /// cache is empty. Like `peek`, `peek_lru` does not update the LRU list so the item's
+    /// position will be unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// assert_eq!(cache.peek_lru(), Some((&1, &"a")));
+    /// ```
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let (key, val);
+        unsafe {
+            // safety: we can unwrap root unchecked because if we're not empty, we've already
+            //  allocated
+            let node = self.tail().unwrap_unchecked().as_ptr();
+            key = &(*(*node).key.as_ptr()) as &K;
+            val = &(*(*node).val.as_ptr()) as &V;
+        }
+
+        Some((key, val))
+    }
+
+    /// Returns the value corresponding to the most recently used item or `None` if the
+    /// cache is empty. Like `peek`, `peek_mru` does not update the LRU list so the item's
+    /// position will be unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// assert_eq!(cache.peek_mru(), Some((&2, &"b")));
+    ///
+    /// cache.get(&1);
+    /// assert_eq!(cache.peek_mru(), Some((&1, &"a")));
+    /// ```
+    pub fn peek_mru(&self) -> Option<(&K, &V)> {
+        let node = self.head()?;
+        unsafe {
+            let node = node.as_ref();
+            Some((node.key.assume_init_ref(), node.val.assume_init_ref()))
+        }
+    }
+
+    /// Returns a bool indicating whether the given key is in the cache. Does not update the
+    /// LRU list.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    ///
+    /// assert!(!cache.contains(&1));
+    /// assert!(cache.contains(&2));
+    /// assert!(cache.contains(&3));
+    /// ```
+    pub fn contains<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.contains(KeyWrapper::from_ref(k))
+    }
+
+    /// Removes and returns the value corresponding to the key from the cache or
+    /// `None` if it does not exist.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(2, "a");
+    ///
+    /// assert_eq!(cache.pop(&1), None);
+    /// assert_eq!(cache.pop(&2), Some("a"));
+    /// assert_eq!(cache.pop(&2), None);
+    /// assert_eq!(cache.len(), 0);
+    /// ```
+    pub fn pop<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Some(self.pop_entry(k)?.1)
+    }
+
+    /// Removes and returns the key and the value corresponding to the key from the cache or
+    /// `None` if it does not exist.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "a");
+    ///
+    /// assert_eq!(cache.pop(&1), Some("a"));
+    /// assert_eq!(cache.pop_entry(&2), Some((2, "a")));
+    /// assert_eq!(cache.pop(&1), None);
+    /// assert_eq!(cache.pop_entry(&2), None);
+    /// assert_eq!(cache.len(), 0);
+    /// ```
+    pub fn pop_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.entry_ref(k) {
+            Entry::Occupied(entry) => Some(entry.remove_entry()),
+            Entry::Vacant(_) => None,
+        }
+    }
+
+    /// Removes and returns the key and value corresponding to the least recently
+    /// used item or `None` if the cache is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(2, "a");
+    /// cache.put(3, "b");
+    /// cache.put(4, "c");
+    /// cache.get(&3);
+    ///
+    /// assert_eq!(cache.pop_lru(), Some((4, "c")));
+    /// assert_eq!(cache.pop_lru(), Some((3, "b")));
+    /// assert_eq!(cache.pop_lru(), None);
+    /// assert_eq!(cache.len(), 0);
+    /// ```
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        Some(self.entry_lru()?.remove_entry())
+    }
+
+    /// Removes and returns the key and value corresponding to the most recently
+    /// used item or `None` if the cache is empty.
+    ///
+    /// The limiter is told that the entry was removed, like for `pop`. Popping never evicts or
+    /// restores other entries: after a `push` which evicted an entry, `pop_mru` removes the
+    /// pushed entry, but the evicted entry is only available from the return value of `push` (or
+    /// from `OccupiedEntry::take_evicted`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// assert_eq!(cache.push(3, "c"), Some((1, "a")));
+    ///
+    /// assert_eq!(cache.pop_mru(), Some((3, "c")));
+    /// assert_eq!(cache.pop_mru(), Some((2, "b")));
+    /// assert_eq!(cache.pop_mru(), None);
+    /// ```
+    pub fn pop_mru(&mut self) -> Option<(K, V)> {
+        Some(self.entry_mru()?.remove_entry())
+    }
+
+    /// Marks the key as the most recently used one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    /// cache.get(&1);
+    /// cache.get(&2);
+    ///
+    /// // If we do `pop_lru` now, we would pop 3.
+    /// // assert_eq!(cache.pop_lru(), Some((3, "c")));
+    ///
+    /// // By promoting 3, we make sure it isn't popped.
+    /// cache.promote(&3);
+    /// assert_eq!(cache.pop_lru(), Some((1, "a")));
+    /// ```
+    pub fn promote<Q>(&mut self, k: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Entry::Occupied(mut entry) = self.entry_ref(k) {
+            entry.promote();
+        }
+    }
+
+    /// Marks the key as the least recently used one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    /// cache.get(&1);
+    /// cache.get(&2);
+    ///
+    /// // If we do `pop_lru` now, we would pop 3.
+    /// // assert_eq!(cache.pop_lru(), Some((3, "c")));
+    ///
+    /// // By demoting 1 and 2, we make sure those are popped first.
+    /// cache.demote(&2);
+    /// cache.demote(&1);
+    /// assert_eq!(cache.pop_lru(), Some((1, "a")));
+    /// assert_eq!(cache.pop_lru(), Some((2, "b")));
+    /// ```
+    pub fn demote<Q>(&mut self, k: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Entry::Occupied(mut entry) = self.entry_ref(k) {
+            entry.demote();
+        }
+    }
+
+    /// Returns the number of key-value pairs that are currently in the the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    /// assert_eq!(cache.len(), 0);
+    ///
+    /// cache.put(1, "a");
+    /// assert_eq!(cache.len(), 1);
+    ///
+    /// cache.put(2, "b");
+    /// assert_eq!(cache.len(), 2);
+    ///
+    /// cache.put(3, "c");
+    /// assert_eq!(cache.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns a bool indicating whether the cache is empty or not.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    /// assert!(cache.is_empty());
+    ///
+    /// cache.put(1, "a");
+    /// assert!(!cache.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.map.len() == 0
+    }
+
+    /// Gets a reference to the cache's limiter.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let cache = LruCache::<usize, usize>::new(10);
+    /// assert_eq!(cache.cap(), cache.limiter().limit());
+    /// ```
+    pub fn limiter(&self) -> &L {
+        &self.limiter
+    }
+
+    /// Gets a mutable reference to the cache's limiter. The actual reference is wrapped in a
+    /// deref-able guard which handles automatically updating the cache if the limiter's limit
+    /// changes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    /// cache.put(1, 1);
+    /// cache.put(2, 2);
+    /// cache.limiter_mut().set_limit(1);
+    /// assert_eq!(cache.len(), 1);
+    /// ```
+    pub fn limiter_mut(&mut self) -> impl '_ + DerefMut<Target = L> {
+        struct Guard<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator>(
+            &'a mut LruCache<K, V, L, S, A>,
+        );
+
+        impl<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator> Deref
+            for Guard<'a, K, V, L, S, A>
+        {
+            type Target = L;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0.limiter
+            }
+        }
+
+        impl<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator> DerefMut
+            for Guard<'a, K, V, L, S, A>
+        {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0.limiter
+            }
+        }
+
+        impl<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator> Drop
+            for Guard<'a, K, V, L, S, A>
+        {
+            fn drop(&mut self) {
+                while self.0.evict(&[]).is_some() {}
+            }
+        }
+
+        Guard(self)
+    }
+
+    /// Shrinks the capacity of the cache as much as possible. This will not evict any entries.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    /// cache.put(1, 1);
+    /// cache.put(2, 2);
+    /// cache.shrink_to_fit();
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+        self.nodes.shrink_to_fit();
+    }
+
+    /// Returns the maximum number of unused nodes the cache keeps around to store later entries
+    /// in. See `set_max_free_nodes`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let cache: LruCache<isize, &str> = LruCache::new(2);
+    /// assert_eq!(cache.max_free_nodes(), usize::MAX);
+    /// ```
+    pub fn max_free_nodes(&self) -> usize {
+        self.nodes.max_free()
+    }
+
+    /// Bounds the number of unused nodes the cache keeps around to store later entries in.
+    ///
+    /// Nodes are allocated in chunks. Once a node is no longer used, because its entry was popped
+    /// or removed, it is kept for reuse by later inserts. By default all of them are kept, until
+    /// `shrink_to_fit` is called. With a bound, a chunk is released once none of its nodes are
+    /// used anymore while more than `max` nodes are unused. The most recently emptied chunk is
+    /// kept as a spare though, so that a cache whose size goes back and forth across the end of a
+    /// chunk doesn't allocate and release it every time. Chunks which still hold entries can't be
+    /// released either, so more than `max` nodes may stay unused.
+    ///
+    /// Pass `usize::MAX` to remove the bound.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::unbounded();
+    /// cache.set_max_free_nodes(16);
+    /// for i in 0..1000 {
+    ///     cache.put(i, i);
+    /// }
+    /// // releases the memory of the popped entries as it goes
+    /// while cache.pop_lru().is_some() {}
+    /// ```
+    pub fn set_max_free_nodes(&mut self, max: usize) {
+        self.nodes.set_max_free(max);
+    }
+
+    /// Reserves capacity for at least `additional` more entries, so that inserting them will not
+    /// allocate. This does not change the limit of the cache.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity overflows `usize`, or exceeds the `u32` indices which link the
+    /// entries.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, Unlimited};
+    /// let mut cache: LruCache<isize, &str, Unlimited> = LruCache::unbounded();
+    /// cache.reserve(10);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional);
+        self.nodes.reserve(additional);
+        self.alloc_root();
+    }
+
+    /// Tries to reserve capacity for at least `additional` more entries, so that inserting them
+    /// will not allocate. Unlike `reserve`, this returns an error instead of aborting if the
+    /// capacity overflows or the allocator reports a failure.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, Unlimited};
+    /// let mut cache: LruCache<isize, &str, Unlimited> = LruCache::unbounded();
+    /// assert!(cache.try_reserve(10).is_ok());
+    /// assert!(cache.try_reserve(usize::MAX).is_err());
+    /// ```
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        #[cfg(feature = "hashbrown")]
+        self.map.try_reserve(additional).map_err(|err| match err {
+            hashbrown::TryReserveError::CapacityOverflow => TryReserveErrorKind::CapacityOverflow,
+            hashbrown::TryReserveError::AllocError { layout } => TryReserveErrorKind::AllocError {
+                layout,
+                non_exhaustive: (),
+            },
+        })?;
+        // std doesn't expose the reason for its errors on stable, so report them as overflows
+        #[cfg(not(feature = "hashbrown"))]
+        self.map
+            .try_reserve(additional)
+            .map_err(|_| TryReserveErrorKind::CapacityOverflow)?;
+        self.nodes.try_reserve(additional)?;
+        self.try_alloc_root()
+    }
+
+    /// Clears the contents of the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache: LruCache<isize, &str> = LruCache::new(2);
+    /// assert_eq!(cache.len(), 0);
+    ///
+    /// cache.put(1, "a");
+    /// assert_eq!(cache.len(), 1);
+    ///
+    /// cache.put(2, "b");
+    /// assert_eq!(cache.len(), 2);
+    ///
+    /// cache.clear();
+    /// assert_eq!(cache.len(), 0);
+    /// ```
+    pub fn clear(&mut self) {
+        while self.pop_lru().is_some() {}
+    }
+
+    /// Moves all entries of `other` into this cache as its most recently used entries, keeping
+    /// their recency order, and leaves `other` empty. Entries of this cache whose key is also in
+    /// `other` are replaced. This is the same as `merge` with `MergePolicy::PreferOther`.
+    ///
+    /// The entries are added to the limiter one by one, and only once all of them are in place
+    /// are entries evicted, in LRU order, if the cache is oversized. Entries rejected by the
+    /// limiter are dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(3);
+    /// let mut other = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// other.put(3, "c");
+    /// other.put(1, "alpha");
+    ///
+    /// cache.append(&mut other);
+    /// assert!(other.is_empty());
+    ///
+    /// let entries: Vec<_> = cache.iter().collect();
+    /// assert_eq!(entries, [(&1, &"alpha"), (&3, &"c"), (&2, &"b")]);
+    /// ```
+    pub fn append<L2, S2, A2>(&mut self, other: &mut LruCache<K, V, L2, S2, A2>)
+    where
+        L2: Limiter<K, V, S2, A2>,
+        S2: BuildHasher,
+        A2: Allocator,
+    {
+        CursorMut {
+            cache: self,
+            node: None,
+        }
+        .splice_after(other);
+    }
+
+    /// Merges all entries of `other` into this cache. `policy` decides whether the entries of
+    /// `other` become more or less recently used than the entries of this cache, and which entry
+    /// is kept for keys in both caches. See `MergePolicy`.
+    ///
+    /// Like for `append`, entries are only evicted once the merge is complete, so the entries
+    /// which end up least recently used are the ones evicted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{LruCache, MergePolicy};
+    /// let mut cache = LruCache::new(3);
+    /// let mut other = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// other.put(3, "c");
+    /// other.put(1, "alpha");
+    ///
+    /// cache.merge(other, MergePolicy::PreferSelf);
+    ///
+    /// let entries: Vec<_> = cache.iter().collect();
+    /// assert_eq!(entries, [(&2, &"b"), (&1, &"a"), (&3, &"c")]);
+    /// ```
+    pub fn merge<L2, S2, A2>(&mut self, mut other: LruCache<K, V, L2, S2, A2>, policy: MergePolicy)
+    where
+        L2: Limiter<K, V, S2, A2>,
+        S2: BuildHasher,
+        A2: Allocator,
+    {
+        let mut cursor = CursorMut {
+            cache: self,
+            node: None,
+        };
+        match policy {
+            MergePolicy::PreferOther => cursor.splice_after(&mut other),
+            MergePolicy::PreferSelf => {
+                // appending at the tail in MRU order keeps the merged entries' order
+                while let Some(node) = other.head() {
+                    let key = unsafe { node.as_ref().key.assume_init_ref() };
+                    if cursor.cache.contains(key) {
+                        other.unlink(node);
+                        drop(unsafe { other.free_node(node) });
+                    } else {
+                        cursor.move_from(&mut other, node, false);
+                    }
+                }
+                cursor.shrink();
+            }
+        }
+    }
+
+    /// An iterator visiting all entries in most-recently used order. The iterator element type is
+    /// `(&K, &V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lru::LruCache;
+    ///
+    /// let mut cache = LruCache::new(3);
+    /// cache.put("a", 1);
+    /// cache.put("b", 2);
+    /// cache.put("c", 3);
+    ///
+    /// for (key, val) in cache.iter() {
+    ///     println!("key: {} val: {}", key, val);
+    /// }
+    /// ```
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let (front, back) = self.root.map_or((ROOT, ROOT), |root| unsafe {
+            (root.as_ref().next, root.as_ref().prev)
+        });
+        Iter {
+            len: self.len(),
+            front,
+            back,
+            nodes: self.nodes.raw(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// An iterator visiting all entries in least-recently used order. This is the same as
+    /// `iter().rev()`. The iterator element type is `(&K, &V)`.
+    ///
+    /// Note that `iter()` is double-ended, so the most recent `n` entries can be visited with
+    /// `iter().take(n)` and the least recent ones with `iter_lru().take(n)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lru::LruCache;
+    ///
+    /// let mut cache = LruCache::new(3);
+    /// cache.put("a", 1);
+    /// cache.put("b", 2);
+    /// cache.put("c", 3);
+    ///
+    /// let keys: Vec<_> = cache.iter_lru().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, ["a", "b", "c"]);
+    /// ```
+    pub fn iter_lru(&self) -> Rev<Iter<'_, K, V>> {
+        self.iter().rev()
+    }
+
+    /// An iterator visiting all entries in most-recently-used order, giving a mutable reference on
+    /// V.  The iterator element type is `(&K, &mut V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lru::LruCache;
+    ///
+    /// struct HddBlock {
+    ///     dirty: bool,
+    ///     data: [u8; 512]
+    /// }
+    ///
+    /// let mut cache = LruCache::new(3);
+    /// cache.put(0, HddBlock { dirty: false, data: [0x00; 512]});
+    /// cache.put(1, HddBlock { dirty: true,  data: [0x55; 512]});
+    /// cache.put(2, HddBlock { dirty: true,  data: [0x77; 512]});
+    ///
+    /// // write dirty blocks to disk.
+    /// for (block_id, block) in cache.iter_mut() {
+    ///     if block.dirty {
+    ///         // write block to disk
+    ///         block.dirty = false
+    ///     }
+    /// }
+    /// ```
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        let (front, back) = self.root.map_or((ROOT, ROOT), |root| unsafe {
+            (root.as_ref().next, root.as_ref().prev)
+        });
+        IterMut {
+            len: self.len(),
+            front,
+            back,
+            nodes: self.nodes.raw(),
+            phantom: PhantomData,
+        }
+    }
+
+    // Gets the entry which should be evicted next, as chosen by the limiter. Entries in `exclude`
+    //  are never chosen.
+    fn victim(&self, exclude: &[NonNull<LruEntry<K, V>>]) -> Option<NonNull<LruEntry<K, V>>> {
+        let tail = self.tail()?;
+        let root = unsafe { self.root.unwrap_unchecked() };
+        let lru = Cursor::new(root, tail, self.nodes.raw());
+        if let Some(cursor) = self.limiter.select_victim(self, lru) {
+            assert!(
+                cursor.root == root,
+                "Limiter selected a victim from another cache"
+            );
+            if !exclude.contains(&cursor.node) {
+                return Some(cursor.node);
+            }
+        }
+        let mut node = Some(tail);
+        while let Some(excluded) = node.filter(|node| exclude.contains(node)) {
+            node = self.node_at(unsafe { excluded.as_ref().prev });
+        }
+        node
+    }
+
+    // Evicts and returns one entry if the limiter reports the cache as oversized. Entries in
+    //  `exclude` are never evicted.
+    fn evict(&mut self, exclude: &[NonNull<LruEntry<K, V>>]) -> Option<(K, V)> {
+        if !self.limiter.is_oversized(self) {
+            return None;
+        }
+        // if no other entries are left, the limiter is reporting oversized on what's excluded.
+        //  Just bail out
+        let node = self.victim(exclude)?;
+        self.unlink(node);
+        Some(unsafe { self.free_node(node) })
+    }
+
+    // Gets the most recently used node
+    fn head(&self) -> Option<NonNull<LruEntry<K, V>>> {
+        if self.is_empty() {
+            return None;
+        }
+        self.node_at(unsafe { self.root.unwrap_unchecked().as_ref().next })
+    }
+
+    // Gets the least recently used node
+    fn tail(&self) -> Option<NonNull<LruEntry<K, V>>> {
+        if self.is_empty() {
+            return None;
+        }
+        self.node_at(unsafe { self.root.unwrap_unchecked().as_ref().prev })
+    }
+
+    // Removes `node` from the map and the LRU list, without freeing it
+    fn unlink(&mut self, node: NonNull<LruEntry<K, V>>) {
+        let (key, value) = unsafe {
+            let node = node.as_ref();
+            (node.key.assume_init_ref(), node.val.assume_init_ref())
+        };
+        let removed = self.map.remove(KeyWrapper::from_ref(key));
+        debug_assert!(removed);
+        self.detach(node.as_ptr());
+        self.limiter.on_remove(self, key, value);
+    }
+
+    // Frees a node which was already unlinked, returning its key and value
+    unsafe fn free_node(&mut self, node: NonNull<LruEntry<K, V>>) -> (K, V) {
+        let LruEntry { key, val, .. } = self.nodes.free(node);
+        (key.assume_init(), val.assume_init())
+    }
+
+    // Gets the node with the given index, or `None` for the root
+    fn node_at(&self, index: u32) -> Option<NonNull<LruEntry<K, V>>> {
+        if index == ROOT {
+            None
+        } else {
+            Some(unsafe { self.nodes.ptr(index) })
+        }
+    }
+
+    // Like `node_at`, but gets the root itself for its index
+    fn node_or_root(&self, index: u32) -> NonNull<LruEntry<K, V>> {
+        self.node_at(index)
+            .unwrap_or_else(|| unsafe { self.root.unwrap_unchecked() })
+    }
+
+    // Gets the index of `node`, which may be the root
+    fn index_of(&self, node: *mut LruEntry<K, V>) -> u32 {
+        if self.root.map(NonNull::as_ptr) == Some(node) {
+            ROOT
+        } else {
+            unsafe { self.nodes.index(NonNull::new_unchecked(node)) }
+        }
+    }
+
+    // Gets a cursor pointing at `node`, which must be in the LRU list
+    fn cursor(&self, node: *mut LruEntry<K, V>) -> Cursor<'_, K, V> {
+        unsafe {
+            Cursor::new(
+                self.root.unwrap_unchecked(),
+                NonNull::new_unchecked(node),
+                self.nodes.raw(),
+            )
+        }
+    }
+
+    fn detach(&mut self, node: *mut LruEntry<K, V>) {
+        self.limiter.on_detach(self, self.cursor(node));
+        unsafe {
+            let (prev, next) = ((*node).prev, (*node).next);
+            (*self.node_or_root(prev).as_ptr()).next = next;
+            (*self.node_or_root(next).as_ptr()).prev = prev;
+        }
+    }
+
+    fn alloc_root(&mut self) {
+        handle_reserve(self.try_alloc_root());
+    }
+
+    fn try_alloc_root(&mut self) -> Result<(), TryReserveError> {
+        if self.root.is_some() {
+            return Ok(());
+        }
+        let layout = Layout::new::<LruEntry<K, V>>();
+        let root = match self.nodes.allocator().allocate(layout) {
+            Ok(root) => root.as_ptr() as *mut LruEntry<K, V>,
+            Err(_) => {
+                return Err(TryReserveErrorKind::AllocError {
+                    layout,
+                    non_exhaustive: (),
+                }
+                .into())
+            }
+        };
+        unsafe {
+            ptr::write(root, LruEntry::new_sigil());
+            self.root = Some(NonNull::new_unchecked(root));
+        }
+        Ok(())
+    }
+
+    // Attaches `node` after the sigil `self.head` node.
+    fn attach(&mut self, node: *mut LruEntry<K, V>) {
+        unsafe {
+            let root = self.root.unwrap_unchecked().as_ptr();
+            let index = self.nodes.index(NonNull::new_unchecked(node));
+            (*node).next = (*root).next;
+            (*node).prev = ROOT;
+            (*root).next = index;
+            (*self.node_or_root((*node).next).as_ptr()).prev = index;
+        }
+        self.limiter.on_attach(self, self.cursor(node));
+    }
+
+    // Attaches `node` before the sigil `self.tail` node.
+    fn attach_last(&mut self, node: *mut LruEntry<K, V>) {
+        unsafe {
+            let root = self.root.unwrap_unchecked().as_ptr();
+            let index = self.nodes.index(NonNull::new_unchecked(node));
+            (*node).next = ROOT;
+            (*node).prev = (*root).prev;
+            (*root).prev = index;
+            (*self.node_or_root((*node).prev).as_ptr()).next = index;
+        }
+        self.limiter.on_attach(self, self.cursor(node));
+    }
+
+    // Attaches `node` next to `at`, on its less recently used side if `after` is set. `at` may be
+    //  the root, in which case `after` attaches at the head and `!after` at the tail.
+    fn attach_next_to(&mut self, node: *mut LruEntry<K, V>, at: *mut LruEntry<K, V>, after: bool) {
+        unsafe {
+            let index = self.nodes.index(NonNull::new_unchecked(node));
+            let (prev, next) = if after {
+                (self.index_of(at), (*at).next)
+            } else {
+                ((*at).prev, self.index_of(at))
+            };
+            (*node).prev = prev;
+            (*node).next = next;
+            (*self.node_or_root(prev).as_ptr()).next = index;
+            (*self.node_or_root(next).as_ptr()).prev = index;
+        }
+        self.limiter.on_attach(self, self.cursor(node));
+    }
+}
+impl<
+        K: Hash + Eq,
+        V,
+        L: Limiter<K, V, S, A> + FreshLimiter,
+        S: BuildHasher + Clone,
+        A: Allocator + Clone,
+    > LruCache<K, V, L, S, A>
+{
+    /// Splits off the `n` least recently used entries into a new cache, keeping their recency
+    /// order. The new cache gets a fresh limiter with this cache's configuration, and clones of
+    /// its hasher and allocator. If the cache has fewer than `n` entries, all of them are moved.
+    ///
+    /// Entries are moved one by one through the limiters of both caches. Since the new cache's
+    /// limiter has the same configuration, it normally accepts all of them, and any evictions
+    /// happen only once every entry is in place.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(4);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    ///
+    /// let cold = cache.split_off_lru(2);
+    /// assert_eq!(cache.len(), 1);
+    /// assert_eq!(cold.cap(), 4);
+    ///
+    /// let keys: Vec<_> = cold.iter().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, [2, 1]);
+    /// ```
+    pub fn split_off_lru(&mut self, n: usize) -> LruCache<K, V, L, S, A> {
+        let mut split = self.empty_like(n.min(self.len()));
+        {
+            // moving the tail to the new cache's head one by one keeps the order
+            let mut cursor = split.cursor_mru();
+            for _ in 0..n {
+                match self.tail() {
+                    Some(node) => cursor.move_from(self, node, true),
+                    None => break,
+                }
+            }
+            cursor.shrink();
+        }
+        split
+    }
+
+    /// Splits off all entries for which `pred` returns true into a new cache, keeping their
+    /// recency order. The new cache gets a fresh limiter, and clones of this cache's hasher and
+    /// allocator, like for `split_off_lru`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(4);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    /// cache.put(4, "d");
+    ///
+    /// let odd = cache.split_off_where(|key, _| key % 2 == 1);
+    ///
+    /// let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, [4, 2]);
+    /// let keys: Vec<_> = odd.iter().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, [3, 1]);
+    /// ```
+    pub fn split_off_where(
+        &mut self,
+        mut pred: impl FnMut(&K, &V) -> bool,
+    ) -> LruCache<K, V, L, S, A> {
+        let mut split = self.empty_like(0);
+        {
+            let mut cursor = split.cursor_mru();
+            // walk from the tail, moving matches to the new cache's head
+            let mut next = self.tail();
+            while let Some(node) = next {
+                next = self.node_at(unsafe { node.as_ref().prev });
+                let matches = unsafe {
+                    let entry = node.as_ref();
+                    pred(entry.key.assume_init_ref(), entry.val.assume_init_ref())
+                };
+                if matches {
+                    cursor.move_from(self, node, true);
+                }
+            }
+            cursor.shrink();
+        }
+        split
+    }
+
+    // Creates an empty cache with a fresh limiter and clones of this cache's hasher and allocator
+    fn empty_like(&self, capacity: usize) -> LruCache<K, V, L, S, A> {
+        LruCache::construct(
+            self.limiter.fresh(),
+            capacity,
+            self.map.hasher().clone(),
+            self.nodes.allocator().clone(),
+        )
+    }
+}
+impl<K, V, L, S, A: Allocator> Drop for LruCache<K, V, L, S, A> {
+    fn drop(&mut self) {
+        self.map.drain().for_each(|node| unsafe {
+            let node = node.0.as_ptr();
+            ptr::drop_in_place((*node).key.as_mut_ptr());
+            ptr::drop_in_place((*node).val.as_mut_ptr());
+        });
+        // The slab releases the memory of the entries when it is dropped. The root's key and value
+        // are maybe-uninit and never initialized, so it only needs to be deallocated.
+
+        if let Some(root) = self.root {
+            let layout = Layout::new::<LruEntry<K, V>>();
+            unsafe { self.nodes.allocator().deallocate(root.cast(), layout) };
+        }
+    }
+}
+impl<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator> IntoIterator
+    for &'a LruCache<K, V, L, S, A>
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+impl<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator> IntoIterator
+    for &'a mut LruCache<K, V, L, S, A>
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+// The compiler does not automatically derive Send and Sync for LruCache because it contains
+// raw pointers. The raw pointers are safely encapsulated by LruCache though so we can
+// implement Send and Sync for it below.
+unsafe impl<K: Send, V: Send, L: Send, S: Send, A: Allocator + Send> Send
+    for LruCache<K, V, L, S, A>
+{
+}
+unsafe impl<K: Sync, V: Sync, L: Sync, S: Sync, A: Allocator + Sync> Sync
+    for LruCache<K, V, L, S, A>
+{
+}
+impl<K: Hash + Eq, V, L: Limiter<K, V, S, A> + Debug, S: BuildHasher, A: Allocator> fmt::Debug
+    for LruCache<K, V, L, S, A>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LruCache")
+            .field("len", &self.len())
+            .field("limiter", &self.limiter())
+            .finish()
+    }
+}
+/// A read-only cursor pointing at an entry of a `LruCache`, which can be moved along the LRU list.
+///
+/// This `struct` is passed to `Limiter::select_victim` to let limiters choose which entry gets
+/// evicted. See its documentation for more.
+pub struct Cursor<'a, K: 'a, V: 'a> {
+    root: NonNull<LruEntry<K, V>>,
+    node: NonNull<LruEntry<K, V>>,
+    nodes: RawSlab<LruEntry<K, V>>,
+
+    // cursors are invariant over 'a, so that a cursor into one cache can never be passed off as a
+    //  cursor into another
+    phantom: PhantomData<Cell<&'a (K, V)>>,
+}
+impl<'a, K, V> Cursor<'a, K, V> {
+    fn new(
+        root: NonNull<LruEntry<K, V>>,
+        node: NonNull<LruEntry<K, V>>,
+        nodes: RawSlab<LruEntry<K, V>>,
+    ) -> Self {
+        Cursor {
+            root,
+            node,
+            nodes,
+            phantom: PhantomData,
+        }
+    }
+
+    fn move_to(self, index: u32) -> Option<Self> {
+        if index == ROOT {
+            None
+        } else {
+            let node = unsafe { self.nodes.ptr(index) };
+            Some(Cursor::new(self.root, node, self.nodes))
+        }
+    }
+
+    /// Gets the key of the entry the cursor points at.
+    pub fn key(&self) -> &'a K {
+        unsafe { &*(*self.node.as_ptr()).key.as_ptr() }
+    }
+
+    /// Gets the value of the entry the cursor points at.
+    pub fn value(&self) -> &'a V {
+        unsafe { &*(*self.node.as_ptr()).val.as_ptr() }
+    }
+
+    /// Gets a cursor pointing at the next (less recently used) entry, or `None` if this is the
+    /// least recently used entry.
+    pub fn next(self) -> Option<Self> {
+        let next = unsafe { self.node.as_ref().next };
+        self.move_to(next)
+    }
+
+    /// Gets a cursor pointing at the previous (more recently used) entry, or `None` if this is
+    /// the most recently used entry.
+    pub fn prev(self) -> Option<Self> {
+        let prev = unsafe { self.node.as_ref().prev };
+        self.move_to(prev)
+    }
+}
+impl<'a, K, V> Clone for Cursor<'a, K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<'a, K, V> Copy for Cursor<'a, K, V> {}
+impl<'a, K: Debug, V: Debug> Debug for Cursor<'a, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cursor")
+            .field("key", self.key())
+            .field("value", self.value())
+            .finish()
+    }
+}
+
+// The compiler does not automatically derive Send and Sync for Cursor because it contains
+// raw pointers.
+unsafe impl<'a, K: Sync, V: Sync> Send for Cursor<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Sync for Cursor<'a, K, V> {}
+/// A cursor over the entries of a `LruCache` which can be moved freely along the LRU list, and
+/// which can remove and insert entries at its position.
+///
+/// The cursor either points at an entry or at a "ghost" position between the least and the most
+/// recently used entries. Moving it past either end of the list moves it to the ghost position,
+/// and moving it on from there wraps around to the other end.
+///
+/// Every insertion goes through the cache's limiter, and entries are evicted as usual if the
+/// limiter reports the cache as oversized afterwards. The entry the cursor points at and the entry
+/// being inserted are never evicted.
+///
+/// This `struct` is created by the [`cursor_mru`], [`cursor_lru`] and [`cursor_at`] methods on
+/// [`LruCache`][`LruCache`]. See their documentation for more.
+///
+/// [`cursor_mru`]: struct.LruCache.html#method.cursor_mru
+/// [`cursor_lru`]: struct.LruCache.html#method.cursor_lru
+/// [`cursor_at`]: struct.LruCache.html#method.cursor_at
+/// [`LruCache`]: struct.LruCache.html
+pub struct CursorMut<
+    'a,
+    K: Hash + Eq,
+    V,
+    L: Limiter<K, V, S, A> = SizeLimited,
+    S: BuildHasher = DefaultHasher,
+    A: Allocator = Global,
+> {
+    cache: &'a mut LruCache<K, V, L, S, A>,
+    // `None` is the ghost position
+    node: Option<NonNull<LruEntry<K, V>>>,
+}
+impl<'a, K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator>
+    CursorMut<'a, K, V, L, S, A>
+{
+    /// Gets the key of the entry the cursor points at, or `None` if it is at the ghost position.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// assert_eq!(cache.cursor_lru().key(), Some(&1));
+    /// ```
+    pub fn key(&self) -> Option<&K> {
+        self.node
+            .map(|node| unsafe { &*(*node.as_ptr()).key.as_ptr() })
+    }
+
+    /// Gets the key and a mutable reference to the value of the entry the cursor points at, or
+    /// `None` if it is at the ghost position. Does not update the LRU list.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, 10);
+    /// cache.put(2, 20);
+    ///
+    /// let mut cursor = cache.cursor_mru();
+    /// if let Some((_, value)) = cursor.current() {
+    ///     *value += 1;
+    /// }
+    /// assert_eq!(cache.peek(&2), Some(&21));
+    /// ```
+    pub fn current(&mut self) -> Option<(&K, &mut V)> {
+        self.node.map(|node| unsafe {
+            let node = &mut *node.as_ptr();
+            (node.key.assume_init_ref(), node.val.assume_init_mut())
+        })
+    }
+
+    // Moves the cursor to the node with the given index, which is the ghost position for the root
+    fn move_to(&mut self, index: u32) {
+        self.node = self.cache.node_at(index);
+    }
+
+    /// Moves the cursor to the next (less recently used) entry. If the cursor points at the least
+    /// recently used entry, it is moved to the ghost position, and from there to the most recently
+    /// used entry.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// let mut cursor = cache.cursor_mru();
+    /// cursor.move_next();
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// cursor.move_next();
+    /// assert_eq!(cursor.key(), None);
+    /// cursor.move_next();
+    /// assert_eq!(cursor.key(), Some(&2));
+    /// ```
+    pub fn move_next(&mut self) {
+        match self.node {
+            Some(node) => self.move_to(unsafe { node.as_ref().next }),
+            None => self.node = self.cache.head(),
+        }
+    }
+
+    /// Moves the cursor to the previous (more recently used) entry. If the cursor points at the
+    /// most recently used entry, it is moved to the ghost position, and from there to the least
+    /// recently used entry.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// let mut cursor = cache.cursor_lru();
+    /// cursor.move_prev();
+    /// assert_eq!(cursor.key(), Some(&2));
+    /// cursor.move_prev();
+    /// assert_eq!(cursor.key(), None);
+    /// cursor.move_prev();
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// ```
+    pub fn move_prev(&mut self) {
+        match self.node {
+            Some(node) => self.move_to(unsafe { node.as_ref().prev }),
+            None => self.node = self.cache.tail(),
+        }
+    }
+
+    /// Removes the entry the cursor points at and returns its key and value, moving the cursor to
+    /// the next (less recently used) entry. Returns `None` if the cursor is at the ghost position.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    ///
+    /// let mut cursor = cache.cursor_at(&2).unwrap();
+    /// assert_eq!(cursor.remove_current(), Some((2, "b")));
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// assert!(!cache.contains(&2));
+    /// ```
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        let node = self.node?;
+        self.move_next();
+        self.cache.unlink(node);
+        Some(unsafe { self.cache.free_node(node) })
+    }
+
+    // Links the entry next to the cursor without evicting anything. If the key is already in the
+    //  cache, its entry is moved and the old key and value are returned alongside the node.
+    #[allow(clippy::type_complexity)]
+    fn link(
+        &mut self,
+        k: K,
+        v: V,
+        after: bool,
+    ) -> Result<(NonNull<LruEntry<K, V>>, Option<(K, V)>), (K, V)> {
+        let existing = self.cache.map.get(KeyWrapper::from_ref(&k)).map(|x| x.0);
+        if let Some(mut node) = existing {
+            let behavior = unsafe {
+                let entry = node.as_ref();
+                self.cache.limiter.on_update(
+                    self.cache,
+                    entry.key.assume_init_ref(),
+                    entry.val.assume_init_ref(),
+                    Some(&k),
+                    Some(&v),
+                )
+            };
+            if behavior == AddBehavior::Reject {
+                return Err((k, v));
+            }
+            let replaced = unsafe {
+                let entry = node.as_mut();
+                (
+                    replace(entry.key.assume_init_mut(), k),
+                    replace(entry.val.assume_init_mut(), v),
+                )
+            };
+            // an entry next to itself is already in place
+            if Some(node) != self.node {
+                self.cache.detach(node.as_ptr());
+                self.attach(node, after);
+            }
+            return Ok((node, Some(replaced)));
+        }
+        if self.cache.limiter.on_add(self.cache, &k, &v) == AddBehavior::Reject {
+            return Err((k, v));
+        }
+        let node = self.cache.nodes.alloc(LruEntry::new(k, v));
+        self.cache.alloc_root();
+        self.attach(node, after);
+        self.cache.map.insert(EntryWrapper(node));
+        Ok((node, None))
+    }
+
+    fn attach(&mut self, node: NonNull<LruEntry<K, V>>, after: bool) {
+        let at = self
+            .node
+            .unwrap_or_else(|| unsafe { self.cache.root.unwrap_unchecked() });
+        self.cache.attach_next_to(node.as_ptr(), at.as_ptr(), after);
+    }
+
+    // Evicts entries other than `node` and the current one while the cache is oversized, and
+    //  returns the first evicted entry
+    fn shrink_around(&mut self, node: NonNull<LruEntry<K, V>>) -> Option<(K, V)> {
+        let mut exclude = [node; 2];
+        if let Some(current) = self.node {
+            exclude[1] = current;
+        }
+        let evicted = self.cache.evict(&exclude);
+        while self.cache.evict(&exclude).is_some() {}
+        evicted
+    }
+
+    fn insert(&mut self, k: K, v: V, after: bool) -> Option<(K, V)> {
+        let (node, replaced) = match self.link(k, v, after) {
+            Ok(linked) => linked,
+            Err(rejected) => return Some(rejected),
+        };
+        let evicted = self.shrink_around(node);
+        replaced.or(evicted)
+    }
+
+    // Like `insert`, but returns the old or rejected value like `LruCache::put`
+    fn put(&mut self, k: K, v: V, after: bool) -> Option<V> {
+        match self.link(k, v, after) {
+            Ok((node, replaced)) => {
+                self.shrink_around(node);
+                replaced.map(|(_, v)| v)
+            }
+            Err((_, v)) => Some(v),
+        }
+    }
+
+    /// Inserts a key-value pair right before the entry the cursor points at, so that it is more
+    /// recently used than that entry. If the cursor is at the ghost position, the entry is
+    /// inserted as the least recently used one. The cursor does not move.
+    ///
+    /// If the key is already in the cache, its entry is moved to the new position and the old key
+    /// and value are returned. Otherwise, if the limiter rejects the new entry it is returned, and
+    /// if it evicts another entry to make room, the evicted entry is returned. Any further
+    /// evicted entries are dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(3, "c");
+    ///
+    /// let mut cursor = cache.cursor_at(&1).unwrap();
+    /// assert_eq!(cursor.insert_before(2, "b"), None);
+    /// // the cache is full, so inserting evicts the least recently used entry other than the one
+    /// //  under the cursor
+    /// assert_eq!(cursor.insert_before(4, "d"), Some((2, "b")));
+    ///
+    /// let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, [3, 4, 1]);
+    /// ```
+    pub fn insert_before(&mut self, k: K, v: V) -> Option<(K, V)> {
+        self.insert(k, v, false)
+    }
+
+    /// Inserts a key-value pair right after the entry the cursor points at, so that it is less
+    /// recently used than that entry. If the cursor is at the ghost position, the entry is
+    /// inserted as the most recently used one. The cursor does not move.
+    ///
+    /// The return value is the same as for `insert_before`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(3, "c");
+    ///
+    /// let mut cursor = cache.cursor_at(&3).unwrap();
+    /// assert_eq!(cursor.insert_after(2, "b"), None);
+    /// assert_eq!(cursor.insert_after(1, "alpha"), Some((1, "a")));
+    ///
+    /// let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, [3, 1, 2]);
+    /// ```
+    pub fn insert_after(&mut self, k: K, v: V) -> Option<(K, V)> {
+        self.insert(k, v, true)
+    }
+
+    /// Moves all entries of `other` right before the entry the cursor points at, keeping their
+    /// recency order, and leaves `other` empty. If the cursor is at the ghost position, the entries
+    /// become the least recently used ones. The cursor does not move.
+    ///
+    /// Entries are added to this cache's limiter one by one, and only once all of them are in
+    /// place are entries evicted if the cache is oversized. Entries rejected by the limiter are
+    /// dropped, as are entries of this cache whose key is also in `other`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(4);
+    /// let mut other = LruCache::unbounded();
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(4, "d");
+    /// other.put(2, "b");
+    /// other.put(3, "c");
+    ///
+    /// cache.cursor_at(&1).unwrap().splice_before(&mut other);
+    /// assert!(other.is_empty());
+    ///
+    /// let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, [4, 3, 2, 1]);
+    /// ```
+    pub fn splice_before<L2, S2, A2>(&mut self, other: &mut LruCache<K, V, L2, S2, A2>)
+    where
+        L2: Limiter<K, V, S2, A2>,
+        S2: BuildHasher,
+        A2: Allocator,
+    {
+        // inserting right before the cursor pushes the earlier insertions towards the head
+        while let Some(node) = other.head() {
+            self.move_from(other, node, false);
+        }
+        self.shrink();
+    }
+
+    /// Moves all entries of `other` right after the entry the cursor points at, keeping their
+    /// recency order, and leaves `other` empty. If the cursor is at the ghost position, the entries
+    /// become the most recently used ones. The cursor does not move.
+    ///
+    /// Evictions work the same way as for `splice_before`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::LruCache;
+    /// let mut cache = LruCache::new(3);
+    /// let mut other = LruCache::unbounded();
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(4, "d");
+    /// other.put(2, "b");
+    /// other.put(3, "c");
+    ///
+    /// // the cache only fits three entries, so the least recently used one is evicted after the
+    /// //  splice
+    /// cache.cursor_at(&4).unwrap().splice_after(&mut other);
+    ///
+    /// let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, [4, 3, 2]);
+    /// ```
+    pub fn splice_after<L2, S2, A2>(&mut self, other: &mut LruCache<K, V, L2, S2, A2>)
+    where
+        L2: Limiter<K, V, S2, A2>,
+        S2: BuildHasher,
+        A2: Allocator,
+    {
+        // inserting right after the cursor pushes the earlier insertions towards the tail
+        while let Some(node) = other.tail() {
+            self.move_from(other, node, true);
+        }
+        self.shrink();
+    }
+
+    // Moves the entry of `node` out of `other` and links it next to the cursor, without evicting
+    fn move_from<L2, S2, A2>(
+        &mut self,
+        other: &mut LruCache<K, V, L2, S2, A2>,
+        node: NonNull<LruEntry<K, V>>,
+        after: bool,
+    ) where
+        L2: Limiter<K, V, S2, A2>,
+        S2: BuildHasher,
+        A2: Allocator,
+    {
+        other.unlink(node);
+        let (k, v) = unsafe { other.free_node(node) };
+        drop(self.link(k, v, after));
+    }
+
+    // Evicts entries other than the current one while the cache is oversized
+    fn shrink(&mut self) {
+        let exclude = self.node.as_ref().map_or(&[][..], core::slice::from_ref);
+        while self.cache.evict(exclude).is_some() {}
+    }
+}
+impl<'a, K: Hash + Eq + Debug, V: Debug, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator> Debug
+    for CursorMut<'a, K, V, L, S, A>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let value = self
+            .node
+            .map(|node| unsafe { &*(*node.as_ptr()).val.as_ptr() });
+        f.debug_struct("CursorMut")
+            .field("key", &self.key())
+            .field("value", &value)
+            .finish()
+    }
+}
+/// An iterator over the entries of a `LruCache`.
+///
+/// This `struct` is created by the [`iter`] method on [`LruCache`][`LruCache`]. See its
+/// documentation for more.
+///
+/// [`iter`]: struct.LruCache.html#method.iter
+/// [`LruCache`]: struct.LruCache.html
+pub struct Iter<'a, K: 'a, V: 'a> {
+    len: usize,
+
+    front: u32,
+    back: u32,
+    nodes: RawSlab<LruEntry<K, V>>,
+
+    phantom: PhantomData<&'a K>,
+}
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = unsafe { self.nodes.ptr(self.front).as_ptr() };
+        let key = unsafe { &(*(*node).key.as_ptr()) as &K };
+        let val = unsafe { &(*(*node).val.as_ptr()) as &V };
+
+        self.len -= 1;
+        self.front = unsafe { (*node).next };
+
+        Some((key, val))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    fn count(self) -> usize {
+        self.len
+    }
+}
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = unsafe { self.nodes.ptr(self.back).as_ptr() };
+        let key = unsafe { &(*(*node).key.as_ptr()) as &K };
+        let val = unsafe { &(*(*node).val.as_ptr()) as &V };
+
+        self.len -= 1;
+        self.back = unsafe { (*node).prev };
+
+        Some((key, val))
+    }
+}
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+impl<'a, K, V> Clone for Iter<'a, K, V> {
+    fn clone(&self) -> Iter<'a, K, V> {
+        Iter {
+            len: self.len,
+            front: self.front,
+            back: self.back,
+            nodes: self.nodes,
+            phantom: PhantomData,
+        }
+    }
+}
+
+// The compiler does not automatically derive Send and Sync for Iter because it contains
+// raw pointers.
+unsafe impl<'a, K: Send, V: Send> Send for Iter<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Sync for Iter<'a, K, V> {}
+/// An iterator over mutables entries of a `LruCache`.
+///
+/// This `struct` is created by the [`iter_mut`] method on [`LruCache`][`LruCache`]. See its
+/// documentation for more.
+///
+/// [`iter_mut`]: struct.LruCache.html#method.iter_mut
+/// [`LruCache`]: struct.LruCache.html
+pub struct IterMut<'a, K: 'a, V: 'a> {
+    len: usize,
+
+    front: u32,
+    back: u32,
+    nodes: RawSlab<LruEntry<K, V>>,
+
+    phantom: PhantomData<&'a K>,
+}
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = unsafe { self.nodes.ptr(self.front).as_ptr() };
+        let key = unsafe { &mut (*(*node).key.as_mut_ptr()) as &mut K };
+        let val = unsafe { &mut (*(*node).val.as_mut_ptr()) as &mut V };
+
+        self.len -= 1;
+        self.front = unsafe { (*node).next };
+
+        Some((key, val))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    fn count(self) -> usize {
+        self.len
+    }
+}
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = unsafe { self.nodes.ptr(self.back).as_ptr() };
+        let key = unsafe { &mut (*(*node).key.as_mut_ptr()) as &mut K };
+        let val = unsafe { &mut (*(*node).val.as_mut_ptr()) as &mut V };
+
+        self.len -= 1;
+        self.back = unsafe { (*node).prev };
+
+        Some((key, val))
+    }
+}
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+impl<'a, K, V> FusedIterator for IterMut<'a, K, V> {}
+
+// The compiler does not automatically derive Send and Sync for Iter because it contains
+// raw pointers.
+unsafe impl<'a, K: Send, V: Send> Send for IterMut<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Sync for IterMut<'a, K, V> {}
+/// An iterator that moves out of a `LruCache`.
+///
+/// This `struct` is created by the [`into_iter`] method on [`LruCache`][`LruCache`]. See its
+/// documentation for more.
+///
+/// [`into_iter`]: struct.LruCache.html#method.into_iter
+/// [`LruCache`]: struct.LruCache.html
+pub struct IntoIter<K, V>
+where
+    K: Hash + Eq,
+{
+    cache: LruCache<K, V>,
+}
+impl<K, V> Iterator for IntoIter<K, V>
+where
+    K: Hash + Eq,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.cache.pop_lru()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.cache.len();
+        (len, Some(len))
+    }
+
+    fn count(self) -> usize {
+        self.cache.len()
+    }
+}
+impl<K, V> ExactSizeIterator for IntoIter<K, V> where K: Hash + Eq {}
+impl<K, V> FusedIterator for IntoIter<K, V> where K: Hash + Eq {}
+impl<K: Hash + Eq, V> IntoIterator for LruCache<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter { cache: self }
+    }
+}
+
+/// Doctests for what should *not* compile
+///
+/// ```compile_fail
+/// let mut cache = lru::LruCache::<u32, u32>::unbounded();
+/// let _: &'static u32 = cache.get_or_insert(0, || 92);
+/// ```
+///
+/// ```compile_fail
+/// let mut cache = lru::LruCache::<u32, u32>::unbounded();
+/// let _: Option<(&'static u32, _)> = cache.peek_lru();
+/// let _: Option<(_, &'static u32)> = cache.peek_lru();
+/// ```
+fn _test_lifetimes() {}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AddBehavior, CostLimited, Cursor, DefaultHasher, Entry, FreshLimiter, GroupLimited,
+        Limiter, LocalCostLimited, LruCache, MergePolicy, SizeLimited,
+    };
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::fmt::Debug;
+    use core::hash::BuildHasher;
+    use scoped_threadpool::Pool;
+    use std::cell::{Cell, RefCell};
+    use std::hash::Hash;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use HeaplessLruCache;
+
+    fn assert_opt_eq<V: PartialEq + Debug>(opt: Option<&V>, v: V) {
+        assert!(opt.is_some());
+        assert_eq!(opt.unwrap(), &v);
+    }
+
+    fn assert_opt_eq_mut<V: PartialEq + Debug>(opt: Option<&mut V>, v: V) {
+        assert!(opt.is_some());
+        assert_eq!(opt.unwrap(), &v);
+    }
+
+    fn assert_opt_eq_tuple<K: PartialEq + Debug, V: PartialEq + Debug>(
+        opt: Option<(&K, &V)>,
+        kv: (K, V),
+    ) {
+        assert!(opt.is_some());
+        let res = opt.unwrap();
+        assert_eq!(res.0, &kv.0);
+        assert_eq!(res.1, &kv.1);
+    }
+
+    fn assert_opt_eq_mut_tuple<K: PartialEq + Debug, V: PartialEq + Debug>(
+        opt: Option<(&K, &mut V)>,
+        kv: (K, V),
+    ) {
+        assert!(opt.is_some());
+        let res = opt.unwrap();
+        assert_eq!(res.0, &kv.0);
+        assert_eq!(res.1, &kv.1);
+    }
+
+    #[test]
+    fn test_unbounded() {
+        let mut cache = LruCache::unbounded();
+        for i in 0..13370 {
+            cache.put(i, ());
+        }
+        assert_eq!(cache.len(), 13370);
+    }
+
+    #[test]
+    #[cfg(feature = "hashbrown")]
+    fn test_with_hasher() {
+        use hashbrown::hash_map::DefaultHashBuilder;
+
+        let s = DefaultHashBuilder::default();
+        let mut cache = LruCache::with_hasher(16, s);
+
+        for i in 0..13370 {
+            cache.put(i, ());
+        }
+        assert_eq!(cache.len(), 16);
+    }
+
+    #[test]
+    fn test_put_and_get() {
+        let mut cache = LruCache::new(2);
+        assert!(cache.is_empty());
+
+        assert_eq!(cache.put("apple", "red"), None);
+        assert_eq!(cache.put("banana", "yellow"), None);
+
+        assert_eq!(cache.cap(), 2);
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.is_empty());
+        assert_opt_eq(cache.get(&"apple"), "red");
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+    }
+
+    #[test]
+    fn test_put_and_get_or_insert() {
+        let mut cache = LruCache::new(2);
+        assert!(cache.is_empty());
+
+        assert_eq!(cache.put("apple", "red"), None);
+        assert_eq!(cache.put("banana", "yellow"), None);
+
+        assert_eq!(cache.cap(), 2);
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.is_empty());
+        assert_eq!(cache.get_or_insert("apple", || "orange"), &"red");
+        assert_eq!(cache.get_or_insert("banana", || "orange"), &"yellow");
+        assert_eq!(cache.get_or_insert("lemon", || "orange"), &"orange");
+        assert_eq!(cache.get_or_insert("lemon", || "red"), &"orange");
+    }
+
+    #[test]
+    fn test_put_and_get_or_insert_mut() {
+        let mut cache = LruCache::new(2);
+        assert!(cache.is_empty());
+
+        assert_eq!(cache.put("apple", "red"), None);
+        assert_eq!(cache.put("banana", "yellow"), None);
+
+        assert_eq!(cache.cap(), 2);
+        assert_eq!(cache.len(), 2);
+
+        let v = cache.get_or_insert_mut("apple", || "orange");
+        assert_eq!(v, &"red");
+        *v = "blue";
+
+        assert_eq!(cache.get_or_insert_mut("apple", || "orange"), &"blue");
+        assert_eq!(cache.get_or_insert_mut("banana", || "orange"), &"yellow");
+        assert_eq!(cache.get_or_insert_mut("lemon", || "orange"), &"orange");
+        assert_eq!(cache.get_or_insert_mut("lemon", || "red"), &"orange");
+    }
+
+    #[test]
+    fn test_put_and_get_mut() {
+        let mut cache = LruCache::new(2);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+
+        assert_eq!(cache.cap(), 2);
+        assert_eq!(cache.len(), 2);
+        assert_opt_eq_mut(cache.get_mut(&"apple"), "red");
+        assert_opt_eq_mut(cache.get_mut(&"banana"), "yellow");
+    }
+
+    #[test]
+    fn test_get_mut_and_update() {
+        let mut cache = LruCache::new(2);
+
+        cache.put("apple", 1);
+        cache.put("banana", 3);
+
+        {
+            let v = cache.get_mut(&"apple").unwrap();
+            *v = 4;
+        }
+
+        assert_eq!(cache.cap(), 2);
+        assert_eq!(cache.len(), 2);
+        assert_opt_eq_mut(cache.get_mut(&"apple"), 4);
+        assert_opt_eq_mut(cache.get_mut(&"banana"), 3);
+    }
+
+    #[test]
+    fn test_put_update() {
+        let mut cache = LruCache::new(2);
+
+        assert_eq!(cache.put("apple", "red"), None);
+        assert_eq!(cache.put("apple", "green"), Some("red"));
+
+        assert_eq!(cache.len(), 1);
+        assert_opt_eq(cache.get(&"apple"), "green");
+    }
+
+    #[test]
+    fn test_put_removes_oldest() {
+        let mut cache = LruCache::new(2);
+
+        assert_eq!(cache.put("apple", "red"), None);
+        assert_eq!(cache.put("banana", "yellow"), None);
+        assert_eq!(cache.put("pear", "green"), None);
+
+        assert!(cache.get(&"apple").is_none());
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+        assert_opt_eq(cache.get(&"pear"), "green");
+
+        // Even though we inserted "apple" into the cache earlier it has since been removed from
+        // the cache so there is no current value for `put` to return.
+        assert_eq!(cache.put("apple", "green"), None);
+        assert_eq!(cache.put("tomato", "red"), None);
+
+        assert!(cache.get(&"pear").is_none());
+        assert_opt_eq(cache.get(&"apple"), "green");
+        assert_opt_eq(cache.get(&"tomato"), "red");
+    }
+
+    #[test]
+    fn test_peek() {
+        let mut cache = LruCache::new(2);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+
+        assert_opt_eq(cache.peek(&"banana"), "yellow");
+        assert_opt_eq(cache.peek(&"apple"), "red");
+
+        cache.put("pear", "green");
+
+        assert!(cache.peek(&"apple").is_none());
+        assert_opt_eq(cache.peek(&"banana"), "yellow");
+        assert_opt_eq(cache.peek(&"pear"), "green");
+    }
+
+    #[test]
+    fn test_peek_mut() {
+        let mut cache = LruCache::new(2);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+
+        assert_opt_eq_mut(cache.peek_mut(&"banana"), "yellow");
+        assert_opt_eq_mut(cache.peek_mut(&"apple"), "red");
+        assert!(cache.peek_mut(&"pear").is_none());
+
+        cache.put("pear", "green");
+
+        assert!(cache.peek_mut(&"apple").is_none());
+        assert_opt_eq_mut(cache.peek_mut(&"banana"), "yellow");
+        assert_opt_eq_mut(cache.peek_mut(&"pear"), "green");
+
+        {
+            let v = cache.peek_mut(&"banana").unwrap();
+            *v = "green";
+        }
+
+        assert_opt_eq_mut(cache.peek_mut(&"banana"), "green");
+    }
+
+    #[test]
+    fn test_peek_lru() {
+        let mut cache = LruCache::new(2);
+
+        assert!(cache.peek_lru().is_none());
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+        assert_opt_eq_tuple(cache.peek_lru(), ("apple", "red"));
+
+        cache.get(&"apple");
+        assert_opt_eq_tuple(cache.peek_lru(), ("banana", "yellow"));
+
+        cache.clear();
+        assert!(cache.peek_lru().is_none());
+    }
+
+    #[test]
+    fn test_contains() {
+        let mut cache = LruCache::new(2);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+        cache.put("pear", "green");
+
+        assert!(!cache.contains(&"apple"));
+        assert!(cache.contains(&"banana"));
+        assert!(cache.contains(&"pear"));
+    }
+
+    #[test]
+    fn test_pop() {
+        let mut cache = LruCache::new(2);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+
+        assert_eq!(cache.len(), 2);
+        assert_opt_eq(cache.get(&"apple"), "red");
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+
+        let popped = cache.pop(&"apple");
+        assert!(popped.is_some());
+        assert_eq!(popped.unwrap(), "red");
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&"apple").is_none());
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+    }
+
+    #[test]
+    fn test_pop_entry() {
+        let mut cache = LruCache::new(2);
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+
+        assert_eq!(cache.len(), 2);
+        assert_opt_eq(cache.get(&"apple"), "red");
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+
+        let popped = cache.pop_entry(&"apple");
+        assert!(popped.is_some());
+        assert_eq!(popped.unwrap(), ("apple", "red"));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&"apple").is_none());
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+    }
+
+    #[test]
+    fn test_pop_lru() {
+        let mut cache = LruCache::new(200);
+
+        for i in 0..75 {
+            cache.put(i, "A");
+        }
+        for i in 0..75 {
+            cache.put(i + 100, "B");
+        }
+        for i in 0..75 {
+            cache.put(i + 200, "C");
+        }
+        assert_eq!(cache.len(), 200);
+
+        for i in 0..75 {
+            assert_opt_eq(cache.get(&(74 - i + 100)), "B");
+        }
+        assert_opt_eq(cache.get(&25), "A");
+
+        for i in 26..75 {
+            assert_eq!(cache.pop_lru(), Some((i, "A")));
+        }
+        for i in 0..75 {
+            assert_eq!(cache.pop_lru(), Some((i + 200, "C")));
+        }
+        for i in 0..75 {
+            assert_eq!(cache.pop_lru(), Some((74 - i + 100, "B")));
+        }
+        assert_eq!(cache.pop_lru(), Some((25, "A")));
+        for _ in 0..50 {
+            assert_eq!(cache.pop_lru(), None);
+        }
+    }
+
+    #[test]
+    fn test_mru_accessors() {
+        let mut cache = LruCache::new(3);
+        assert_eq!(cache.peek_mru(), None);
+        assert!(cache.entry_mru().is_none());
+        assert_eq!(cache.pop_mru(), None);
+
+        cache.put(1, "a");
+        cache.put(2, "b");
+        cache.put(3, "c");
+        cache.get(&1);
+        assert_eq!(cache.peek_mru(), Some((&1, &"a")));
+        assert_eq!(
+            cache.iter_lru().collect::<Vec<_>>(),
+            [(&2, &"b"), (&3, &"c"), (&1, &"a")]
+        );
+
+        cache.entry_mru().unwrap().demote();
+        assert_eq!(cache.peek_mru(), Some((&3, &"c")));
+        assert_eq!(cache.pop_mru(), Some((3, "c")));
+        assert_eq!(cache.pop_mru(), Some((2, "b")));
+        assert_eq!(cache.pop_mru(), Some((1, "a")));
+        assert_eq!(cache.pop_mru(), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut cache = LruCache::new(2);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+
+        assert_eq!(cache.len(), 2);
+        assert_opt_eq(cache.get(&"apple"), "red");
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_resize_larger() {
+        let mut cache = LruCache::new(2);
+
+        cache.put(1, "a");
+        cache.put(2, "b");
+        cache.resize(4);
+        cache.put(3, "c");
+        cache.put(4, "d");
+
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.get(&1), Some(&"a"));
+        assert_eq!(cache.get(&2), Some(&"b"));
+        assert_eq!(cache.get(&3), Some(&"c"));
+        assert_eq!(cache.get(&4), Some(&"d"));
+    }
+
+    #[test]
+    fn test_resize_smaller() {
+        let mut cache = LruCache::new(4);
+
+        cache.put(1, "a");
+        cache.put(2, "b");
+        cache.put(3, "c");
+        cache.put(4, "d");
+
+        cache.resize(2);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&1).is_none());
+        assert!(cache.get(&2).is_none());
+        assert_eq!(cache.get(&3), Some(&"c"));
+        assert_eq!(cache.get(&4), Some(&"d"));
+    }
+
+    #[test]
+    fn test_send() {
+        use std::thread;
+
+        let mut cache = LruCache::new(4);
+        cache.put(1, "a");
+
+        let handle = thread::spawn(move || {
+            assert_eq!(cache.get(&1), Some(&"a"));
+        });
+
+        assert!(handle.join().is_ok());
+    }
+
+    #[test]
+    fn test_multiple_threads() {
+        let mut pool = Pool::new(1);
+        let mut cache = LruCache::new(4);
+        cache.put(1, "a");
+
+        let cache_ref = &cache;
+        pool.scoped(|scoped| {
+            scoped.execute(move || {
+                assert_eq!(cache_ref.peek(&1), Some(&"a"));
+            });
+        });
+
+        assert_eq!((cache_ref).peek(&1), Some(&"a"));
+    }
+
+    #[test]
+    fn test_iter_forwards() {
+        let mut cache = LruCache::new(3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+
+        {
+            // iter const
+            let mut iter = cache.iter();
+            assert_eq!(iter.len(), 3);
+            assert_opt_eq_tuple(iter.next(), ("c", 3));
+
+            assert_eq!(iter.len(), 2);
+            assert_opt_eq_tuple(iter.next(), ("b", 2));
+
+            assert_eq!(iter.len(), 1);
+            assert_opt_eq_tuple(iter.next(), ("a", 1));
+
+            assert_eq!(iter.len(), 0);
+            assert_eq!(iter.next(), None);
+        }
+        {
+            // iter mut
+            let mut iter = cache.iter_mut();
+            assert_eq!(iter.len(), 3);
+            assert_opt_eq_mut_tuple(iter.next(), ("c", 3));
+
+            assert_eq!(iter.len(), 2);
+            assert_opt_eq_mut_tuple(iter.next(), ("b", 2));
+
+            assert_eq!(iter.len(), 1);
+            assert_opt_eq_mut_tuple(iter.next(), ("a", 1));
+
+            assert_eq!(iter.len(), 0);
+            assert_eq!(iter.next(), None);
+        }
+    }
+
+    #[test]
+    fn test_iter_backwards() {
+        let mut cache = LruCache::new(3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+
+        {
+            // iter const
+            let mut iter = cache.iter();
+            assert_eq!(iter.len(), 3);
+            assert_opt_eq_tuple(iter.next_back(), ("a", 1));
+
+            assert_eq!(iter.len(), 2);
+            assert_opt_eq_tuple(iter.next_back(), ("b", 2));
+
+            assert_eq!(iter.len(), 1);
+            assert_opt_eq_tuple(iter.next_back(), ("c", 3));
+
+            assert_eq!(iter.len(), 0);
+            assert_eq!(iter.next_back(), None);
+        }
+
+        {
+            // iter mut
+            let mut iter = cache.iter_mut();
+            assert_eq!(iter.len(), 3);
+            assert_opt_eq_mut_tuple(iter.next_back(), ("a", 1));
+
+            assert_eq!(iter.len(), 2);
+            assert_opt_eq_mut_tuple(iter.next_back(), ("b", 2));
+
+            assert_eq!(iter.len(), 1);
+            assert_opt_eq_mut_tuple(iter.next_back(), ("c", 3));
+
+            assert_eq!(iter.len(), 0);
+            assert_eq!(iter.next_back(), None);
+        }
+    }
+
+    #[test]
+    fn test_iter_forwards_and_backwards() {
+        let mut cache = LruCache::new(3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+
+        {
+            // iter const
+            let mut iter = cache.iter();
+            assert_eq!(iter.len(), 3);
+            assert_opt_eq_tuple(iter.next(), ("c", 3));
+
+            assert_eq!(iter.len(), 2);
+            assert_opt_eq_tuple(iter.next_back(), ("a", 1));
+
+            assert_eq!(iter.len(), 1);
+            assert_opt_eq_tuple(iter.next(), ("b", 2));
+
+            assert_eq!(iter.len(), 0);
+            assert_eq!(iter.next_back(), None);
+        }
+        {
+            // iter mut
+            let mut iter = cache.iter_mut();
+            assert_eq!(iter.len(), 3);
+            assert_opt_eq_mut_tuple(iter.next(), ("c", 3));
+
+            assert_eq!(iter.len(), 2);
+            assert_opt_eq_mut_tuple(iter.next_back(), ("a", 1));
+
+            assert_eq!(iter.len(), 1);
+            assert_opt_eq_mut_tuple(iter.next(), ("b", 2));
+
+            assert_eq!(iter.len(), 0);
+            assert_eq!(iter.next_back(), None);
+        }
+    }
+
+    #[test]
+    fn test_iter_multiple_threads() {
+        let mut pool = Pool::new(1);
+        let mut cache = LruCache::new(3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+
+        let mut iter = cache.iter();
+        assert_eq!(iter.len(), 3);
+        assert_opt_eq_tuple(iter.next(), ("c", 3));
+
+        {
+            let iter_ref = &mut iter;
+            pool.scoped(|scoped| {
+                scoped.execute(move || {
+                    assert_eq!(iter_ref.len(), 2);
+                    assert_opt_eq_tuple(iter_ref.next(), ("b", 2));
+                });
+            });
+        }
+
+        assert_eq!(iter.len(), 1);
+        assert_opt_eq_tuple(iter.next(), ("a", 1));
+
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_iter_clone() {
+        let mut cache = LruCache::new(3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+
+        let mut iter = cache.iter();
+        let mut iter_clone = iter.clone();
+
+        assert_eq!(iter.len(), 2);
+        assert_opt_eq_tuple(iter.next(), ("b", 2));
+        assert_eq!(iter_clone.len(), 2);
+        assert_opt_eq_tuple(iter_clone.next(), ("b", 2));
+
+        assert_eq!(iter.len(), 1);
+        assert_opt_eq_tuple(iter.next(), ("a", 1));
+        assert_eq!(iter_clone.len(), 1);
+        assert_opt_eq_tuple(iter_clone.next(), ("a", 1));
+
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter_clone.len(), 0);
+        assert_eq!(iter_clone.next(), None);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let mut cache = LruCache::new(3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+
+        let mut iter = cache.into_iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next(), Some(("a", 1)));
+
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.next(), Some(("b", 2)));
+
+        assert_eq!(iter.len(), 1);
+        assert_eq!(iter.next(), Some(("c", 3)));
+
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_that_pop_actually_detaches_node() {
+        let mut cache = LruCache::new(5);
+
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+        cache.put("d", 4);
+        cache.put("e", 5);
+
+        assert_eq!(cache.pop(&"c"), Some(3));
+
+        cache.put("f", 6);
+
+        let mut iter = cache.iter();
+        assert_opt_eq_tuple(iter.next(), ("f", 6));
+        assert_opt_eq_tuple(iter.next(), ("e", 5));
+        assert_opt_eq_tuple(iter.next(), ("d", 4));
+        assert_opt_eq_tuple(iter.next(), ("b", 2));
+        assert_opt_eq_tuple(iter.next(), ("a", 1));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_get_with_borrow() {
+        use alloc::string::String;
+
+        let mut cache = LruCache::new(2);
+
+        let key = String::from("apple");
+        cache.put(key, "red");
+
+        assert_opt_eq(cache.get("apple"), "red");
+    }
+
+    #[test]
+    fn test_get_mut_with_borrow() {
+        use alloc::string::String;
+
+        let mut cache = LruCache::new(2);
+
+        let key = String::from("apple");
+        cache.put(key, "red");
+
+        assert_opt_eq_mut(cache.get_mut("apple"), "red");
+    }
+
+    #[test]
+    fn test_no_memory_leaks() {
+        static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        struct DropCounter;
+
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                DROP_COUNT.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let n = 100;
+        for _ in 0..n {
+            let mut cache = LruCache::new(1);
+            for i in 0..n {
+                cache.put(i, DropCounter {});
+            }
+        }
+        assert_eq!(DROP_COUNT.load(Ordering::SeqCst), n * n);
+    }
+
+    #[test]
+    fn test_no_memory_leaks_with_clear() {
+        static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        struct DropCounter;
+
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                DROP_COUNT.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let n = 100;
+        for _ in 0..n {
+            let mut cache = LruCache::new(1);
+            for i in 0..n {
+                cache.put(i, DropCounter {});
+            }
+            cache.clear();
+        }
+        assert_eq!(DROP_COUNT.load(Ordering::SeqCst), n * n);
+    }
+
+    #[test]
+    fn test_no_memory_leaks_with_resize() {
+        static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        struct DropCounter;
+
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                DROP_COUNT.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let n = 100;
+        for _ in 0..n {
+            let mut cache = LruCache::new(1);
+            for i in 0..n {
+                cache.put(i, DropCounter {});
+            }
+            cache.clear();
+        }
+        assert_eq!(DROP_COUNT.load(Ordering::SeqCst), n * n);
+    }
+
+    #[test]
+    fn test_no_memory_leaks_with_pop() {
+        static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Hash, Eq, PartialEq)]
+        struct KeyDropCounter(usize);
+
+        impl Drop for KeyDropCounter {
+            fn drop(&mut self) {
+                DROP_COUNT.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let n = 100;
+        for _ in 0..n {
+            let mut cache = LruCache::new(1);
+
+            for i in 0..100 {
+                cache.put(KeyDropCounter(i), i);
+                cache.pop(&KeyDropCounter(i));
+            }
+        }
+
+        assert_eq!(DROP_COUNT.load(Ordering::SeqCst), n * n * 2);
+    }
+
+    #[test]
+    fn test_promote_and_demote() {
+        let mut cache = LruCache::new(5);
+        for i in 0..5 {
+            cache.push(i, i);
+        }
+        cache.promote(&1);
+        cache.promote(&0);
+        cache.demote(&3);
+        cache.demote(&4);
+        assert_eq!(cache.pop_lru(), Some((4, 4)));
+        assert_eq!(cache.pop_lru(), Some((3, 3)));
+        assert_eq!(cache.pop_lru(), Some((2, 2)));
+        assert_eq!(cache.pop_lru(), Some((1, 1)));
+        assert_eq!(cache.pop_lru(), Some((0, 0)));
+        assert_eq!(cache.pop_lru(), None);
+    }
+
+    #[test]
+    fn test_zero_cap() {
+        let mut cache = LruCache::new(0);
+        assert_eq!(cache.put(0, 0), Some(0));
+        assert_eq!(cache.push(1, 1), Some((1, 1)));
+        assert_eq!(cache.try_get_or_insert(2, || 2), Err((2, 2)));
+        assert_eq!(cache.try_get_or_insert_mut(3, || 3), Err((3, 3)));
+    }
+
+    #[test]
+    fn test_size_limiter() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        assert_eq!(cache.push(3, 3), Some((1, 1)));
+        let _ = cache.pop_lru();
+        assert_eq!(cache.push(4, 4), None);
+        assert_eq!(cache.push(5, 5), Some((3, 3)));
+    }
+
+    #[test]
+    fn test_unlimited_limiter() {
+        let mut cache = LruCache::unbounded();
+        for i in 0..1000 {
+            cache.put(i, i);
+        }
+        assert_eq!(cache.len(), 1000);
+    }
+
+    struct TraceLimited<L> {
+        pub limiter: L,
+        adds: RefCell<usize>,
+        updates: RefCell<usize>,
+        removes: RefCell<usize>,
+    }
+
+    impl<L> TraceLimited<L> {
+        pub fn new(limiter: L) -> Self {
+            Self {
+                limiter,
+                adds: RefCell::new(0),
+                updates: RefCell::new(0),
+                removes: RefCell::new(0),
+            }
+        }
+
+        pub fn reset(&mut self) -> (usize, usize, usize) {
+            (
+                self.adds.replace(0),
+                self.updates.replace(0),
+                self.removes.replace(0),
+            )
+        }
+    }
+
+    impl<K: Hash + Eq, V, L: Limiter<K, V, S>, S> Limiter<K, V, S> for TraceLimited<L> {
+        fn is_oversized(&self, cache: &LruCache<K, V, impl Limiter<K, V, S>, S>) -> bool {
+            self.limiter.is_oversized(cache)
+        }
+
+        fn on_add(
+            &self,
+            cache: &LruCache<K, V, impl Limiter<K, V, S>, S>,
+            key: &K,
+            value: &V,
+        ) -> AddBehavior {
+            *self.adds.borrow_mut() += 1;
+            self.limiter.on_add(cache, key, value)
+        }
+
+        fn on_update(
+            &self,
+            cache: &LruCache<K, V, impl Limiter<K, V, S>, S>,
+            old_key: &K,
+            old_value: &V,
+            new_key: Option<&K>,
+            new_value: Option<&V>,
+        ) -> AddBehavior {
+            *self.updates.borrow_mut() += 1;
+            self.limiter
+                .on_update(cache, old_key, old_value, new_key, new_value)
+        }
+
+        fn on_remove(&self, cache: &LruCache<K, V, impl Limiter<K, V, S>, S>, key: &K, value: &V) {
+            *self.removes.borrow_mut() += 1;
+            self.limiter.on_remove(cache, key, value)
+        }
+
+        fn entry_cost(&self, key: &K, value: &V) -> usize {
+            self.limiter.entry_cost(key, value)
+        }
+
+        fn on_attach<'a>(
+            &self,
+            cache: &'a LruCache<K, V, impl Limiter<K, V, S>, S>,
+            entry: Cursor<'a, K, V>,
+        ) {
+            self.limiter.on_attach(cache, entry)
+        }
+
+        fn on_detach<'a>(
+            &self,
+            cache: &'a LruCache<K, V, impl Limiter<K, V, S>, S>,
+            entry: Cursor<'a, K, V>,
+        ) {
+            self.limiter.on_detach(cache, entry)
+        }
+
+        // counted as an update
+        fn on_recost(
+            &self,
+            cache: &LruCache<K, V, impl Limiter<K, V, S>, S>,
+            key: &K,
+            old_cost: usize,
+            value: &V,
+        ) -> AddBehavior {
+            *self.updates.borrow_mut() += 1;
+            self.limiter.on_recost(cache, key, old_cost, value)
+        }
+    }
+
+    #[test]
+    fn test_limit_put() {
+        let mut cache = LruCache::with_limiter(TraceLimited::new(SizeLimited::new(1)));
+        cache.put(0, 0);
+        assert_eq!(cache.limiter_mut().reset(), (1, 0, 0));
+        cache.put(1, 1);
+        assert_eq!(cache.limiter_mut().reset(), (1, 0, 1));
+        cache.put(1, 2);
+        assert_eq!(cache.limiter_mut().reset(), (0, 1, 0));
+    }
+
+    #[test]
+    fn test_limit_push() {
+        let mut cache = LruCache::with_limiter(TraceLimited::new(SizeLimited::new(1)));
+        cache.push(0, 0);
+        assert_eq!(cache.limiter_mut().reset(), (1, 0, 0));
+        cache.push(1, 1);
+        assert_eq!(cache.limiter_mut().reset(), (1, 0, 1));
+        cache.push(1, 2);
+        assert_eq!(cache.limiter_mut().reset(), (0, 1, 0));
+    }
+
+    #[test]
+    fn test_limit_get_or_insert() {
+        let mut cache = LruCache::with_limiter(TraceLimited::new(SizeLimited::new(1)));
+        cache.get_or_insert(0, || 0);
+        assert_eq!(cache.limiter_mut().reset(), (1, 0, 0));
+        cache.get_or_insert(0, || 0);
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+        cache.get_or_insert(1, || 1);
+        assert_eq!(cache.limiter_mut().reset(), (1, 0, 1));
+    }
+
+    #[test]
+    fn test_limit_get_or_insert_mut() {
+        let mut cache = LruCache::with_limiter(TraceLimited::new(SizeLimited::new(1)));
+        cache.get_or_insert_mut(0, || 0);
+        assert_eq!(cache.limiter_mut().reset(), (1, 0, 0));
+        cache.get_or_insert_mut(0, || 0);
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+        cache.get_or_insert_mut(1, || 1);
+        assert_eq!(cache.limiter_mut().reset(), (1, 0, 1));
+    }
+
+    #[test]
+    fn test_limit_pop() {
+        let mut cache = LruCache::with_limiter(TraceLimited::new(SizeLimited::new(1)));
+        cache.pop(&0);
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+        cache.push(0, 0);
+        cache.limiter_mut().reset();
+        cache.pop(&1);
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+        cache.pop(&0);
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 1));
+        cache.pop(&0);
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_limit_pop_entry() {
+        let mut cache = LruCache::with_limiter(TraceLimited::new(SizeLimited::new(1)));
+        cache.pop_entry(&0);
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+        cache.push(0, 0);
+        cache.limiter_mut().reset();
+        cache.pop_entry(&1);
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+        cache.pop_entry(&0);
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 1));
+        cache.pop_entry(&0);
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_limit_pop_lru() {
+        let mut cache = LruCache::with_limiter(TraceLimited::new(SizeLimited::new(1)));
+        cache.pop_lru();
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+        cache.push(0, 0);
+        cache.limiter_mut().reset();
+        cache.pop_lru();
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 1));
+        cache.pop_lru();
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_limit_pop_mru() {
+        let mut cache = LruCache::with_limiter(TraceLimited::new(SizeLimited::new(1)));
+        cache.pop_mru();
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+        cache.push(0, 0);
+        cache.limiter_mut().reset();
+        cache.pop_mru();
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 1));
+        cache.pop_mru();
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+    }
+
+    fn check_cost_limited<L: Limiter<usize, usize, DefaultHasher>>(
+        limiter: L,
+        current: impl Fn(&L) -> usize,
+    ) {
+        let mut cache = LruCache::with_limiter(limiter);
+        for i in 0..5 {
+            cache.put(i, i);
+        }
+        assert_eq!(cache.len(), 5);
+        assert_eq!(current(cache.limiter()), 10);
+        {
+            let mut entry = cache.entry(10).insert(10);
+            for i in 0..5 {
+                assert_eq!(entry.take_evicted(), Some((i, i)));
+            }
+            assert_eq!(entry.take_evicted(), None);
+        }
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.put(10, 4), Some(10));
+        assert_eq!(current(cache.limiter()), 4);
+        assert_eq!(cache.push(11, 11), Some((11, 11)));
+        assert_eq!(cache.pop(&10), Some(4));
+        assert_eq!(current(cache.limiter()), 0);
+    }
+
+    #[test]
+    fn test_cost_limited() {
+        check_cost_limited(
+            CostLimited::with_func(10, (|_key: &usize| 0, |value: &usize| *value)),
+            CostLimited::current,
+        );
+    }
+
+    #[test]
+    fn test_local_cost_limited() {
+        check_cost_limited(
+            LocalCostLimited::with_func(10, (|_key: &usize| 0, |value: &usize| *value)),
+            LocalCostLimited::current,
+        );
+    }
+
+    fn check_get_mut_recost<L: Limiter<usize, Vec<u8>, DefaultHasher>>(
+        limiter: L,
+        current: impl Fn(&L) -> usize,
+    ) {
+        let mut cache = LruCache::with_limiter(limiter);
+        for i in 0..5 {
+            cache.put(i, vec![0; 2]);
+        }
+        cache.get_mut_recost(&0).unwrap().extend_from_slice(&[1; 4]);
+        assert_eq!(current(cache.limiter()), 10);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.peek_lru(), Some((&3, &vec![0; 2])));
+
+        cache.get_mut_recost(&0).unwrap().truncate(1);
+        assert_eq!(current(cache.limiter()), 5);
+        assert_eq!(cache.pop(&0), Some(vec![0]));
+        assert_eq!(current(cache.limiter()), 4);
+        assert!(cache.get_mut_recost(&0).is_none());
+    }
+
+    #[test]
+    fn test_get_mut_recost() {
+        check_get_mut_recost(
+            CostLimited::with_func(10, (|_key: &usize| 0, |value: &Vec<u8>| value.len())),
+            CostLimited::current,
+        );
+    }
+
+    #[test]
+    fn test_local_get_mut_recost() {
+        check_get_mut_recost(
+            LocalCostLimited::with_func(10, (|_key: &usize| 0, |value: &Vec<u8>| value.len())),
+            LocalCostLimited::current,
+        );
+    }
+
+    #[test]
+    fn test_oversized_replacement() {
+        // replacing a value with one larger than the limit keeps the entry, like any other update
+        let mut cache =
+            LruCache::with_limiter(CostLimited::with_func(10, (|_: &u32| 0, |v: &usize| *v)));
+        cache.put(1, 5);
+        cache.put(2, 5);
+        assert_eq!(cache.put(1, 11), Some(5));
+        assert_eq!(cache.limiter().current(), 16);
+        assert_eq!(cache.push(1, 12), Some((1, 11)));
+        assert_eq!(cache.limiter().current(), 17);
+        assert_eq!(cache.peek(&1), Some(&12));
+    }
+
+    #[test]
+    fn test_limit_get_mut_recost() {
+        let mut cache = LruCache::with_limiter(TraceLimited::new(SizeLimited::new(1)));
+        cache.put(0, 0);
+        cache.limiter_mut().reset();
+        *cache.get_mut_recost(&0).unwrap() = 1;
+        assert_eq!(cache.limiter_mut().reset(), (0, 1, 0));
+        assert_eq!(cache.get(&0), Some(&1));
+        assert!(cache.get_mut_recost(&1).is_none());
+        assert_eq!(cache.limiter_mut().reset(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_no_memory_leaks_with_recost_reject() {
+        static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        struct DropCounter(usize);
+
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                DROP_COUNT.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let mut cache = LruCache::with_limiter(CostLimited::with_func(
+            10,
+            (|_key: &usize| 0, |value: &DropCounter| value.0),
+        ));
+        cache.put(0, DropCounter(5));
+        cache.put(1, DropCounter(5));
+        cache.get_mut_recost(&0).unwrap().0 = 11;
+        assert!(!cache.contains(&0));
+        assert_eq!(cache.limiter().current(), 5);
+        assert_eq!(DROP_COUNT.load(Ordering::SeqCst), 1);
+        drop(cache);
+        assert_eq!(DROP_COUNT.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_group_limited() {
+        let limiter = GroupLimited::new(2, SizeLimited::new(4), |key: &(u8, u8)| key.0);
+        let mut cache = LruCache::with_limiter(limiter);
+        cache.put((1, 0), ());
+        cache.put((0, 0), ());
+        cache.put((0, 1), ());
+        assert_eq!(cache.push((0, 2), ()), Some(((0, 0), ())));
+        assert_eq!(cache.limiter().group_len(&0), 2);
+        assert_eq!(cache.limiter().group_len(&1), 1);
+
+        // the global limit still evicts the global LRU entry
+        cache.put((2, 0), ());
+        assert_eq!(cache.push((2, 1), ()), Some(((1, 0), ())));
+        assert_eq!(cache.limiter().group_len(&1), 0);
+        assert_eq!(cache.len(), 4);
+
+        cache.pop(&(0, 1));
+        assert_eq!(cache.limiter().group_len(&0), 1);
+        assert_eq!(cache.push((2, 2), ()), Some(((2, 0), ())));
+
+        // a fresh limiter keeps the limits but counts no entries
+        let fresh = cache.limiter().fresh();
+        assert_eq!(fresh.group_limit(), 2);
+        assert_eq!(fresh.limiter().limit(), 4);
+        assert_eq!(fresh.group_len(&2), 0);
+    }
+
+    #[test]
+    fn test_group_limited_order() {
+        let limiter = GroupLimited::new(2, SizeLimited::new(10), |key: &(u8, u8)| key.0);
+        let mut cache = LruCache::with_limiter(limiter);
+        cache.put((0, 0), ());
+        cache.put((1, 0), ());
+        cache.put((0, 1), ());
+
+        // the group follows accesses, demotions and moves in the LRU list
+        cache.get(&(0, 0));
+        assert_eq!(cache.push((0, 2), ()), Some(((0, 1), ())));
+        cache.demote(&(0, 2));
+        assert_eq!(cache.push((0, 3), ()), Some(((0, 2), ())));
+        if let Entry::Occupied(mut entry) = cache.entry((0, 3)) {
+            assert!(entry.move_before(&(1, 0)));
+        }
+        assert_eq!(cache.push((0, 4), ()), Some(((0, 3), ())));
+
+        // emptied groups are dropped and can come back
+        cache.pop(&(0, 0));
+        cache.pop(&(0, 4));
+        assert_eq!(cache.limiter().group_len(&0), 0);
+        cache.put((0, 5), ());
+        cache.put((1, 1), ());
+        cache.put((0, 6), ());
+
+        // an entry inserted into the middle of the list goes before the next entry of its group
+        let mut cursor = cache.cursor_at(&(1, 1)).unwrap();
+        assert_eq!(cursor.insert_before((0, 7), ()), Some(((0, 5), ())));
+        assert_eq!(
+            cache.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            [(0, 6), (0, 7), (1, 1), (1, 0)]
+        );
+        assert_eq!(cache.limiter().group_len(&0), 2);
+        assert_eq!(cache.push((0, 8), ()), Some(((0, 7), ())));
+    }
+
+    #[test]
+    fn test_group_limited_set_group_limit() {
+        let limiter = GroupLimited::new(3, SizeLimited::new(10), |key: &(u8, u8)| key.0);
+        let mut cache = LruCache::with_limiter(limiter);
+        for i in 0..3 {
+            cache.put((0, i), ());
+            cache.put((1, i), ());
+        }
+        cache.limiter_mut().set_group_limit(1);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![(1, 2), (0, 2)]
+        );
+
+        cache.limiter_mut().set_group_limit(0);
+        assert!(cache.is_empty());
+        assert_eq!(cache.push((0, 0), ()), Some(((0, 0), ())));
+    }
+
+    // Protects entries with even keys from eviction while there are odd ones to evict instead
+    struct ProtectEven(SizeLimited);
+
+    impl<V, S: BuildHasher> Limiter<usize, V, S> for ProtectEven {
+        fn is_oversized(&self, cache: &LruCache<usize, V, impl Limiter<usize, V, S>, S>) -> bool {
+            self.0.is_oversized(cache)
+        }
+
+        fn on_add(
+            &self,
+            cache: &LruCache<usize, V, impl Limiter<usize, V, S>, S>,
+            key: &usize,
+            value: &V,
+        ) -> AddBehavior {
+            self.0.on_add(cache, key, value)
+        }
+
+        fn on_update(
+            &self,
+            cache: &LruCache<usize, V, impl Limiter<usize, V, S>, S>,
+            old_key: &usize,
+            old_value: &V,
+            new_key: Option<&usize>,
+            new_value: Option<&V>,
+        ) -> AddBehavior {
+            self.0
+                .on_update(cache, old_key, old_value, new_key, new_value)
+        }
+
+        fn select_victim<'a>(
+            &self,
+            _cache: &'a LruCache<usize, V, impl Limiter<usize, V, S>, S>,
+            lru: Cursor<'a, usize, V>,
+        ) -> Option<Cursor<'a, usize, V>> {
+            let mut cursor = Some(lru);
+            while let Some(entry) = cursor {
+                if entry.key() % 2 == 1 {
+                    return Some(entry);
+                }
+                cursor = entry.prev();
+            }
+            None
+        }
+    }
+
+    #[test]
+    fn test_select_victim() {
+        let mut cache = LruCache::with_limiter(ProtectEven(SizeLimited::new(3)));
+        cache.put(0, 0);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        assert_eq!(cache.push(4, 4), Some((1, 1)));
+        // with no odd entries left, the LRU entry is evicted
+        assert_eq!(cache.push(6, 6), Some((0, 0)));
+
+        cache.put(3, 3);
+        {
+            let mut entry = cache.entry(8).insert(8);
+            assert_eq!(entry.take_evicted(), Some((3, 3)));
+            assert_eq!(entry.take_evicted(), None);
+        }
+        cache.pop(&4);
+        assert_eq!(cache.push(5, 5), None);
+        assert_eq!(cache.push(7, 7), Some((5, 5)));
+        assert_eq!(
+            cache.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![7, 8, 6]
+        );
+
+        cache.put(9, 9);
+        cache.limiter_mut().0.set_limit(2);
+        assert_eq!(
+            cache.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![8, 6]
+        );
+    }
+
+    #[test]
+    fn test_shrink_to_fit_reuses_nodes() {
+        let mut cache = LruCache::unbounded();
+        for i in 0..100 {
+            cache.put(i, i);
+        }
+        for i in 0..90 {
+            cache.pop(&i);
+        }
+        cache.shrink_to_fit();
+        for i in 100..200 {
+            cache.put(i, i);
+        }
+        assert_eq!(cache.len(), 110);
+        assert!(cache
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .eq((90..200).rev().map(|i| (i, i))));
+
+        cache.clear();
+        cache.shrink_to_fit();
+        cache.put(0, 0);
+        assert_eq!(cache.pop_lru(), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_allocator() {
+        use allocator_api2::alloc::{AllocError, Allocator, Global, Layout};
+        use core::ptr::NonNull;
+
+        // counts live allocations, ignoring zero-sized ones since they hold no memory
+        #[derive(Clone, Copy)]
+        struct Counting<'a>(&'a Cell<isize>);
+
+        unsafe impl<'a> Allocator for Counting<'a> {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                if layout.size() != 0 {
+                    self.0.set(self.0.get() + 1);
+                }
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                if layout.size() != 0 {
+                    self.0.set(self.0.get() - 1);
+                }
+                Global.deallocate(ptr, layout)
+            }
+        }
+
+        let live = Cell::new(0);
+        {
+            let mut cache = LruCache::new_in(50, Counting(&live));
+            for i in 0..100 {
+                cache.put(i, i);
+            }
+            assert!(live.get() > 0);
+            assert_eq!(cache.len(), 50);
+            cache.clear();
+            cache.shrink_to_fit();
+
+            let limiter = CostLimited::with_func(10, (|_: &u32| 1, |_: &u32| 1));
+            let mut cache = LruCache::with_limiter_and_hasher_in(
+                limiter,
+                DefaultHasher::default(),
+                Counting(&live),
+            );
+            for i in 0..100 {
+                cache.put(i, i);
+            }
+            assert_eq!(cache.len(), 5);
+        }
+        assert_eq!(live.get(), 0);
+    }
+
+    #[test]
+    fn test_try_reserve() {
+        use allocator_api2::alloc::{AllocError, Allocator, Global, Layout};
+        use allocator_api2::collections::TryReserveErrorKind;
+        use core::ptr::NonNull;
+
+        // fails any allocation once the budget of bytes is used up
+        #[derive(Clone, Copy)]
+        struct Budget<'a>(&'a Cell<usize>);
+
+        unsafe impl<'a> Allocator for Budget<'a> {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                let left = self.0.get().checked_sub(layout.size()).ok_or(AllocError)?;
+                self.0.set(left);
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                Global.deallocate(ptr, layout)
+            }
+        }
+
+        let budget = Cell::new(4096);
+        let mut cache = LruCache::with_limiter_and_hasher_in(
+            SizeLimited::new(1000),
+            DefaultHasher::default(),
+            Budget(&budget),
+        );
+        assert_eq!(
+            cache.try_reserve(usize::MAX).unwrap_err().kind(),
+            TryReserveErrorKind::CapacityOverflow
+        );
+        match cache.try_reserve(1000).unwrap_err().kind() {
+            TryReserveErrorKind::AllocError { .. } => {}
+            kind => panic!("unexpected error {:?}", kind),
+        }
+
+        // the cache is still usable after a failure
+        assert!(cache.try_reserve(10).is_ok());
+        let left = budget.get();
+        for i in 0..10 {
+            cache.put(i, i);
+        }
+        assert_eq!(budget.get(), left);
+        assert_eq!(cache.len(), 10);
+    }
+
+    #[test]
+    fn test_max_free_nodes() {
+        for &max in &[0, 8, usize::MAX] {
+            let mut cache = LruCache::unbounded();
+            let mut expected = LruCache::unbounded();
+            cache.set_max_free_nodes(max);
+            let mut state = 1u32;
+            for round in 0..20_000u32 {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let key = (state >> 16) % 200;
+                // alternate between growing and shrinking phases
+                if (round / 1000) & 1 == 0 || state & 3 == 0 {
+                    assert_eq!(cache.put(key, round), expected.put(key, round));
+                } else {
+                    assert_eq!(cache.pop_lru(), expected.pop_lru());
+                }
+                if round == 10_000 {
+                    cache.set_max_free_nodes(max / 2);
+                }
+            }
+            assert!(cache.iter().eq(expected.iter()));
+            cache.clear();
+            cache.shrink_to_fit();
+            cache.put(0, 0);
+            assert_eq!(cache.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_entry_handles() {
+        let mut cache = LruCache::new(2);
+        let a = cache.put_handle("a", 1).unwrap();
+        let b = cache.entry("b").insert(2).handle();
+        assert_eq!(cache.entry("a").handle(), Some(a));
+        assert_eq!(cache.put_handle("a", 3), Some(a));
+        assert_eq!(cache.get_by_handle(&a), Some(&3));
+        assert!(cache.promote_handle(&b));
+
+        // evicting "a" stores "c" in its node, which must not revive the handle
+        let c = cache.put_handle("c", 4).unwrap();
+        assert_ne!(a, c);
+        assert_eq!(cache.get_by_handle(&a), None);
+        assert!(!cache.promote_handle(&a));
+        assert_eq!(cache.remove_handle(&a), None);
+        assert_eq!(cache.get_by_handle(&c), Some(&4));
+
+        // neither does reusing the node of a removed entry
+        assert_eq!(cache.remove_handle(&b), Some(("b", 2)));
+        let d = cache.put_handle("d", 5).unwrap();
+        assert_eq!(cache.get_by_handle(&b), None);
+        assert_eq!(cache.get_by_handle(&d), Some(&5));
+
+        // handles of other caches or released memory are rejected, even when the other cache
+        //  stored an entry in the same slot with the same generation
+        let mut other = LruCache::new(2);
+        other.put("c", 4);
+        assert_eq!(other.get_by_handle(&c), None);
+        let mut first = LruCache::new(1);
+        let mut second = LruCache::new(1);
+        let handle = first.put_handle("a", 1).unwrap();
+        second.put("a", 2);
+        assert_eq!(second.get_by_handle(&handle), None);
+        let dropped = LruCache::new(1).put_handle("a", 3).unwrap();
+        assert_eq!(first.get_by_handle(&dropped), None);
+        assert_eq!(first.get_by_handle(&handle), Some(&1));
+        cache.clear();
+        cache.shrink_to_fit();
+        assert_eq!(cache.get_by_handle(&c), None);
+        assert_eq!(cache.remove_handle(&d), None);
+
+        let mut cache = LruCache::new(0);
+        assert_eq!(cache.put_handle("a", 1), None);
+    }
+
+    #[test]
+    fn test_cursor_mut() {
+        fn keys<L: Limiter<u32, usize, DefaultHasher>>(
+            cache: &LruCache<u32, usize, L>,
+        ) -> Vec<u32> {
+            cache.iter().map(|(key, _)| *key).collect()
+        }
+
+        let mut cache =
+            LruCache::with_limiter(CostLimited::with_func(10, (|_: &u32| 0, |v: &usize| *v)));
+        let mut cursor = cache.cursor_mru();
+        assert_eq!(cursor.key(), None);
+        assert_eq!(cursor.remove_current(), None);
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.insert_before(1, 1), None);
+        assert_eq!(cursor.insert_after(2, 2), None);
+        assert_eq!(cursor.insert_before(3, 3), None);
+        assert_eq!(keys(&cache), [2, 1, 3]);
+        assert_eq!(cache.limiter().current(), 6);
+
+        // the entry under the cursor and the inserted one are kept, even if least recently used
+        let mut cursor = cache.cursor_lru();
+        assert_eq!(cursor.insert_after(4, 5), Some((1, 1)));
+        assert_eq!(cursor.insert_after(5, 11), Some((5, 11)));
+        assert_eq!(cursor.insert_before(2, 3), Some((2, 2)));
+        assert_eq!(keys(&cache), [2, 3]);
+        assert_eq!(cache.limiter().current(), 6);
+
+        let mut cursor = cache.cursor_at(&2).unwrap();
+        assert_eq!(cursor.current(), Some((&2, &mut 3)));
+        assert_eq!(cursor.remove_current(), Some((2, 3)));
+        assert_eq!(cursor.key(), Some(&3));
+        cursor.move_prev();
+        assert_eq!(cursor.key(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.key(), Some(&3));
+        assert_eq!(cache.limiter().current(), 3);
+
+        // splicing evicts only once every entry is in place
+        let mut other = LruCache::unbounded();
+        other.put(6, 2);
+        other.put(4, 1);
+        other.put(7, 2);
+        cache.cursor_at(&3).unwrap().splice_after(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(keys(&cache), [3, 7, 4, 6]);
+        assert_eq!(cache.limiter().current(), 8);
+        other.put(8, 5);
+        other.put(9, 1);
+        cache.cursor_at(&4).unwrap().splice_before(&mut other);
+        assert_eq!(keys(&cache), [3, 7, 9, 4]);
+        assert_eq!(cache.limiter().current(), 7);
+
+        while cache.cursor_mru().remove_current().is_some() {}
+        assert_eq!(cache.limiter().current(), 0);
+    }
+
+    #[test]
+    fn test_put_at_position() {
+        let mut cache = LruCache::new(3);
+        assert_eq!(cache.put_lru(1, "a"), None);
+        assert_eq!(cache.put_lru(2, "b"), None);
+        assert_eq!(cache.put(3, "c"), None);
+        assert_eq!(cache.put_lru(1, "alpha"), Some("a"));
+        let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [3, 2, 1]);
+
+        // a full cache evicts its least recently used entry before the new one takes its place
+        assert_eq!(cache.put_lru(4, "d"), None);
+        assert_eq!(cache.peek_lru(), Some((&4, &"d")));
+        assert!(!cache.contains(&1));
+
+        // the anchor is never evicted, even when it is the least recently used entry
+        assert_eq!(cache.put_after(&4, 5, "e"), Ok(None));
+        let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [3, 4, 5]);
+        assert_eq!(cache.put_after(&3, 3, "gamma"), Ok(Some("c")));
+        assert_eq!(cache.put_after(&1, 1, "a"), Err((1, "a")));
+
+        if let Entry::Occupied(mut entry) = cache.entry(3) {
+            assert!(entry.move_before(&5));
+            assert!(entry.move_before(&3));
+        }
+        let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [4, 3, 5]);
+
+        let mut cache = LruCache::new(0);
+        assert_eq!(cache.put_lru(1, "a"), Some("a"));
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        fn keys<L: Limiter<u32, usize, DefaultHasher>>(
+            cache: &LruCache<u32, usize, L>,
+        ) -> Vec<u32> {
+            cache.iter().map(|(key, _)| *key).collect()
+        }
+
+        let limiter = CostLimited::with_func(10, (|_: &u32| 0, |v: &usize| *v));
+        let mut cache = LruCache::with_limiter(limiter);
+        for key in 1..=5 {
+            cache.put(key, key as usize - 1);
+        }
+        assert_eq!(cache.limiter().current(), 10);
+
+        // the split off cache starts out with its own accounting
+        let cold = cache.split_off_lru(2);
+        assert_eq!(keys(&cold), [2, 1]);
+        assert_eq!(cold.limiter().current(), 1);
+        assert_eq!(cold.limiter().limit(), 10);
+        assert_eq!(keys(&cache), [5, 4, 3]);
+        assert_eq!(cache.limiter().current(), 9);
+
+        let even = cache.split_off_where(|key, _| key % 2 == 0);
+        assert_eq!(keys(&even), [4]);
+        assert_eq!(even.limiter().current(), 3);
+        assert_eq!(cache.limiter().current(), 6);
+        assert!(cache.split_off_where(|_, _| false).is_empty());
+        assert_eq!(cache.split_off_lru(10).len(), 2);
+        assert!(cache.is_empty());
+        assert_eq!(cache.limiter().current(), 0);
+
+        // evictions happen after the merge, so the merged entries are evicted first when they
+        //  become the least recently used ones
+        cache.put(6, 5);
+        cache.put(4, 1);
+        cache.merge(cold, MergePolicy::PreferSelf);
+        assert_eq!(keys(&cache), [4, 6, 2, 1]);
+        cache.put(7, 4);
+        assert_eq!(keys(&cache), [7, 4, 6]);
+        cache.merge(even, MergePolicy::PreferSelf);
+        assert_eq!(keys(&cache), [7, 4, 6]);
+        assert_eq!(cache.limiter().current(), 10);
+
+        let mut other = LruCache::unbounded();
+        other.put(6, 1);
+        other.put(8, 2);
+        cache.append(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(keys(&cache), [8, 6, 7, 4]);
+        assert_eq!(cache.limiter().current(), 8);
+    }
+
+    // Encodes `u32` keys as little-endian bytes and `String` values as UTF-8
+    #[cfg(not(feature = "no_std"))]
+    struct Utf8;
+
+    #[cfg(not(feature = "no_std"))]
+    impl super::Codec<u32, alloc::string::String> for Utf8 {
+        fn encode_key(&self, key: &u32, buf: &mut Vec<u8>) {
+            buf.extend_from_slice(&key.to_le_bytes());
+        }
+        fn encode_value(&self, value: &alloc::string::String, buf: &mut Vec<u8>) {
+            buf.extend_from_slice(value.as_bytes());
+        }
+        fn decode_key(&self, bytes: &[u8]) -> Option<u32> {
+            use std::convert::TryInto;
+            Some(u32::from_le_bytes(bytes.try_into().ok()?))
+        }
+        fn decode_value(&self, bytes: &[u8]) -> Option<alloc::string::String> {
+            alloc::string::String::from_utf8(bytes.to_vec()).ok()
+        }
+    }
+
+    #[test]
+    fn test_memoized() {
+        use super::{MaybeCached, Memoized};
+
+        let calls = Cell::new(0);
+        let mut square = Memoized::new(2, |x: &usize| {
+            calls.set(calls.get() + 1);
+            x * x
+        });
+        assert_eq!(*square.call(2), 4);
+        assert_eq!(*square.call(3), 9);
+        assert_eq!(*square.call(2), 4);
+        assert_eq!(calls.get(), 2);
+
+        // 3 is the least recently used, so it is evicted and computed again
+        assert_eq!(*square.call(4), 16);
+        assert_eq!(*square.call(3), 9);
+        assert_eq!(calls.get(), 4);
+        assert!(square.cache_mut().pop(&4).is_some());
+        assert_eq!(*square.call(4), 16);
+        assert_eq!(calls.get(), 5);
+
+        // results which the limiter rejects are returned but not cached
+        let limiter = CostLimited::with_func(10, (|_: &usize| 0, |v: &usize| *v));
+        let mut square = Memoized::with_cache(LruCache::with_limiter(limiter), |x: &usize| x * x);
+        assert_eq!(square.call(3), MaybeCached::Cached(&9));
+        assert_eq!(square.call(4), MaybeCached::Uncached(16));
+        assert_eq!(square.call(4).into_owned(), 16);
+        assert!(square.call(3).is_cached());
+        assert_eq!(square.cache().len(), 1);
+        assert_eq!(square.cache().peek(&3), Some(&9));
+        let (_, cache) = square.into_inner();
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_negative_cache() {
+        use super::{Lookup, NegativeCache};
+
+        let mut cache = NegativeCache::new(2, 3);
+        assert!(cache.is_empty());
+        assert_eq!(cache.put(1, 10), None);
+        assert_eq!(cache.put_absent(2), None);
+        assert_eq!(cache.get(&1), Lookup::Present(&10));
+        assert_eq!(cache.get(&2), Lookup::Absent);
+        assert_eq!(cache.get(&3), Lookup::Miss);
+        assert!(cache.peek(&2).is_absent() && cache.peek(&3).is_miss());
+        assert_eq!(cache.peek(&1).present(), Some(&10));
+
+        // missing keys never evict values
+        for k in 3..10 {
+            cache.put_absent(k);
+        }
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.absent_len(), 3);
+        assert_eq!(cache.get(&2), Lookup::Miss);
+        assert_eq!(cache.get(&9), Lookup::Absent);
+
+        // and values never evict missing keys
+        cache.put(10, 100);
+        cache.put(11, 110);
+        assert_eq!(cache.get(&1), Lookup::Miss);
+        assert_eq!(cache.absent_len(), 3);
+
+        // a key is either present or absent
+        assert_eq!(cache.put(9, 90), None);
+        assert_eq!(cache.get(&9), Lookup::Present(&90));
+        assert_eq!(cache.absent_len(), 2);
+        assert_eq!(cache.put_absent(9), Some(90));
+        assert_eq!(cache.get(&9), Lookup::Absent);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.pop(&9), None);
+        assert_eq!(cache.get(&9), Lookup::Miss);
+
+        cache.resize_absent(1);
+        assert_eq!((cache.absent_cap(), cache.absent_len()), (1, 1));
+        cache.clear_absent();
+        assert_eq!(cache.absent_len(), 0);
+        assert_eq!(cache.len(), 1);
+        cache.put_absent(1);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_two_level() {
+        use super::{InclusionPolicy, TwoLevel};
+
+        fn keys<L: Limiter<u32, u32, DefaultHasher>>(cache: &LruCache<u32, u32, L>) -> Vec<u32> {
+            cache.iter().map(|(k, _)| *k).collect()
+        }
+
+        let mut l2 = LruCache::new(3);
+        let mut cache = TwoLevel::new(LruCache::new(2), &mut l2, InclusionPolicy::Exclusive);
+        for i in 1..=6 {
+            assert_eq!(cache.put(i, i * 10), None);
+        }
+        assert_eq!(keys(cache.l1()), [6, 5]);
+        assert_eq!(keys(cache.l2()), [4, 3, 2]);
+        assert_eq!(cache.get(&2), Some(&20));
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(keys(cache.l1()), [2, 6]);
+        assert_eq!(keys(cache.l2()), [5, 4, 3]);
+        assert_eq!(cache.put(3, 31), Some(30));
+        assert_eq!(keys(cache.l1()), [3, 2]);
+        assert_eq!(keys(cache.l2()), [6, 5, 4]);
+        assert_eq!(cache.peek(&4), Some(&40));
+        assert_eq!(cache.invalidate(&4), Some(40));
+        assert_eq!(cache.invalidate(&3), Some(31));
+        assert!(!cache.contains(&4));
+        assert!(!cache.contains(&3));
+        drop(cache);
+        assert_eq!(keys(&l2), [6, 5]);
+
+        // Entries L1 rejects stay in L2
+        let l1 = LruCache::with_limiter(CostLimited::with_func(
+            25,
+            (|_: &u32| 0, |v: &u32| *v as usize),
+        ));
+        let mut cache = TwoLevel::new(l1, LruCache::new(3), InclusionPolicy::Exclusive);
+        cache.put(1, 10);
+        cache.put(2, 30);
+        assert_eq!(keys(cache.l1()), [1]);
+        assert_eq!(keys(cache.l2()), [2]);
+        assert_eq!(cache.get(&2), Some(&30));
+        assert_eq!(keys(cache.l1()), [1]);
+        assert_eq!(keys(cache.l2()), [2]);
+
+        let mut cache = TwoLevel::new(
+            LruCache::new(2),
+            LruCache::new(3),
+            InclusionPolicy::Inclusive,
+        );
+        for i in 1..=4 {
+            assert_eq!(cache.put(i, i * 10), None);
+        }
+        assert_eq!(keys(cache.l1()), [4, 3]);
+        assert_eq!(keys(cache.l2()), [4, 3, 2]);
+        assert_eq!(cache.get(&2), Some(&20));
+        assert_eq!(keys(cache.l1()), [2, 4]);
+        assert_eq!(keys(cache.l2()), [2, 4, 3]);
+        // L1 hits keep the entry fresh in L2
+        assert_eq!(cache.get(&4), Some(&40));
+        assert_eq!(keys(cache.l2()), [4, 2, 3]);
+        assert_eq!(cache.put(2, 21), Some(20));
+        assert_eq!(cache.l1().peek(&2), Some(&21));
+        assert_eq!(cache.l2().peek(&2), Some(&21));
+        assert_eq!(cache.invalidate(&2), Some(21));
+        assert!(!cache.contains(&2));
+        assert_eq!(keys(cache.l1()), [4]);
+        assert_eq!(keys(cache.l2()), [4, 3]);
+
+        // Entries evicted from L2 leave L1 too
+        let mut cache = TwoLevel::new(
+            LruCache::new(2),
+            LruCache::new(1),
+            InclusionPolicy::Inclusive,
+        );
+        cache.put(1, 10);
+        cache.put(2, 20);
+        assert_eq!(keys(cache.l1()), [2]);
+        assert_eq!(keys(cache.l2()), [2]);
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn test_snapshot() {
+        use super::snapshot::Crc32;
+        use super::{LimiterKind, SnapshotError};
+        use alloc::string::String;
+
+        let mut cache = LruCache::with_limiter(CostLimited::with_func(
+            16,
+            (|_: &u32| 1, |v: &String| v.len()),
+        ));
+        cache.put(1, String::from("one"));
+        cache.put(2, String::from("two"));
+        cache.put(3, String::from("three"));
+        cache.get(&1);
+        let mut snapshot = Vec::new();
+        cache.write_snapshot(&mut snapshot, &Utf8).unwrap();
+        assert_eq!(&snapshot[..8], b"LRUSNAP\0");
+
+        let mut restored = LruCache::unbounded();
+        let info = restored.read_snapshot(&mut &snapshot[..], &Utf8).unwrap();
+        assert_eq!(info.limiter, LimiterKind::Cost(16));
+        assert_eq!(info.len, 3);
+        assert!(restored.iter().eq(cache.iter()));
+
+        // Only the most recent entries fit into a smaller cache
+        let mut small = LruCache::new(2);
+        small.put(9, String::from("nine"));
+        small.read_snapshot(&mut &snapshot[..], &Utf8).unwrap();
+        assert_eq!(small.iter().map(|(k, _)| *k).collect::<Vec<_>>(), [1, 3]);
+
+        // Trailing data is not consumed
+        let mut trailing = snapshot.clone();
+        trailing.push(42);
+        let mut reader = &trailing[..];
+        LruCache::unbounded()
+            .read_snapshot(&mut reader, &Utf8)
+            .unwrap();
+        assert_eq!(reader, [42]);
+
+        // Errors leave the cache untouched
+        for len in 0..snapshot.len() {
+            let err = small
+                .read_snapshot(&mut &snapshot[..len], &Utf8)
+                .unwrap_err();
+            assert!(matches!(err, SnapshotError::Truncated), "{}: {}", len, err);
+        }
+        let mut corrupted = snapshot.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        let err = small.read_snapshot(&mut &corrupted[..], &Utf8).unwrap_err();
+        assert!(matches!(err, SnapshotError::ChecksumMismatch { .. }));
+        let mut corrupted = snapshot.clone();
+        let value = corrupted.len() - 5;
+        corrupted[value] = 0xff;
+        let err = small.read_snapshot(&mut &corrupted[..], &Utf8).unwrap_err();
+        assert!(
+            matches!(err, SnapshotError::ChecksumMismatch { .. }),
+            "{}",
+            err
+        );
+        let mut crc = Crc32::new();
+        crc.update(&corrupted[..value + 1]);
+        corrupted[value + 1..].copy_from_slice(&crc.finish().to_le_bytes());
+        let err = small.read_snapshot(&mut &corrupted[..], &Utf8).unwrap_err();
+        assert!(matches!(err, SnapshotError::Decode(2)));
+        let mut corrupted = snapshot.clone();
+        corrupted[0] = b'X';
+        let err = small.read_snapshot(&mut &corrupted[..], &Utf8).unwrap_err();
+        assert!(matches!(err, SnapshotError::BadMagic));
+        let mut corrupted = snapshot.clone();
+        corrupted[8] = 2;
+        let err = small.read_snapshot(&mut &corrupted[..], &Utf8).unwrap_err();
+        assert!(matches!(err, SnapshotError::UnsupportedVersion(2)));
+        assert_eq!(small.iter().map(|(k, _)| *k).collect::<Vec<_>>(), [1, 3]);
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn test_journal() {
+        use super::{JournalError, JournaledCache};
+        use alloc::format;
+        use alloc::string::{String, ToString};
+        use std::fs::{self, OpenOptions};
+        use std::io::Write;
+
+        fn keys(journal: &JournaledCache<u32, String, Utf8>) -> Vec<u32> {
+            journal.cache().iter().map(|(k, _)| *k).collect()
+        }
+
+        let path = std::env::temp_dir().join(format!("lru-test-journal-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        // A new journal is written next to the path and renamed into place, replacing whatever an
+        // earlier crash left behind
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".compact");
+        fs::write(&tmp_path, b"").unwrap();
+        let mut journal = JournaledCache::open(&path, LruCache::new(3), Utf8).unwrap();
+        assert!(!std::path::Path::new(&tmp_path).exists());
+        assert!(fs::metadata(&path).unwrap().len() > 0);
+        for i in 0..4 {
+            assert_eq!(journal.put(i, i.to_string()).unwrap(), None);
+        }
+        assert_eq!(journal.get(&1).unwrap().unwrap(), "1");
+        assert_eq!(journal.get(&0).unwrap(), None);
+        assert_eq!(journal.pop(&2).unwrap().unwrap(), "2");
+        journal.promote(&3).unwrap();
+        assert_eq!(journal.records(), 7);
+        assert_eq!(keys(&journal), [3, 1]);
+        drop(journal);
+
+        let mut journal = JournaledCache::open(&path, LruCache::new(3), Utf8).unwrap();
+        assert_eq!(journal.records(), 7);
+        assert_eq!(keys(&journal), [3, 1]);
+        journal.put(4, "four".to_string()).unwrap();
+        journal.sync().unwrap();
+        drop(journal);
+
+        // A torn last record is dropped and truncated away
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+        let mut journal = JournaledCache::open(&path, LruCache::new(3), Utf8).unwrap();
+        assert_eq!(keys(&journal), [3, 1]);
+        journal.put(5, "five".to_string()).unwrap();
+        drop(journal);
+        let journal = JournaledCache::open(&path, LruCache::new(3), Utf8).unwrap();
+        assert_eq!(keys(&journal), [5, 3, 1]);
+        drop(journal);
+
+        // So is a garbled one
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[1, 4, 0, 0, 0, 42])
+            .unwrap();
+        let mut journal = JournaledCache::open(&path, LruCache::new(3), Utf8).unwrap();
+        assert_eq!(keys(&journal), [5, 3, 1]);
+
+        // Compaction replaces the records with a snapshot
+        journal.set_compact_after(2);
+        journal.put(6, "six".to_string()).unwrap();
+        assert_eq!(journal.records(), 1);
+        journal.get(&3).unwrap();
+        journal.pop(&5).unwrap();
+        assert_eq!(journal.records(), 1);
+        assert_eq!(keys(&journal), [3, 6]);
+        drop(journal);
+        let journal = JournaledCache::open(&path, LruCache::new(3), Utf8).unwrap();
+        assert_eq!(journal.records(), 1);
+        assert_eq!(keys(&journal), [3, 6]);
+        drop(journal);
+
+        // Corruption before the last record is an error
+        let mut bytes = fs::read(&path).unwrap();
+        let len = bytes.len();
+        bytes[len - 6] ^= 1;
+        bytes.extend_from_within(len - 10..);
+        fs::write(&path, &bytes).unwrap();
+        let err = JournaledCache::open(&path, LruCache::new(3), Utf8).unwrap_err();
+        assert!(matches!(err, JournalError::Corrupted(_)), "{}", err);
+
+        fs::write(&path, b"not a journal").unwrap();
+        let err = JournaledCache::open(&path, LruCache::new(3), Utf8).unwrap_err();
+        assert!(matches!(err, JournalError::BadHeader));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(all(not(feature = "no_std"), target_has_atomic = "ptr"))]
+    #[test]
+    fn test_tiered_cache() {
+        use super::{MaybeCached, TieredCache};
+        use alloc::format;
+        use alloc::string::{String, ToString};
+        use std::fs;
+        use std::io::{self, Write};
+
+        let path = std::env::temp_dir().join(format!("lru-test-tiered-{}", std::process::id()));
+        let mut cache = TieredCache::new(&path, LruCache::new(2), 3, Utf8).unwrap();
+        for i in 0..5 {
+            assert_eq!(cache.put(i, i.to_string()).unwrap(), None);
+        }
+        assert_eq!(cache.len(), 5);
+        assert_eq!(cache.disk_len(), 3);
+        assert_eq!(cache.disk_bytes(), 3);
+
+        // Hits on disk are promoted back to memory, demoting the memory tier's LRU entry
+        assert_eq!(*cache.get(&0).unwrap().unwrap(), "0");
+        assert_eq!(cache.peek(&0).unwrap(), "0");
+        assert!(cache.peek(&3).is_none());
+        assert!(cache.contains(&3));
+        assert_eq!(cache.put(1, "one".to_string()).unwrap().unwrap(), "1");
+        assert_eq!(cache.len(), 5);
+
+        // Values larger than the disk budget are dropped when spilled
+        cache.put(9, "too long".to_string()).unwrap();
+        cache.put(10, "x".to_string()).unwrap();
+        cache.put(11, "y".to_string()).unwrap();
+        assert_eq!(cache.pop(&9).unwrap(), None);
+        assert_eq!(cache.pop(&1).unwrap().unwrap(), "one");
+        assert_eq!(cache.pop(&10).unwrap().unwrap(), "x");
+        assert!(!cache.contains(&0));
+        assert_eq!(cache.len(), 1);
+
+        // The file is rewritten once it holds more unused space than the budget
+        for i in 20..100 {
+            cache.put(i, (i % 10).to_string()).unwrap();
+            if i >= 22 {
+                assert!(fs::metadata(&path).unwrap().len() <= 7);
+            }
+        }
+        for i in 95..100 {
+            assert_eq!(*cache.get(&i).unwrap().unwrap(), (i % 10).to_string());
+        }
+        assert_eq!(cache.len(), 5);
+        drop(cache);
+        assert!(!path.exists());
+
+        // Entries the memory tier rejects live on disk
+        let memory = LruCache::with_limiter(CostLimited::with_func(
+            4,
+            (|_: &u32| 0, |v: &String| v.len()),
+        ));
+        let mut cache = TieredCache::new(&path, memory, 16, Utf8).unwrap();
+        cache.put(1, "small".to_string()).unwrap();
+        cache.put(2, "tiny".to_string()).unwrap();
+        assert_eq!(cache.disk_len(), 1);
+        assert_eq!(
+            cache.get(&1).unwrap(),
+            Some(MaybeCached::Uncached("small".to_string()))
+        );
+        assert_eq!(cache.disk_len(), 1);
+        assert!(cache.peek(&1).is_none());
+        assert!(cache.get(&2).unwrap().unwrap().is_cached());
+        drop(cache);
+
+        // Entries whose value cannot be read stay on disk
+        let mut cache = TieredCache::new(&path, LruCache::new(1), 16, Utf8).unwrap();
+        cache.put(1, "a".to_string()).unwrap();
+        cache.put(2, "b".to_string()).unwrap();
+        let mut file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.write_all(&[0xff]).unwrap();
+        let err = cache.get(&1).unwrap_err();
+        assert_eq!(err.error.kind(), io::ErrorKind::InvalidData);
+        assert!(err.entries.is_empty());
+        assert_eq!(
+            cache.pop(&1).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        let err = cache.put(1, "c".to_string()).unwrap_err();
+        assert_eq!(err.entries, [(1, "c".to_string())]);
+        assert_eq!(err.old, None);
+        assert_eq!(cache.disk_len(), 1);
+        assert!(cache.contains(&1));
+        assert_eq!(cache.peek(&2).unwrap(), "b");
+    }
+
+    #[cfg(all(not(feature = "no_std"), target_has_atomic = "ptr"))]
+    #[test]
+    fn test_block_reader() {
+        use super::LruBlockReader;
+        use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+        // Counts the reads which reach the source
+        struct Counting(Cursor<Vec<u8>>, usize);
+
+        impl Read for Counting {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.1 += 1;
+                self.0.read(buf)
+            }
+        }
+
+        impl Seek for Counting {
+            fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+
+        let data: Vec<u8> = (0..100).collect();
+        // blocks of 10 bytes, four of them cached, two read ahead
+        let mut reader = LruBlockReader::new(Counting(Cursor::new(data.clone()), 0), 10, 40);
+        let mut buf = [0; 5];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [0, 1, 2, 3, 4]);
+        assert_eq!((reader.hits(), reader.misses()), (0, 1));
+        assert_eq!(reader.cached_blocks(), 3);
+        assert_eq!(reader.cached_bytes(), 30);
+
+        // the blocks read ahead are hits
+        let mut buf = [0; 25];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data[5..30]);
+        assert_eq!((reader.hits(), reader.misses()), (3, 1));
+        assert_eq!(reader.get_ref().1, 3);
+
+        // the requested block is cached after the ones read ahead, which evict the oldest blocks
+        let mut buf = [0; 10];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data[30..40]);
+        assert_eq!((reader.hits(), reader.misses()), (3, 2));
+        assert!(!reader.is_cached(0) && !reader.is_cached(1));
+        assert!(reader.is_cached(2) && reader.is_cached(3) && reader.is_cached(4));
+        assert!(reader.is_cached(5));
+
+        // random access does not read ahead, and the last block is short
+        assert_eq!(reader.seek(SeekFrom::Start(95)).unwrap(), 95);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &data[95..]);
+        assert!(!reader.is_cached(8));
+        assert_eq!(reader.seek(SeekFrom::End(-3)).unwrap(), 97);
+        let hits = reader.hits();
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [97, 98, 99]);
+        assert_eq!(reader.hits(), hits + 1);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(reader.seek(SeekFrom::Current(-50)).unwrap(), 50);
+        assert!(reader.seek(SeekFrom::Current(-51)).is_err());
+
+        // cleared blocks are read from the source again
+        reader.clear();
+        reader.reset_stats();
+        reader.seek(SeekFrom::Start(45)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [45, 46, 47]);
+        assert_eq!((reader.hits(), reader.misses()), (0, 1));
+
+        // blocks larger than the budget are read but not cached
+        let mut reader = LruBlockReader::new(Cursor::new(data.clone()), 10, 5);
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, data);
+        assert_eq!(reader.cached_blocks(), 0);
+        assert_eq!(reader.hits(), 0);
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn test_write_back() {
+        use super::WriteBackCache;
+        use std::time::Duration;
+
+        // records every entry offered to the flusher, and fails while `fail` is set
+        let offered = RefCell::new(Vec::new());
+        let fail = Cell::new(false);
+        let flusher = |k: &u32, v: &u32| {
+            offered.borrow_mut().push((*k, *v));
+            if fail.get() {
+                Err("store is down")
+            } else {
+                Ok(())
+            }
+        };
+        let mut cache = WriteBackCache::new(LruCache::new(2), flusher);
+
+        assert_eq!(cache.put_dirty(1, 10).unwrap(), None);
+        assert_eq!(cache.put(2, 20).unwrap(), None);
+        assert!(cache.is_dirty(&1) && !cache.is_dirty(&2));
+        assert_eq!(cache.dirty_len(), 1);
+
+        // only dirty entries are flushed on eviction
+        cache.put(3, 30).unwrap();
+        cache.put_dirty(4, 40).unwrap();
+        assert_eq!(*offered.borrow(), [(1, 10)]);
+
+        // overwriting a dirty entry with a clean one does not flush it
+        assert_eq!(cache.put_dirty(3, 31).unwrap(), Some(30));
+        assert_eq!(cache.put(3, 32).unwrap(), Some(31));
+        assert!(!cache.is_dirty(&3));
+
+        // evicted entries which fail to flush are handed back
+        fail.set(true);
+        let err = cache.put(5, 50).unwrap_err();
+        assert_eq!(err.error, "store is down");
+        assert_eq!(err.entries, [(4, 40)]);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.dirty_len(), 0);
+
+        // popped entries stay cached while they fail to flush
+        assert!(cache.mark_dirty(&5));
+        assert!(!cache.mark_dirty(&4));
+        assert_eq!(cache.pop(&5), Err("store is down"));
+        assert!(cache.contains(&5) && cache.is_dirty(&5));
+        fail.set(false);
+        assert_eq!(cache.pop(&5), Ok(Some(50)));
+        assert_eq!(cache.pop(&3), Ok(Some(32)));
+        assert_eq!(*offered.borrow(), [(1, 10), (4, 40), (5, 50), (5, 50)]);
+        offered.borrow_mut().clear();
+
+        // entries are flushed by age, and `get_mut` marks them as dirty
+        cache.put(6, 60).unwrap();
+        *cache.get_mut(&6).unwrap() += 1;
+        std::thread::sleep(Duration::from_millis(50));
+        cache.put_dirty(7, 70).unwrap();
+        cache.flush_older_than(Duration::from_millis(25)).unwrap();
+        assert_eq!(*offered.borrow(), [(6, 61)]);
+        assert!(!cache.is_dirty(&6) && cache.is_dirty(&7));
+        assert_eq!(cache.peek(&6), Some(&61));
+
+        // clearing flushes everything first
+        fail.set(true);
+        assert!(cache.clear().is_err());
+        assert_eq!(cache.len(), 2);
+        fail.set(false);
+        cache.clear().unwrap();
+        assert!(cache.is_empty());
+        assert_eq!(*offered.borrow(), [(6, 61), (7, 70), (7, 70)]);
+        offered.borrow_mut().clear();
+
+        // every dirty entry is offered on drop, even after errors
+        cache.put_dirty(8, 80).unwrap();
+        cache.put_dirty(9, 90).unwrap();
+        fail.set(true);
+        drop(cache);
+        offered.borrow_mut().sort_unstable();
+        assert_eq!(*offered.borrow(), [(8, 80), (9, 90)]);
+
+        // entries rejected by the cache are flushed right away
+        let offered = RefCell::new(Vec::new());
+        let flusher = |k: &u32, v: &u32| -> Result<(), ()> {
+            offered.borrow_mut().push((*k, *v));
+            Ok(())
+        };
+        let mut cache = WriteBackCache::new(LruCache::new(0), flusher);
+        cache.put_dirty(1, 10).unwrap();
+        cache.put(2, 20).unwrap();
+        assert!(cache.is_empty());
+        assert_eq!(cache.dirty_len(), 0);
+        drop(cache);
+        assert_eq!(*offered.borrow(), [(1, 10)]);
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn test_write_through() {
+        use super::{BackingStore, MaybeCached, WriteThroughCache};
+        use std::collections::HashMap;
+
+        let mut cache = WriteThroughCache::new(LruCache::new(2), HashMap::new());
+        assert_eq!(cache.put(1, 10).unwrap(), None);
+        assert_eq!(cache.put(2, 20).unwrap(), None);
+        assert_eq!(cache.put(2, 21).unwrap(), Some(20));
+        cache.put(3, 30).unwrap();
+        assert!(!cache.contains(&1));
+        assert_eq!(cache.store().len(), 3);
+
+        // misses read through and cache the value
+        assert_eq!(cache.get(&1).unwrap().as_deref(), Some(&10));
+        assert!(cache.contains(&1) && !cache.contains(&2));
+        assert_eq!(cache.get(&4).unwrap(), None);
+        assert_eq!(cache.invalidate(&1), Some(10));
+        assert_eq!(cache.store()[&1], 10);
+
+        assert_eq!(cache.remove(&3).unwrap(), Some(30));
+        assert_eq!(cache.remove(&2).unwrap(), None);
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&2).unwrap(), None);
+        let (_, store) = cache.into_inner();
+        assert_eq!(store.len(), 1);
+
+        // a store which holds nothing and fails while `down` is set
+        #[derive(Debug, PartialEq)]
+        struct Down;
+        struct Flaky {
+            down: bool,
+        }
+
+        impl BackingStore<u32, u32> for Flaky {
+            type Error = Down;
+
+            fn load(&mut self, key: &u32) -> Result<Option<u32>, Down> {
+                if self.down {
+                    Err(Down)
+                } else {
+                    Ok(Some(key * 10))
+                }
+            }
+
+            fn store(&mut self, _key: &u32, _value: &u32) -> Result<(), Down> {
+                if self.down {
+                    Err(Down)
+                } else {
+                    Ok(())
+                }
+            }
+
+            fn remove(&mut self, key: &u32) -> Result<(), Down> {
+                self.store(key, &0)
+            }
+        }
+
+        // failed writes leave the cache unchanged
+        let mut cache = WriteThroughCache::new(LruCache::new(2), Flaky { down: false });
+        cache.put(1, 11).unwrap();
+        cache.store_mut().down = true;
+        assert_eq!(cache.put(1, 12), Err(Down));
+        assert_eq!(cache.put(2, 20), Err(Down));
+        assert_eq!(cache.remove(&1), Err(Down));
+        assert_eq!(cache.get(&3), Err(Down));
+        assert_eq!(cache.get(&1), Ok(Some(MaybeCached::Cached(&11))));
+        assert_eq!(cache.len(), 1);
+        cache.store_mut().down = false;
+        assert_eq!(cache.get(&3), Ok(Some(MaybeCached::Cached(&30))));
+
+        // values rejected by the cache are still returned
+        let mut cache = WriteThroughCache::new(LruCache::new(0), Flaky { down: false });
+        assert_eq!(cache.get(&5), Ok(Some(MaybeCached::Uncached(50))));
+        assert!(cache.is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use super::{CostFn, Unlimited};
+        use serde::{Deserialize, Deserializer};
+        use serde_test::{assert_de_tokens, assert_de_tokens_error, assert_ser_tokens, Token};
+
+        #[derive(Debug, Default)]
+        struct ValueCost;
+
+        impl CostFn<u32, usize> for ValueCost {
+            fn key_cost(&self, _key: &u32) -> usize {
+                0
+            }
+
+            fn value_cost(&self, value: &usize) -> usize {
+                *value
+            }
+        }
+
+        // compares caches by their entries in recency order
+        struct Restored<L>(LruCache<u32, usize, L>);
+
+        impl<L: Limiter<u32, usize, DefaultHasher>> Debug for Restored<L> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.debug_list().entries(self.0.iter()).finish()
+            }
+        }
+
+        impl<L: Limiter<u32, usize, DefaultHasher>> PartialEq for Restored<L> {
+            fn eq(&self, other: &Self) -> bool {
+                self.0.iter().eq(other.0.iter())
+            }
+        }
+
+        impl<'de, L: Limiter<u32, usize, DefaultHasher> + Deserialize<'de>> Deserialize<'de>
+            for Restored<L>
+        {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                Ok(Restored(LruCache::deserialize(deserializer)?))
+            }
+        }
+
+        fn tokens(limiter: &'static str, limit: u64, entries: &[(u32, u64)]) -> Vec<Token> {
+            let mut tokens = vec![
+                Token::Struct {
+                    name: "LruCache",
+                    len: 2,
+                },
+                Token::Str("limiter"),
+                Token::Struct {
+                    name: limiter,
+                    len: 1,
+                },
+                Token::Str("limit"),
+                Token::U64(limit),
+                Token::StructEnd,
+                Token::Str("entries"),
+                Token::Seq {
+                    len: Some(entries.len()),
+                },
+            ];
+            for &(key, value) in entries {
+                tokens.extend([
+                    Token::Tuple { len: 2 },
+                    Token::U32(key),
+                    Token::U64(value),
+                    Token::TupleEnd,
+                ]);
+            }
+            tokens.extend([Token::SeqEnd, Token::StructEnd]);
+            tokens
+        }
+
+        let mut cache = LruCache::new(3);
+        cache.put(1, 10);
+        cache.put(2, 20);
+        cache.put(3, 30);
+        cache.get(&1);
+        let cache_tokens = tokens("SizeLimited", 3, &[(2, 20), (3, 30), (1, 10)]);
+        assert_ser_tokens(&cache, &cache_tokens);
+        let restored = Restored(cache);
+        assert_de_tokens(&restored, &cache_tokens);
+
+        let mut cache = LruCache::with_limiter(CostLimited::with_func(10, ValueCost));
+        cache.put(1, 4);
+        cache.put(2, 6);
+        let cache_tokens = tokens("CostLimited", 10, &[(1, 4), (2, 6)]);
+        assert_ser_tokens(&cache, &cache_tokens);
+        let restored = Restored(cache);
+        assert_de_tokens(&restored, &cache_tokens);
+        assert_eq!(restored.0.limiter().current(), 10);
+
+        // the limiter may come after the entries, and structs may be sequences
+        let mut cache = LruCache::with_limiter(LocalCostLimited::with_func(10, ValueCost));
+        cache.put(1, 4);
+        cache.put(2, 6);
+        let restored = Restored(cache);
+        assert_de_tokens(
+            &restored,
+            &[
+                Token::Map { len: Some(2) },
+                Token::Str("entries"),
+                Token::Seq { len: Some(2) },
+                Token::Tuple { len: 2 },
+                Token::U32(1),
+                Token::U64(4),
+                Token::TupleEnd,
+                Token::Tuple { len: 2 },
+                Token::U32(2),
+                Token::U64(6),
+                Token::TupleEnd,
+                Token::SeqEnd,
+                Token::Str("limiter"),
+                Token::Seq { len: Some(1) },
+                Token::U64(10),
+                Token::SeqEnd,
+                Token::MapEnd,
+            ],
+        );
+        let restored = Restored(LruCache::with_limiter(Unlimited));
+        assert_de_tokens(
+            &restored,
+            &[
+                Token::Seq { len: Some(2) },
+                Token::UnitStruct { name: "Unlimited" },
+                Token::Seq { len: Some(0) },
+                Token::SeqEnd,
+                Token::SeqEnd,
+            ],
+        );
+
+        // errors are raised right after the offending entry
+        let limited = |entries| {
+            let mut tokens = tokens("CostLimited", 10, entries);
+            tokens.truncate(tokens.len() - 2);
+            tokens
+        };
+        assert_de_tokens_error::<Restored<CostLimited<ValueCost>>>(
+            &limited(&[(1, 4), (1, 4)]),
+            "duplicate key in entry 1",
+        );
+        assert_de_tokens_error::<Restored<CostLimited<ValueCost>>>(
+            &limited(&[(1, 4), (2, 7)]),
+            "invalid length 2, expected at most as many entries as the limiter allows",
+        );
+        assert_de_tokens_error::<Restored<CostLimited<ValueCost>>>(
+            &limited(&[(1, 11)]),
+            "entry 0 was rejected by the limiter",
+        );
+        assert_de_tokens_error::<Restored<SizeLimited>>(
+            &[
+                Token::Struct {
+                    name: "LruCache",
+                    len: 1,
+                },
+                Token::Str("entries"),
+                Token::Seq { len: Some(0) },
+                Token::SeqEnd,
+                Token::StructEnd,
+            ],
+            "missing field `limiter`",
+        );
+    }
+
+    #[test]
+    fn test_heapless() {
+        let mut cache: HeaplessLruCache<u32, u32, 3> = HeaplessLruCache::new();
+        assert_eq!(cache.pop_lru(), None);
+        assert_eq!(cache.put(1, 10), None);
+        assert_eq!(cache.put(2, 20), None);
+        assert_eq!(cache.put(3, 30), None);
+        assert_eq!(cache.put(1, 11), Some(10));
+        assert_eq!(cache.push(4, 40), Some((2, 20)));
+        assert_eq!(cache.peek(&3), Some(&30));
+        assert_eq!(cache.peek_lru(), Some((&3, &30)));
+        assert_eq!(cache.get(&3), Some(&30));
+        assert_eq!(
+            cache.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(),
+            vec![(3, 30), (4, 40), (1, 11)]
+        );
+        assert_eq!(
+            cache.iter().rev().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![1, 4, 3]
+        );
+        assert_eq!(cache.pop(&4), Some(40));
+        assert_eq!(cache.pop_lru(), Some((1, 11)));
+        assert_eq!(cache.len(), 1);
+
+        let mut empty: HeaplessLruCache<u32, u32, 0> = HeaplessLruCache::new();
+        assert_eq!(empty.put(1, 1), Some(1));
+        assert_eq!(empty.push(1, 1), Some((1, 1)));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_heapless_matches_lru_cache() {
+        let mut heapless: HeaplessLruCache<u32, u32, 16> = HeaplessLruCache::new();
+        let mut cache = LruCache::new(16);
+        // a simple LCG keeps the sequence of operations deterministic
+        let mut state = 12345u32;
+        for _ in 0..10_000 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (state >> 16) % 40;
+            match state % 4 {
+                0 => assert_eq!(heapless.pop(&key), cache.pop(&key)),
+                1 => assert_eq!(heapless.get(&key), cache.get(&key)),
+                2 => assert_eq!(heapless.push(key, state), cache.push(key, state)),
+                _ => assert_eq!(heapless.put(key, state), cache.put(key, state)),
+            }
+            assert_eq!(heapless.len(), cache.len());
+        }
+        assert!(heapless.iter().eq(cache.iter()));
+    }
+
+    #[test]
+    fn test_heapless_drops_entries() {
+        let drops = RefCell::new(0);
+
+        struct DropCounter<'a>(&'a RefCell<usize>);
+
+        impl<'a> Drop for DropCounter<'a> {
+            fn drop(&mut self) {
+                *self.0.borrow_mut() += 1;
+            }
+        }
+
+        {
+            let mut cache: HeaplessLruCache<u32, DropCounter, 2> = HeaplessLruCache::new();
+            cache.put(1, DropCounter(&drops));
+            cache.put(2, DropCounter(&drops));
+            cache.put(3, DropCounter(&drops));
+            assert_eq!(*drops.borrow(), 1);
+            cache.put(2, DropCounter(&drops));
+            assert_eq!(*drops.borrow(), 2);
+        }
+        assert_eq!(*drops.borrow(), 4);
+    }
+
+    #[test]
+    fn test_heapless_in_static() {
+        static CACHE: std::sync::Mutex<HeaplessLruCache<u32, &str, 2>> =
+            std::sync::Mutex::new(HeaplessLruCache::new());
+
+        let mut cache = CACHE.lock().unwrap();
+        cache.put(1, "a");
+        assert_eq!(cache.get(&1), Some(&"a"));
+    }
+}
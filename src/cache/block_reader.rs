@@ -0,0 +1,323 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A block cache in front of a `Read + Seek` source.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use core::cmp;
+use core::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use {CostFn, CostLimited, LruCache, MaybeCached};
+
+/// The number of blocks an `LruBlockReader` reads ahead by default.
+pub const DEFAULT_PREFETCH: usize = 2;
+
+// Charges blocks for their length
+#[derive(Debug, Default)]
+struct BlockCost;
+
+impl CostFn<u64, Box<[u8]>> for BlockCost {
+    fn key_cost(&self, _key: &u64) -> usize {
+        0
+    }
+
+    fn value_cost(&self, block: &Box<[u8]>) -> usize {
+        block.len()
+    }
+}
+
+/// A reader which caches fixed-size blocks of a `Read + Seek` source in an `LruCache`.
+///
+/// Blocks are limited to a budget of bytes. Reads are served from cached blocks where possible and
+/// only go to the source for missing ones, so random access to a large file only reads each block
+/// once for as long as it stays cached. When a missing block directly follows the last block which
+/// was accessed, the blocks after it are read ahead as well. Reads never span more than one block,
+/// so use `read_exact` or `read_to_end` to fill a larger buffer.
+///
+/// The cached blocks are not invalidated when the source changes. Call `clear` after modifying it.
+///
+/// # Example
+///
+/// ```
+/// use lru::LruBlockReader;
+/// use std::io::{Cursor, Read, Seek, SeekFrom};
+///
+/// let data: Vec<u8> = (0..=255).collect();
+///
+/// // Blocks of 16 bytes, up to four of them cached
+/// let mut reader = LruBlockReader::new(Cursor::new(data), 16, 64);
+/// reader.set_prefetch(0);
+///
+/// let mut buf = [0; 4];
+/// reader.seek(SeekFrom::Start(100)).unwrap();
+/// reader.read_exact(&mut buf).unwrap();
+/// assert_eq!(buf, [100, 101, 102, 103]);
+///
+/// reader.seek(SeekFrom::Start(98)).unwrap();
+/// reader.read_exact(&mut buf).unwrap();
+/// assert_eq!(buf, [98, 99, 100, 101]);
+/// assert_eq!((reader.hits(), reader.misses()), (1, 1));
+/// ```
+pub struct LruBlockReader<R> {
+    inner: R,
+    block_size: usize,
+    cache: LruCache<u64, Box<[u8]>, CostLimited<BlockCost>>,
+    prefetch: usize,
+    // The position of the reader and, if known, of the source
+    pos: u64,
+    inner_pos: Option<u64>,
+    last_block: Option<u64>,
+    hits: u64,
+    misses: u64,
+}
+
+impl<R: Read + Seek> LruBlockReader<R> {
+    /// Creates a reader over `inner` which caches blocks of `block_size` bytes, up to a total of
+    /// `budget` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is zero.
+    pub fn new(inner: R, block_size: usize, budget: usize) -> Self {
+        assert!(block_size > 0, "block size must be non-zero");
+        LruBlockReader {
+            inner,
+            block_size,
+            cache: LruCache::with_limiter(CostLimited::with_func(budget, BlockCost)),
+            prefetch: DEFAULT_PREFETCH,
+            pos: 0,
+            inner_pos: None,
+            last_block: None,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the size of the blocks.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Returns the number of blocks read ahead on sequential access.
+    pub fn prefetch(&self) -> usize {
+        self.prefetch
+    }
+
+    /// Sets the number of blocks read ahead on sequential access. Zero disables prefetching.
+    pub fn set_prefetch(&mut self, blocks: usize) {
+        self.prefetch = blocks;
+    }
+
+    /// Returns the number of block lookups which were served from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of block lookups which had to read from the source. Blocks which are
+    /// read ahead are not counted.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Resets the hit and miss counters to zero.
+    pub fn reset_stats(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    /// Returns the number of cached blocks.
+    pub fn cached_blocks(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns the total size of the cached blocks in bytes.
+    pub fn cached_bytes(&self) -> usize {
+        self.cache.limiter().current()
+    }
+
+    /// Returns true if the block with the given index is cached.
+    pub fn is_cached(&self, index: u64) -> bool {
+        self.cache.contains(&index)
+    }
+
+    /// Removes all cached blocks, so that they are read from the source again.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.last_block = None;
+    }
+
+    /// Returns a reference to the source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the source. Seeking it does not change the position of the
+    /// reader, but modifying it requires a call to `clear`.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner_pos = None;
+        &mut self.inner
+    }
+
+    /// Returns the source, positioned anywhere.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Returns the block with the given index, which is empty past the end of the source. A block
+    //  which is too large for the cache is returned by value.
+    fn block(&mut self, index: u64) -> io::Result<MaybeCached<'_, Box<[u8]>>> {
+        let sequential = match self.last_block {
+            Some(last) => index == last + 1,
+            None => index == 0,
+        };
+        self.last_block = Some(index);
+        if self.cache.contains(&index) {
+            self.hits += 1;
+            let block = self.cache.get(&index).expect("block is cached");
+            return Ok(MaybeCached::Cached(block));
+        }
+
+        self.misses += 1;
+        let block = self.read_block(index)?;
+        if block.is_empty() {
+            return Ok(MaybeCached::Uncached(block));
+        }
+        if sequential && block.len() == self.block_size {
+            self.read_ahead(index);
+        }
+        // inserting the requested block last keeps it from being evicted by the ones read ahead
+        match self.cache.entry(index).try_insert(block) {
+            Ok(entry) => Ok(MaybeCached::Cached(entry.into_mut())),
+            Err((_, block)) => Ok(MaybeCached::Uncached(block)),
+        }
+    }
+
+    // Caches the blocks following `index`. Errors are ignored, since the blocks may never be read.
+    fn read_ahead(&mut self, index: u64) {
+        for next in index + 1..=index + self.prefetch as u64 {
+            if self.cache.contains(&next) {
+                continue;
+            }
+            let block = match self.read_block(next) {
+                Ok(block) => block,
+                Err(_) => break,
+            };
+            let complete = block.len() == self.block_size;
+            if !block.is_empty() {
+                self.cache.put(next, block);
+            }
+            if !complete {
+                break;
+            }
+        }
+    }
+
+    // Reads a block from the source, which is only short at its end
+    fn read_block(&mut self, index: u64) -> io::Result<Box<[u8]>> {
+        let start = index * self.block_size as u64;
+        if self.inner_pos != Some(start) {
+            self.inner_pos = None;
+            self.inner.seek(SeekFrom::Start(start))?;
+        }
+        let mut block = vec![0; self.block_size];
+        let mut len = 0;
+        while len < block.len() {
+            match self.inner.read(&mut block[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.inner_pos = None;
+                    return Err(e);
+                }
+            }
+        }
+        self.inner_pos = Some(start + len as u64);
+        block.truncate(len);
+        Ok(block.into_boxed_slice())
+    }
+}
+
+impl<R: Read + Seek> Read for LruBlockReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let block_size = self.block_size as u64;
+        let offset = (self.pos % block_size) as usize;
+        let block = self.block(self.pos / block_size)?;
+        if block.len() <= offset {
+            return Ok(0);
+        }
+        let n = cmp::min(buf.len(), block.len() - offset);
+        buf[..n].copy_from_slice(&block[offset..offset + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for LruBlockReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(pos) => {
+                self.pos = pos;
+                return Ok(pos);
+            }
+            SeekFrom::Current(offset) => (self.pos, offset),
+            SeekFrom::End(offset) => {
+                let len = self.inner.seek(SeekFrom::End(0))?;
+                self.inner_pos = Some(len);
+                (len, offset)
+            }
+        };
+        let pos = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.unsigned_abs())
+        };
+        match pos {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for LruBlockReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LruBlockReader")
+            .field("inner", &self.inner)
+            .field("block_size", &self.block_size)
+            .field("blocks", &self.cache.len())
+            .field("pos", &self.pos)
+            .field("hits", &self.hits)
+            .field("misses", &self.misses)
+            .finish()
+    }
+}
@@ -0,0 +1,520 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A write-ahead journal which makes an `LruCache` durable across restarts, available unless the
+//! `no_std` feature is enabled.
+//!
+//! A journal is a single file made of the magic bytes `LRUJRNL\0` and a `u16` format version,
+//! followed by a snapshot (see the `snapshot` module) and then by one record per operation
+//! performed since that snapshot was taken. A record is an operation tag as a `u8`, the length of
+//! its payload as a `u32`, the payload and a CRC-32 of all of the above as a `u32`. Integers are
+//! little-endian.
+//!
+//! Compaction writes a fresh snapshot to a temporary file and renames it over the journal, so a
+//! crash at any point leaves either the old or the new journal in place. A crash while appending
+//! can only tear the last record, which is discarded when the journal is reopened.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use super::snapshot::Crc32;
+use {Codec, DefaultHasher, Limiter, LruCache, SizeLimited, SnapshotError, SnapshotLimiter};
+
+const MAGIC: [u8; 8] = *b"LRUJRNL\0";
+const VERSION: u16 = 1;
+
+const OP_PUT: u8 = 1;
+const OP_POP: u8 = 2;
+const OP_PROMOTE: u8 = 3;
+
+/// The default number of records after which a `JournaledCache` compacts its journal.
+pub const DEFAULT_COMPACT_AFTER: usize = 1024;
+
+/// An error which occurred while opening a journal.
+#[derive(Debug)]
+pub enum JournalError {
+    /// Reading or writing the journal file failed.
+    Io(io::Error),
+    /// The journal does not start with the journal magic bytes and a supported version.
+    BadHeader,
+    /// The snapshot at the start of the journal could not be read.
+    Snapshot(SnapshotError),
+    /// The record at the given byte offset is corrupted. Unlike a torn last record, this is not
+    /// the result of an interrupted append.
+    Corrupted(u64),
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::Io(err) => write!(f, "journal i/o failed: {}", err),
+            JournalError::BadHeader => f.write_str("not an lru journal"),
+            JournalError::Snapshot(err) => write!(f, "invalid journal snapshot: {}", err),
+            JournalError::Corrupted(offset) => {
+                write!(f, "journal record at offset {} is corrupted", offset)
+            }
+        }
+    }
+}
+
+impl Error for JournalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JournalError::Io(err) => Some(err),
+            JournalError::Snapshot(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for JournalError {
+    fn from(err: io::Error) -> Self {
+        JournalError::Io(err)
+    }
+}
+
+impl From<SnapshotError> for JournalError {
+    fn from(err: SnapshotError) -> Self {
+        JournalError::Snapshot(err)
+    }
+}
+
+// Wraps a reader, counting the bytes read through it
+struct CountingReader<R> {
+    inner: R,
+    offset: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+
+// The outcome of reading one record
+enum Record {
+    Valid(u8, Vec<u8>),
+    End,
+    Torn,
+    Corrupted,
+}
+
+fn read_record(reader: &mut impl Read) -> io::Result<Record> {
+    let mut op = [0];
+    if reader.read(&mut op)? == 0 {
+        return Ok(Record::End);
+    }
+    let mut len = [0; 4];
+    if read_full(reader, &mut len)? < len.len() {
+        return Ok(Record::Torn);
+    }
+    let len = u32::from_le_bytes(len) as u64;
+    let mut payload = Vec::new();
+    let mut checksum = [0; 4];
+    if reader.take(len).read_to_end(&mut payload)? as u64 != len
+        || read_full(reader, &mut checksum)? < checksum.len()
+    {
+        return Ok(Record::Torn);
+    }
+
+    let mut crc = Crc32::new();
+    crc.update(&op);
+    crc.update(&(len as u32).to_le_bytes());
+    crc.update(&payload);
+    if crc.finish() == u32::from_le_bytes(checksum) {
+        Ok(Record::Valid(op[0], payload))
+    } else if reader.read(&mut [0])? == 0 {
+        // A garbled last record is what an interrupted append leaves behind
+        Ok(Record::Torn)
+    } else {
+        Ok(Record::Corrupted)
+    }
+}
+
+// Syncs the directory containing `path`, which makes renaming a file to `path` durable. Only Unix
+//  supports opening directories to do so.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// Like `read_exact`, but returns the number of bytes read instead of failing at EOF
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(read)
+}
+
+/// An `LruCache` which records every `put`, `pop` and promotion in a write-ahead journal file, so
+/// that its contents survive restarts.
+///
+/// Each operation is appended to the journal before it is applied to the cache, and once
+/// `compact_after` records have accumulated, the journal is compacted into a snapshot of the
+/// cache. Appends are handed to the operating system immediately; call `sync` to make them
+/// durable across power loss.
+///
+/// Replaying the journal repeats the recorded operations, including the evictions they caused, so
+/// the cache should be reopened with the same kind of limiter it was journaled with.
+///
+/// # Example
+///
+/// ```
+/// use lru::{Codec, JournaledCache, LruCache};
+/// use std::convert::TryInto;
+///
+/// struct Le;
+///
+/// impl Codec<u32, u32> for Le {
+///     fn encode_key(&self, key: &u32, buf: &mut Vec<u8>) {
+///         buf.extend_from_slice(&key.to_le_bytes());
+///     }
+///     fn encode_value(&self, value: &u32, buf: &mut Vec<u8>) {
+///         buf.extend_from_slice(&value.to_le_bytes());
+///     }
+///     fn decode_key(&self, bytes: &[u8]) -> Option<u32> {
+///         Some(u32::from_le_bytes(bytes.try_into().ok()?))
+///     }
+///     fn decode_value(&self, bytes: &[u8]) -> Option<u32> {
+///         Some(u32::from_le_bytes(bytes.try_into().ok()?))
+///     }
+/// }
+///
+/// let path = std::env::temp_dir().join(format!("lru-journal-doc-{}", std::process::id()));
+/// # let _ = std::fs::remove_file(&path);
+///
+/// let mut cache = JournaledCache::open(&path, LruCache::new(2), Le).unwrap();
+/// cache.put(1, 10).unwrap();
+/// cache.put(2, 20).unwrap();
+/// cache.get(&1).unwrap();
+/// cache.put(3, 30).unwrap();
+/// drop(cache);
+///
+/// let cache = JournaledCache::open(&path, LruCache::new(2), Le).unwrap();
+/// assert_eq!(cache.cache().iter().collect::<Vec<_>>(), [(&3, &30), (&1, &10)]);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct JournaledCache<K, V, C, L = SizeLimited, S = DefaultHasher> {
+    cache: LruCache<K, V, L, S>,
+    codec: C,
+    path: PathBuf,
+    file: File,
+    records: usize,
+    compact_after: usize,
+    buf: Vec<u8>,
+}
+
+impl<K, V, C, L, S> JournaledCache<K, V, C, L, S>
+where
+    K: Hash + Eq,
+    C: Codec<K, V>,
+    L: Limiter<K, V, S> + SnapshotLimiter,
+    S: BuildHasher,
+{
+    /// Opens the journal at `path`, replaying it into `cache`, or creates it from the current
+    /// contents of `cache` if it does not exist yet.
+    ///
+    /// The replayed entries end up more recent than any entries already in `cache`. A torn last
+    /// record, left behind by a crash in the middle of an append, is discarded and truncated away.
+    pub fn open(
+        path: impl AsRef<Path>,
+        mut cache: LruCache<K, V, L, S>,
+        codec: C,
+    ) -> Result<Self, JournalError> {
+        let path = path.as_ref().to_path_buf();
+        let file = match OpenOptions::new().read(true).write(true).open(&path) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                // Create the journal the way `compact` replaces it, so a crash can't leave an
+                //  empty journal behind
+                return Ok(JournaledCache {
+                    file: Self::write_journal(&path, &cache, &codec)?,
+                    cache,
+                    codec,
+                    path,
+                    records: 0,
+                    compact_after: DEFAULT_COMPACT_AFTER,
+                    buf: Vec::new(),
+                });
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut reader = CountingReader {
+            inner: BufReader::new(&file),
+            offset: 0,
+        };
+        let mut header = [0; 10];
+        if read_full(&mut reader, &mut header)? < header.len()
+            || header[..8] != MAGIC
+            || header[8..] != VERSION.to_le_bytes()
+        {
+            return Err(JournalError::BadHeader);
+        }
+        cache.read_snapshot(&mut reader, &codec)?;
+
+        let mut records = 0;
+        loop {
+            let offset = reader.offset;
+            match read_record(&mut reader)? {
+                Record::Valid(op, payload) => {
+                    if !Self::replay(&mut cache, &codec, op, &payload) {
+                        return Err(JournalError::Corrupted(offset));
+                    }
+                    records += 1;
+                }
+                Record::End => break,
+                Record::Torn => {
+                    file.set_len(offset)?;
+                    break;
+                }
+                Record::Corrupted => return Err(JournalError::Corrupted(offset)),
+            }
+        }
+        drop(reader);
+
+        let mut file = file;
+        file.seek(SeekFrom::End(0))?;
+        Ok(JournaledCache {
+            cache,
+            codec,
+            path,
+            file,
+            records,
+            compact_after: DEFAULT_COMPACT_AFTER,
+            buf: Vec::new(),
+        })
+    }
+
+    // Applies a recorded operation to the cache. Returns false if the payload cannot be decoded.
+    fn replay(cache: &mut LruCache<K, V, L, S>, codec: &C, op: u8, payload: &[u8]) -> bool {
+        match op {
+            OP_PUT => {
+                if payload.len() < 4 {
+                    return false;
+                }
+                let (len, payload) = payload.split_at(4);
+                let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize;
+                if payload.len() < len {
+                    return false;
+                }
+                let (key, value) = payload.split_at(len);
+                match (codec.decode_key(key), codec.decode_value(value)) {
+                    (Some(key), Some(value)) => {
+                        cache.put(key, value);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            OP_POP | OP_PROMOTE => match codec.decode_key(payload) {
+                Some(key) if op == OP_POP => {
+                    cache.pop(&key);
+                    true
+                }
+                Some(key) => {
+                    cache.promote(&key);
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    // Appends a record to the journal, compacting it first if it has grown too long
+    fn append(&mut self, op: u8, key: &K, value: Option<&V>) -> io::Result<()> {
+        if self.records >= self.compact_after {
+            self.compact()?;
+        }
+
+        self.buf.clear();
+        self.buf.push(op);
+        self.buf.extend_from_slice(&[0; 4]);
+        if let Some(value) = value {
+            self.buf.extend_from_slice(&[0; 4]);
+            self.codec.encode_key(key, &mut self.buf);
+            let len = self.buf.len() - 9;
+            self.buf[5..9].copy_from_slice(&(len as u32).to_le_bytes());
+            self.codec.encode_value(value, &mut self.buf);
+        } else {
+            self.codec.encode_key(key, &mut self.buf);
+        }
+        let len = u32::try_from(self.buf.len() - 5)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "journal record too large"))?;
+        self.buf[1..5].copy_from_slice(&len.to_le_bytes());
+        let mut crc = Crc32::new();
+        crc.update(&self.buf);
+        let checksum = crc.finish();
+        self.buf.extend_from_slice(&checksum.to_le_bytes());
+
+        // a record which is only partly written, say because the disk is full, would be followed by
+        //  the next ones and make the journal unreadable, so cut it off again
+        let start = self.file.stream_position()?;
+        if let Err(err) = self.file.write_all(&self.buf) {
+            self.file.set_len(start)?;
+            self.file.seek(SeekFrom::Start(start))?;
+            return Err(err);
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Puts a key-value pair into the cache, journaling the operation first. Returns the old value
+    /// if the key was already present, like `LruCache::put`.
+    pub fn put(&mut self, k: K, v: V) -> io::Result<Option<V>> {
+        self.append(OP_PUT, &k, Some(&v))?;
+        Ok(self.cache.put(k, v))
+    }
+
+    /// Returns a reference to the value of the key in the cache and moves the key to the head of
+    /// the LRU list, journaling the promotion. Returns `None` without journaling anything if the
+    /// key is not present.
+    pub fn get(&mut self, k: &K) -> io::Result<Option<&V>> {
+        if self.cache.contains(k) {
+            self.append(OP_PROMOTE, k, None)?;
+        }
+        Ok(self.cache.get(k))
+    }
+
+    /// Returns a reference to the value of the key in the cache without updating the LRU list.
+    /// Nothing is journaled.
+    pub fn peek(&self, k: &K) -> Option<&V> {
+        self.cache.peek(k)
+    }
+
+    /// Moves the key to the head of the LRU list if it is present, journaling the promotion.
+    pub fn promote(&mut self, k: &K) -> io::Result<()> {
+        if self.cache.contains(k) {
+            self.append(OP_PROMOTE, k, None)?;
+            self.cache.promote(k);
+        }
+        Ok(())
+    }
+
+    /// Removes the key from the cache and returns its value, journaling the removal. Returns
+    /// `None` without journaling anything if the key is not present.
+    pub fn pop(&mut self, k: &K) -> io::Result<Option<V>> {
+        if !self.cache.contains(k) {
+            return Ok(None);
+        }
+        self.append(OP_POP, k, None)?;
+        Ok(self.cache.pop(k))
+    }
+
+    /// Rewrites the journal as a snapshot of the cache's current contents.
+    ///
+    /// The snapshot is written to a temporary file next to the journal, synced to disk and then
+    /// renamed over the journal, so the journal on disk is always complete. The directory is
+    /// synced as well, so that the rename survives power loss.
+    pub fn compact(&mut self) -> io::Result<()> {
+        self.file = Self::write_journal(&self.path, &self.cache, &self.codec)?;
+        self.records = 0;
+        Ok(())
+    }
+
+    // Writes a journal holding just a snapshot of `cache` to a temporary file and renames it to
+    //  `path`. Returns the new journal, ready for appends.
+    fn write_journal(path: &Path, cache: &LruCache<K, V, L, S>, codec: &C) -> io::Result<File> {
+        let mut tmp_path = OsString::from(path);
+        tmp_path.push(".compact");
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(&MAGIC)?;
+        tmp.write_all(&VERSION.to_le_bytes())?;
+        cache.write_snapshot(&mut tmp, codec)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        sync_dir(path)?;
+        Ok(tmp)
+    }
+
+    /// Flushes the journal to disk, making all operations so far durable.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+
+    /// Returns the number of records appended since the journal was last compacted.
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Returns the number of records after which the journal is compacted. Defaults to
+    /// `DEFAULT_COMPACT_AFTER`.
+    pub fn compact_after(&self) -> usize {
+        self.compact_after
+    }
+
+    /// Sets the number of records after which the journal is compacted.
+    pub fn set_compact_after(&mut self, records: usize) {
+        self.compact_after = records;
+    }
+
+    /// Returns the journaled cache. Modifications have to go through the `JournaledCache` so
+    /// that they are journaled.
+    pub fn cache(&self) -> &LruCache<K, V, L, S> {
+        &self.cache
+    }
+
+    /// Returns the path of the journal file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the cache, closing the journal.
+    pub fn into_inner(self) -> LruCache<K, V, L, S> {
+        self.cache
+    }
+}
+
+impl<K, V, C, L, S> fmt::Debug for JournaledCache<K, V, C, L, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JournaledCache")
+            .field("path", &self.path)
+            .field("records", &self.records)
+            .field("compact_after", &self.compact_after)
+            .finish()
+    }
+}
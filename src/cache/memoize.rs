@@ -0,0 +1,172 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Memoization of functions with an `LruCache`.
+
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::ops::Deref;
+
+use {DefaultHasher, Entry, Limiter, LruCache, SizeLimited};
+
+/// A value returned by `Memoized::call`, or by the lookups of other wrappers around an `LruCache`
+/// which cache the values they load. It is either borrowed from the cache or, if the cache's
+/// limiter rejected it, owned, since it isn't stored anywhere.
+///
+/// It dereferences to the value either way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaybeCached<'a, V> {
+    /// The value is cached.
+    Cached(&'a V),
+    /// The cache's limiter rejected the value, so it was not cached.
+    Uncached(V),
+}
+
+impl<'a, V> MaybeCached<'a, V> {
+    /// Returns true if the value is cached.
+    pub fn is_cached(&self) -> bool {
+        matches!(self, MaybeCached::Cached(_))
+    }
+
+    /// Returns the value, cloning it if it is cached.
+    pub fn into_owned(self) -> V
+    where
+        V: Clone,
+    {
+        match self {
+            MaybeCached::Cached(value) => value.clone(),
+            MaybeCached::Uncached(value) => value,
+        }
+    }
+}
+
+impl<'a, V> Deref for MaybeCached<'a, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        match self {
+            MaybeCached::Cached(value) => value,
+            MaybeCached::Uncached(value) => value,
+        }
+    }
+}
+
+/// A function whose results are memoized in an `LruCache`, keyed by its arguments.
+///
+/// Functions of several arguments take them as a tuple. The function should be pure: as long as
+/// the result for some arguments is cached, the function is not called for them again.
+///
+/// For memoizing `fn` items, see also the `lru_memoize` attribute enabled by the `macros`
+/// feature.
+///
+/// # Example
+///
+/// ```
+/// use lru::Memoized;
+///
+/// let mut calls = 0;
+/// let mut area = Memoized::new(2, |&(w, h): &(u32, u32)| {
+///     calls += 1;
+///     w * h
+/// });
+///
+/// assert_eq!(*area.call((2, 3)), 6);
+/// assert_eq!(*area.call((2, 3)), 6);
+/// assert_eq!(*area.call((4, 5)), 20);
+/// assert_eq!(area.cache().len(), 2);
+/// drop(area);
+/// assert_eq!(calls, 2);
+/// ```
+pub struct Memoized<F, K, V, L = SizeLimited, S = DefaultHasher> {
+    func: F,
+    cache: LruCache<K, V, L, S>,
+}
+
+impl<F: FnMut(&K) -> V, K: Hash + Eq, V> Memoized<F, K, V> {
+    /// Memoizes `func` in a new cache holding at most `cap` results.
+    pub fn new(cap: usize, func: F) -> Self {
+        Memoized::with_cache(LruCache::new(cap), func)
+    }
+}
+
+impl<F, K, V, L, S> Memoized<F, K, V, L, S>
+where
+    F: FnMut(&K) -> V,
+    K: Hash + Eq,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+{
+    /// Memoizes `func` in the given cache, which may already hold results.
+    pub fn with_cache(cache: LruCache<K, V, L, S>, func: F) -> Self {
+        Memoized { func, cache }
+    }
+
+    /// Returns the result of the function for `args`, calling it only if the result is not
+    /// cached yet. Results which the cache's limiter rejects are returned by value, without being
+    /// cached.
+    pub fn call(&mut self, args: K) -> MaybeCached<'_, V> {
+        match self.cache.entry(args) {
+            Entry::Occupied(entry) => MaybeCached::Cached(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let value = (self.func)(entry.key());
+                match entry.try_insert(value) {
+                    Ok(value) => MaybeCached::Cached(value),
+                    Err((_, value)) => MaybeCached::Uncached(value),
+                }
+            }
+        }
+    }
+
+    /// Returns the cache of results.
+    pub fn cache(&self) -> &LruCache<K, V, L, S> {
+        &self.cache
+    }
+
+    /// Returns a mutable reference to the cache of results, for example to invalidate some of
+    /// them.
+    pub fn cache_mut(&mut self) -> &mut LruCache<K, V, L, S> {
+        &mut self.cache
+    }
+
+    /// Returns the memoized function.
+    pub fn func(&self) -> &F {
+        &self.func
+    }
+
+    /// Returns the memoized function and the cache of its results.
+    pub fn into_inner(self) -> (F, LruCache<K, V, L, S>) {
+        (self.func, self.cache)
+    }
+}
+
+impl<F, K, V, L, S> fmt::Debug for Memoized<F, K, V, L, S>
+where
+    K: Hash + Eq,
+    L: Limiter<K, V, S> + fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Memoized")
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
@@ -0,0 +1,230 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Caching of keys which are known to be missing.
+
+use alloc::borrow::Borrow;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+
+use {DefaultHasher, Limiter, LruCache, SizeLimited};
+
+/// The result of a lookup in a `NegativeCache`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup<T> {
+    /// The key has a cached value.
+    Present(T),
+    /// The key is cached as missing.
+    Absent,
+    /// Nothing is cached for the key.
+    Miss,
+}
+
+impl<T> Lookup<T> {
+    /// Returns the value of a `Present` lookup.
+    pub fn present(self) -> Option<T> {
+        match self {
+            Lookup::Present(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the key is cached as missing.
+    pub fn is_absent(&self) -> bool {
+        matches!(self, Lookup::Absent)
+    }
+
+    /// Returns true if nothing is cached for the key.
+    pub fn is_miss(&self) -> bool {
+        matches!(self, Lookup::Miss)
+    }
+}
+
+/// An `LruCache` which also caches keys known to be missing, such as keys which a slow backend
+/// has no value for.
+///
+/// Keys recorded with `put_absent` are kept in a separate LRU list of their own, limited to a
+/// number of keys, so that they never evict values however many of them there are. Each of them
+/// only costs the memory of its key. A key is either present, absent or unknown: putting a value
+/// forgets that the key was absent and vice versa.
+///
+/// # Example
+///
+/// ```
+/// use lru::{Lookup, NegativeCache};
+///
+/// // Up to two values and four missing keys
+/// let mut cache = NegativeCache::new(2, 4);
+/// cache.put("apple", 3);
+/// for key in &["pear", "plum", "fig"] {
+///     cache.put_absent(*key);
+/// }
+///
+/// assert_eq!(cache.get(&"apple"), Lookup::Present(&3));
+/// assert_eq!(cache.get(&"pear"), Lookup::Absent);
+/// assert_eq!(cache.get(&"kiwi"), Lookup::Miss);
+/// assert_eq!(cache.len(), 1);
+/// assert_eq!(cache.absent_len(), 3);
+///
+/// cache.put("pear", 5);
+/// assert_eq!(cache.get(&"pear"), Lookup::Present(&5));
+/// ```
+pub struct NegativeCache<K, V, L = SizeLimited, S = DefaultHasher> {
+    values: LruCache<K, V, L, S>,
+    absent: LruCache<K, ()>,
+}
+
+impl<K: Hash + Eq, V> NegativeCache<K, V> {
+    /// Creates a cache holding at most `cap` values and `absent_cap` missing keys.
+    pub fn new(cap: usize, absent_cap: usize) -> Self {
+        NegativeCache::with_cache(LruCache::new(cap), absent_cap)
+    }
+}
+
+impl<K, V, L, S> NegativeCache<K, V, L, S>
+where
+    K: Hash + Eq,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+{
+    /// Creates a cache holding values in the given cache, which may already hold some, and at
+    /// most `absent_cap` missing keys.
+    pub fn with_cache(values: LruCache<K, V, L, S>, absent_cap: usize) -> Self {
+        NegativeCache {
+            values,
+            absent: LruCache::new(absent_cap),
+        }
+    }
+
+    /// Puts a value into the cache, returning the old value of the key, if any.
+    pub fn put(&mut self, k: K, v: V) -> Option<V> {
+        self.absent.pop(&k);
+        self.values.put(k, v)
+    }
+
+    /// Records a key as missing, returning the value which was cached for it, if any.
+    pub fn put_absent(&mut self, k: K) -> Option<V> {
+        let old = self.values.pop(&k);
+        self.absent.put(k, ());
+        old
+    }
+
+    /// Looks up a key and marks it as the most recently used of the values or missing keys.
+    pub fn get<Q>(&mut self, k: &Q) -> Lookup<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some(v) = self.values.get(k) {
+            Lookup::Present(v)
+        } else if self.absent.get(k).is_some() {
+            Lookup::Absent
+        } else {
+            Lookup::Miss
+        }
+    }
+
+    /// Looks up a key without updating its recency.
+    pub fn peek<Q>(&self, k: &Q) -> Lookup<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some(v) = self.values.peek(k) {
+            Lookup::Present(v)
+        } else if self.absent.contains(k) {
+            Lookup::Absent
+        } else {
+            Lookup::Miss
+        }
+    }
+
+    /// Forgets everything cached for a key, returning its value, if any.
+    pub fn pop<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.absent.pop(k);
+        self.values.pop(k)
+    }
+
+    /// Returns the number of cached values.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if no values and no missing keys are cached.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.absent.is_empty()
+    }
+
+    /// Returns the number of keys cached as missing.
+    pub fn absent_len(&self) -> usize {
+        self.absent.len()
+    }
+
+    /// Returns the maximum number of keys cached as missing.
+    pub fn absent_cap(&self) -> usize {
+        self.absent.cap()
+    }
+
+    /// Resizes the budget of missing keys, forgetting the least recently used ones if needed.
+    pub fn resize_absent(&mut self, absent_cap: usize) {
+        self.absent.resize(absent_cap);
+    }
+
+    /// Forgets all missing keys, keeping the values.
+    pub fn clear_absent(&mut self) {
+        self.absent.clear();
+    }
+
+    /// Removes all values and missing keys.
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.absent.clear();
+    }
+
+    /// Returns the cache of values.
+    pub fn values(&self) -> &LruCache<K, V, L, S> {
+        &self.values
+    }
+
+    /// Returns the cache of values, dropping the missing keys.
+    pub fn into_values(self) -> LruCache<K, V, L, S> {
+        self.values
+    }
+}
+
+impl<K, V, L, S> fmt::Debug for NegativeCache<K, V, L, S>
+where
+    K: Hash + Eq,
+    L: Limiter<K, V, S> + fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NegativeCache")
+            .field("values", &self.values)
+            .field("absent_len", &self.absent.len())
+            .finish()
+    }
+}
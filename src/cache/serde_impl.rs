@@ -0,0 +1,381 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! `Serialize` and `Deserialize` implementations for `LruCache` and the built-in limiters, enabled
+//! by the `serde` feature.
+//!
+//! A cache is serialized as a struct with its limiter's configuration and a sequence of key-value
+//! pairs in LRU to MRU order. Deserializing rebuilds the exact order without evicting anything:
+//! input which does not fit the limiter is an error rather than being silently truncated.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+
+use allocator_api2::alloc::{Allocator, Global};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+
+#[cfg(target_has_atomic = "ptr")]
+use CostLimited;
+use {CursorMut, Limiter, LocalCostLimited, LruCache, SizeLimited, Unlimited};
+
+const CACHE_FIELDS: &[&str] = &["limiter", "entries"];
+const LIMIT_FIELDS: &[&str] = &["limit"];
+
+impl<K, V, L, S, A> Serialize for LruCache<K, V, L, S, A>
+where
+    K: Hash + Eq + Serialize,
+    V: Serialize,
+    L: Limiter<K, V, S, A> + Serialize,
+    S: BuildHasher,
+    A: Allocator,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut state = serializer.serialize_struct("LruCache", CACHE_FIELDS.len())?;
+        state.serialize_field("limiter", &self.limiter)?;
+        state.serialize_field("entries", &Entries(self))?;
+        state.end()
+    }
+}
+
+// Serializes the entries of a cache in LRU to MRU order
+struct Entries<'a, K, V, L, S, A: Allocator>(&'a LruCache<K, V, L, S, A>);
+
+impl<'a, K, V, L, S, A> Serialize for Entries<'a, K, V, L, S, A>
+where
+    K: Hash + Eq + Serialize,
+    V: Serialize,
+    L: Limiter<K, V, S, A>,
+    S: BuildHasher,
+    A: Allocator,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_seq(self.0.iter_lru())
+    }
+}
+
+impl<'de, K, V, L, S> Deserialize<'de> for LruCache<K, V, L, S>
+where
+    K: Hash + Eq + Deserialize<'de>,
+    V: Deserialize<'de>,
+    L: Limiter<K, V, S> + Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("LruCache", CACHE_FIELDS, CacheVisitor(PhantomData))
+    }
+}
+
+#[allow(clippy::type_complexity)]
+struct CacheVisitor<K, V, L, S>(PhantomData<fn() -> LruCache<K, V, L, S>>);
+
+impl<'de, K, V, L, S> Visitor<'de> for CacheVisitor<K, V, L, S>
+where
+    K: Hash + Eq + Deserialize<'de>,
+    V: Deserialize<'de>,
+    L: Limiter<K, V, S> + Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    type Value = LruCache<K, V, L, S>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("struct LruCache")
+    }
+
+    fn visit_seq<Acc: SeqAccess<'de>>(self, mut seq: Acc) -> Result<Self::Value, Acc::Error> {
+        let limiter = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let mut cache = LruCache::with_limiter_and_hasher(limiter, S::default());
+        seq.next_element_seed(EntriesSeed(&mut cache))?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(cache)
+    }
+
+    fn visit_map<Acc: MapAccess<'de>>(self, mut map: Acc) -> Result<Self::Value, Acc::Error> {
+        let mut cache: Option<Self::Value> = None;
+        let mut entries_seen = false;
+        // entries which came before the limiter, and can only be inserted once it is known
+        let mut pending: Option<Vec<(K, V)>> = None;
+        while let Some(field) = map.next_key_seed(FieldSeed(CACHE_FIELDS))? {
+            match field {
+                Some(0) => {
+                    if cache.is_some() {
+                        return Err(de::Error::duplicate_field("limiter"));
+                    }
+                    let limiter = map.next_value()?;
+                    cache = Some(LruCache::with_limiter_and_hasher(limiter, S::default()));
+                }
+                Some(_) => {
+                    if entries_seen {
+                        return Err(de::Error::duplicate_field("entries"));
+                    }
+                    entries_seen = true;
+                    match &mut cache {
+                        Some(cache) => map.next_value_seed(EntriesSeed(cache))?,
+                        None => pending = Some(map.next_value()?),
+                    }
+                }
+                None => drop(map.next_value::<IgnoredAny>()?),
+            }
+        }
+        let mut cache = cache.ok_or_else(|| de::Error::missing_field("limiter"))?;
+        if !entries_seen {
+            return Err(de::Error::missing_field("entries"));
+        }
+        for (index, (k, v)) in pending.into_iter().flatten().enumerate() {
+            restore(&mut cache, k, v, index)?;
+        }
+        Ok(cache)
+    }
+}
+
+// Deserializes a sequence of entries in LRU to MRU order into a cache
+struct EntriesSeed<'a, K, V, L, S>(&'a mut LruCache<K, V, L, S>);
+
+impl<'a, 'de, K, V, L, S> DeserializeSeed<'de> for EntriesSeed<'a, K, V, L, S>
+where
+    K: Hash + Eq + Deserialize<'de>,
+    V: Deserialize<'de>,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+{
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'a, 'de, K, V, L, S> Visitor<'de> for EntriesSeed<'a, K, V, L, S>
+where
+    K: Hash + Eq + Deserialize<'de>,
+    V: Deserialize<'de>,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of key-value pairs")
+    }
+
+    fn visit_seq<Acc: SeqAccess<'de>>(self, mut seq: Acc) -> Result<(), Acc::Error> {
+        let mut index = 0;
+        while let Some((k, v)) = seq.next_element()? {
+            restore(self.0, k, v, index)?;
+            index += 1;
+        }
+        Ok(())
+    }
+}
+
+// Inserts the `index`th deserialized entry as the most recently used one, without evicting
+fn restore<K, V, L, S, E>(
+    cache: &mut LruCache<K, V, L, S, Global>,
+    k: K,
+    v: V,
+    index: usize,
+) -> Result<(), E>
+where
+    K: Hash + Eq,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+    E: de::Error,
+{
+    if cache.contains(&k) {
+        return Err(E::custom(format_args!("duplicate key in entry {}", index)));
+    }
+    let mut cursor = CursorMut { cache, node: None };
+    if cursor.link(k, v, true).is_err() {
+        return Err(E::custom(format_args!(
+            "entry {} was rejected by the limiter",
+            index
+        )));
+    }
+    if cursor.cache.limiter.is_oversized(cursor.cache) {
+        return Err(E::invalid_length(
+            index + 1,
+            &"at most as many entries as the limiter allows",
+        ));
+    }
+    Ok(())
+}
+
+// Deserializes a struct field name (or index) into its index in a list of fields, or `None` if
+//  the field is unknown
+struct FieldSeed(&'static [&'static str]);
+
+impl<'de> DeserializeSeed<'de> for FieldSeed {
+    type Value = Option<usize>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<usize>, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FieldSeed {
+    type Value = Option<usize>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a field identifier")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Option<usize>, E> {
+        Ok(Some(v as usize).filter(|&v| v < self.0.len()))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Option<usize>, E> {
+        Ok(self.0.iter().position(|field| *field == v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Option<usize>, E> {
+        Ok(self.0.iter().position(|field| field.as_bytes() == v))
+    }
+}
+
+impl Serialize for Unlimited {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.serialize_unit_struct("Unlimited")
+    }
+}
+
+impl<'de> Deserialize<'de> for Unlimited {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UnitVisitor;
+
+        impl<'de> Visitor<'de> for UnitVisitor {
+            type Value = Unlimited;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("unit struct Unlimited")
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<Unlimited, E> {
+                Ok(Unlimited)
+            }
+        }
+
+        deserializer.deserialize_unit_struct("Unlimited", UnitVisitor)
+    }
+}
+
+fn serialize_limit<Ser: Serializer>(
+    serializer: Ser,
+    name: &'static str,
+    limit: usize,
+) -> Result<Ser::Ok, Ser::Error> {
+    let mut state = serializer.serialize_struct(name, LIMIT_FIELDS.len())?;
+    state.serialize_field("limit", &limit)?;
+    state.end()
+}
+
+// Deserializes the limit of a limiter, which must be at most `max`
+fn deserialize_limit<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    name: &'static str,
+    max: usize,
+) -> Result<usize, D::Error> {
+    struct LimitVisitor(&'static str);
+
+    impl<'de> Visitor<'de> for LimitVisitor {
+        type Value = usize;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "struct {}", self.0)
+        }
+
+        fn visit_seq<Acc: SeqAccess<'de>>(self, mut seq: Acc) -> Result<usize, Acc::Error> {
+            seq.next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))
+        }
+
+        fn visit_map<Acc: MapAccess<'de>>(self, mut map: Acc) -> Result<usize, Acc::Error> {
+            let mut limit = None;
+            while let Some(field) = map.next_key_seed(FieldSeed(LIMIT_FIELDS))? {
+                match field {
+                    Some(_) if limit.is_some() => return Err(de::Error::duplicate_field("limit")),
+                    Some(_) => limit = Some(map.next_value()?),
+                    None => drop(map.next_value::<IgnoredAny>()?),
+                }
+            }
+            limit.ok_or_else(|| de::Error::missing_field("limit"))
+        }
+    }
+
+    let limit = deserializer.deserialize_struct(name, LIMIT_FIELDS, LimitVisitor(name))?;
+    if limit > max {
+        return Err(de::Error::invalid_value(
+            de::Unexpected::Unsigned(limit as u64),
+            &"a limit of at most MAX_LIMIT",
+        ));
+    }
+    Ok(limit)
+}
+
+impl Serialize for SizeLimited {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serialize_limit(serializer, "SizeLimited", self.limit())
+    }
+}
+
+impl<'de> Deserialize<'de> for SizeLimited {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let limit = deserialize_limit(deserializer, "SizeLimited", usize::MAX)?;
+        Ok(SizeLimited::new(limit))
+    }
+}
+
+/// Only the limit is serialized. The cost function is not, and is restored from its `Default`
+/// implementation.
+#[cfg(target_has_atomic = "ptr")]
+impl<F> Serialize for CostLimited<F> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serialize_limit(serializer, "CostLimited", self.limit())
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<'de, F: Default> Deserialize<'de> for CostLimited<F> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let limit = deserialize_limit(deserializer, "CostLimited", Self::MAX_LIMIT)?;
+        Ok(CostLimited::new(limit))
+    }
+}
+
+/// Only the limit is serialized. The cost function is not, and is restored from its `Default`
+/// implementation.
+impl<F> Serialize for LocalCostLimited<F> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serialize_limit(serializer, "LocalCostLimited", self.limit())
+    }
+}
+
+impl<'de, F: Default> Deserialize<'de> for LocalCostLimited<F> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let limit = deserialize_limit(deserializer, "LocalCostLimited", Self::MAX_LIMIT)?;
+        Ok(LocalCostLimited::new(limit))
+    }
+}
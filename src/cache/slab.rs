@@ -0,0 +1,453 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Slab storage for the nodes of an `LruCache`.
+//!
+//! Values are stored in chunks of contiguous slots. Chunks are never moved or resized once
+//! allocated, so pointers to values stay valid until the value is freed. Every slot also has a
+//! `u32` index, made of the number of its chunk and its offset in the chunk, which lets values
+//! link to each other without storing full pointers. Released chunks leave a gap in the list of
+//! chunks, which is filled by the next chunk, so indices never change either. Freed slots are kept
+//! on an intrusive free list and reused by later allocations. The number of free slots the slab
+//! keeps can be bounded, in which case chunks which become empty are released while more slots
+//! than that are free. One empty chunk is kept regardless, so that allocating and freeing values
+//! around the end of a chunk doesn't allocate and release it every time.
+//!
+//! Every allocation is tagged with a generation which is unique for the lifetime of the slab, and
+//! every slab has an id which is unique for the lifetime of the program, so that handles to values
+//! can be checked for whether they still refer to the same allocation of the same slab. Without
+//! atomics, all slabs share the same id.
+//!
+//! The chunks, and the list of chunks, are allocated with the slab's allocator. Nothing else is
+//! allocated, not even temporarily.
+
+use alloc::alloc::{handle_alloc_error, Layout};
+use allocator_api2::alloc::Allocator;
+use allocator_api2::collections::{TryReserveError, TryReserveErrorKind};
+use core::mem::{self, ManuallyDrop};
+use core::ptr::{self, NonNull};
+#[cfg(target_has_atomic = "ptr")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// The smallest chunk the slab allocates when it grows on its own
+const MIN_CHUNK: usize = 4;
+// The number of bits of an index which are the offset in its chunk
+const CHUNK_BITS: u32 = 16;
+// The largest chunk the slab allocates
+const MAX_CHUNK: usize = 1 << CHUNK_BITS;
+// The largest number of chunks the slab holds. The last chunk number is left out so that
+//  `u32::MAX` is never the index of a slot.
+const MAX_CHUNKS: usize = (u32::MAX >> CHUNK_BITS) as usize;
+
+// A slot either holds a live value or links to the next free slot. Its generation is zero while
+//  it is free.
+#[repr(C)]
+struct Slot<T> {
+    data: SlotData<T>,
+    generation: u64,
+    index: u32,
+}
+
+#[repr(C)]
+union SlotData<T> {
+    value: ManuallyDrop<T>,
+    next_free: *mut Slot<T>,
+}
+
+// Chunks are stored as raw parts rather than `Vec`s so that moving them never invalidates
+//  pointers handed out by the slab. A released chunk is kept as an empty gap.
+struct Chunk<T> {
+    start: NonNull<Slot<T>>,
+    len: usize,
+    // number of live values in the chunk
+    live: usize,
+    // set while the chunk is about to be released
+    release: bool,
+}
+
+pub(crate) struct Slab<T, A: Allocator> {
+    chunks: allocator_api2::vec::Vec<Chunk<T>, A>,
+    free: *mut Slot<T>,
+    capacity: usize,
+    len: usize,
+    max_free: usize,
+    // an empty chunk which is kept even though more than `max_free` slots are free
+    spare: Option<usize>,
+    // the last generation handed out
+    generation: u64,
+    id: usize,
+}
+
+impl<T, A: Allocator> Slab<T, A> {
+    pub(crate) fn new_in(alloc: A) -> Self {
+        Slab {
+            chunks: allocator_api2::vec::Vec::new_in(alloc),
+            free: ptr::null_mut(),
+            capacity: 0,
+            len: 0,
+            max_free: usize::MAX,
+            spare: None,
+            generation: 0,
+            id: next_id(),
+        }
+    }
+
+    pub(crate) fn id(&self) -> usize {
+        self.id
+    }
+
+    pub(crate) fn allocator(&self) -> &A {
+        self.chunks.allocator()
+    }
+
+    pub(crate) fn max_free(&self) -> usize {
+        self.max_free
+    }
+
+    /// Bounds the number of free slots kept for later allocations. Empty chunks are released
+    /// until at most `max_free` slots are free, and from then on whenever a chunk becomes empty
+    /// while more than `max_free` slots are free, except for one spare chunk.
+    pub(crate) fn set_max_free(&mut self, max_free: usize) {
+        self.max_free = max_free;
+        let mut free = self.capacity - self.len;
+        for chunk in self.chunks.iter_mut() {
+            if free <= max_free {
+                break;
+            }
+            if chunk.len > 0 && chunk.live == 0 {
+                chunk.release = true;
+                free -= chunk.len;
+            }
+        }
+        self.release_marked();
+    }
+
+    /// Makes sure that at least `additional` more values can be allocated without allocating
+    /// more memory.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        handle_reserve(self.try_reserve(additional));
+    }
+
+    /// Like `reserve`, but returns an error instead of aborting if the allocation fails.
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if additional > MAX_CHUNKS * MAX_CHUNK - self.len {
+            // the indices would overflow `u32`
+            return Err(TryReserveErrorKind::CapacityOverflow.into());
+        }
+        let mut free = self.capacity - self.len;
+        while additional > free {
+            // grow like `alloc` does, so that many small reservations don't use up the chunks
+            self.try_add_chunk((additional - free).max(MIN_CHUNK.max(self.capacity)))?;
+            free = self.capacity - self.len;
+        }
+        Ok(())
+    }
+
+    /// Moves `value` into the slab, returning a pointer to it which stays valid until freed.
+    pub(crate) fn alloc(&mut self, value: T) -> NonNull<T> {
+        if self.free.is_null() {
+            handle_reserve(self.try_add_chunk(MIN_CHUNK.max(self.capacity)));
+        }
+        unsafe {
+            let slot = self.free;
+            self.free = (*slot).data.next_free;
+            self.generation += 1;
+            ptr::write(
+                slot,
+                Slot {
+                    data: SlotData {
+                        value: ManuallyDrop::new(value),
+                    },
+                    generation: self.generation,
+                    index: (*slot).index,
+                },
+            );
+            self.len += 1;
+            let chunk = self.chunk_of(slot);
+            self.chunks[chunk].live += 1;
+            if self.spare == Some(chunk) {
+                self.spare = None;
+            }
+            // safety: the slot and its data are repr(C), so the value is at offset 0
+            NonNull::new_unchecked(slot as *mut T)
+        }
+    }
+
+    /// Moves the value out of the slab and marks its slot as free.
+    ///
+    /// # Safety
+    ///
+    /// `value` must have been allocated by this slab and not already freed.
+    pub(crate) unsafe fn free(&mut self, value: NonNull<T>) -> T {
+        let slot = value.as_ptr() as *mut Slot<T>;
+        let value = ManuallyDrop::into_inner(ptr::read(&(*slot).data.value));
+        (*slot).data.next_free = self.free;
+        (*slot).generation = 0;
+        self.free = slot;
+        self.len -= 1;
+        let chunk = self.chunk_of(slot);
+        self.chunks[chunk].live -= 1;
+        if self.chunks[chunk].live == 0 && self.capacity - self.len > self.max_free {
+            // keep the chunk as the spare, since its slots are the next to be allocated, and
+            //  release the previous one instead
+            if let Some(spare) = self.spare.replace(chunk) {
+                self.chunks[spare].release = true;
+                self.release_marked();
+            }
+        }
+        value
+    }
+
+    /// Returns the generation of a live value.
+    ///
+    /// # Safety
+    ///
+    /// `value` must have been allocated by this slab and not freed since.
+    pub(crate) unsafe fn generation(&self, value: NonNull<T>) -> u64 {
+        (*(value.as_ptr() as *const Slot<T>)).generation
+    }
+
+    /// Gives a live value a new generation, as if it had been freed and allocated again.
+    ///
+    /// # Safety
+    ///
+    /// `value` must have been allocated by this slab and not freed since.
+    pub(crate) unsafe fn renew(&mut self, value: NonNull<T>) {
+        self.generation += 1;
+        (*(value.as_ptr() as *mut Slot<T>)).generation = self.generation;
+    }
+
+    /// Returns the index of a live value.
+    ///
+    /// # Safety
+    ///
+    /// `value` must have been allocated by this slab and not freed since.
+    pub(crate) unsafe fn index(&self, value: NonNull<T>) -> u32 {
+        (*(value.as_ptr() as *const Slot<T>)).index
+    }
+
+    /// Returns a pointer to the value with the given index.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be the index of a live value of this slab.
+    pub(crate) unsafe fn ptr(&self, index: u32) -> NonNull<T> {
+        self.raw().ptr(index)
+    }
+
+    /// Returns a view of the slab which resolves indices without borrowing it.
+    pub(crate) fn raw(&self) -> RawSlab<T> {
+        RawSlab {
+            chunks: self.chunks.as_ptr(),
+        }
+    }
+
+    /// Returns the value with the given index if it is still the allocation with the given
+    /// generation. Any index can be passed, the slab checks that it refers to one of its slots.
+    pub(crate) fn get(&self, index: u32, generation: u64) -> Option<NonNull<T>> {
+        let chunk = self.chunks.get((index >> CHUNK_BITS) as usize)?;
+        let offset = (index as usize) & (MAX_CHUNK - 1);
+        if generation == 0 || offset >= chunk.len {
+            return None;
+        }
+        // safety: the slot lies within one of our chunks, so it is initialized
+        let slot = unsafe { chunk.start.as_ptr().add(offset) };
+        if unsafe { (*slot).generation } != generation {
+            return None;
+        }
+        Some(unsafe { NonNull::new_unchecked(slot as *mut T) })
+    }
+
+    /// Releases chunks which hold no live values.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        for chunk in self.chunks.iter_mut() {
+            chunk.release = chunk.len > 0 && chunk.live == 0;
+        }
+        self.release_marked();
+        while self.chunks.last().map_or(false, |chunk| chunk.len == 0) {
+            self.chunks.pop();
+        }
+        self.chunks.shrink_to_fit();
+    }
+
+    // Releases the chunks marked for release, which must be empty
+    fn release_marked(&mut self) {
+        if !self.chunks.iter().any(|chunk| chunk.release) {
+            return;
+        }
+        if self.spare.map_or(false, |spare| self.chunks[spare].release) {
+            self.spare = None;
+        }
+
+        // rebuild the free list without the slots of the chunks we're about to release
+        let mut slot = self.free;
+        self.free = ptr::null_mut();
+        while !slot.is_null() {
+            let next = unsafe { (*slot).data.next_free };
+            if !self.chunks[self.chunk_of(slot)].release {
+                unsafe { (*slot).data.next_free = self.free };
+                self.free = slot;
+            }
+            slot = next;
+        }
+
+        for number in 0..self.chunks.len() {
+            if self.chunks[number].release {
+                let chunk = &self.chunks[number];
+                self.capacity -= chunk.len;
+                unsafe { dealloc_chunk(self.chunks.allocator(), chunk.start, chunk.len) };
+                self.chunks[number] = Chunk {
+                    start: NonNull::dangling(),
+                    len: 0,
+                    live: 0,
+                    release: false,
+                };
+            }
+        }
+    }
+
+    fn chunk_of(&self, slot: *mut Slot<T>) -> usize {
+        unsafe { ((*slot).index >> CHUNK_BITS) as usize }
+    }
+
+    // Adds a chunk of at least `len` slots, or of the largest size if that's smaller
+    fn try_add_chunk(&mut self, len: usize) -> Result<(), TryReserveError> {
+        let len = len.min(MAX_CHUNK);
+        let layout = Layout::array::<Slot<T>>(len).map_err(TryReserveErrorKind::from)?;
+        // fill the first gap, or make room for the chunk in the list first, so that a failure
+        //  doesn't leak it
+        let number = match self.chunks.iter().position(|chunk| chunk.len == 0) {
+            Some(number) => number,
+            None if self.chunks.len() < MAX_CHUNKS => {
+                self.chunks.try_reserve(1)?;
+                self.chunks.len()
+            }
+            None => return Err(TryReserveErrorKind::CapacityOverflow.into()),
+        };
+        let chunk = self.chunks.allocator().allocate(layout).map_err(|_| {
+            TryReserveErrorKind::AllocError {
+                layout,
+                non_exhaustive: (),
+            }
+        })?;
+        // the allocator may give us more than we asked for
+        let len = (chunk.len() / mem::size_of::<Slot<T>>()).min(MAX_CHUNK);
+        let start = chunk.as_ptr() as *mut Slot<T>;
+        // thread the new slots onto the free list, keeping them in address order
+        for i in (0..len).rev() {
+            unsafe {
+                let slot = start.add(i);
+                ptr::write(
+                    slot,
+                    Slot {
+                        data: SlotData {
+                            next_free: self.free,
+                        },
+                        generation: 0,
+                        index: (number << CHUNK_BITS | i) as u32,
+                    },
+                );
+                self.free = slot;
+            }
+        }
+        let chunk = Chunk {
+            start: unsafe { NonNull::new_unchecked(start) },
+            len,
+            live: 0,
+            release: false,
+        };
+        if number == self.chunks.len() {
+            self.chunks.push(chunk);
+        } else {
+            self.chunks[number] = chunk;
+        }
+        self.capacity += len;
+        Ok(())
+    }
+}
+
+impl<T, A: Allocator> Drop for Slab<T, A> {
+    // Note that live values are *not* dropped. Their owner is responsible for freeing them first.
+    fn drop(&mut self) {
+        for chunk in self.chunks.iter().filter(|chunk| chunk.len > 0) {
+            unsafe { dealloc_chunk(self.chunks.allocator(), chunk.start, chunk.len) };
+        }
+    }
+}
+
+// A view of the chunks of a slab, which stays valid as long as the slab doesn't allocate or
+//  release chunks
+pub(crate) struct RawSlab<T> {
+    chunks: *const Chunk<T>,
+}
+
+impl<T> RawSlab<T> {
+    /// Returns a pointer to the value with the given index.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be the index of a live value of the slab, which must not have allocated or
+    /// released chunks since the view was created.
+    pub(crate) unsafe fn ptr(self, index: u32) -> NonNull<T> {
+        let chunk = &*self.chunks.add((index >> CHUNK_BITS) as usize);
+        debug_assert!(((index as usize) & (MAX_CHUNK - 1)) < chunk.len);
+        let slot = chunk.start.as_ptr().add((index as usize) & (MAX_CHUNK - 1));
+        // safety: the slot and its data are repr(C), so the value is at offset 0
+        NonNull::new_unchecked(slot as *mut T)
+    }
+}
+
+impl<T> Clone for RawSlab<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for RawSlab<T> {}
+
+// Returns an id which no other slab has
+#[cfg(target_has_atomic = "ptr")]
+fn next_id() -> usize {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[cfg(not(target_has_atomic = "ptr"))]
+fn next_id() -> usize {
+    0
+}
+
+// Aborts on allocation failures, like the standard collections do
+pub(crate) fn handle_reserve(result: Result<(), TryReserveError>) {
+    match result.map_err(|err| err.kind()) {
+        Ok(()) => {}
+        Err(TryReserveErrorKind::CapacityOverflow) => panic!("capacity overflow"),
+        Err(TryReserveErrorKind::AllocError { layout, .. }) => handle_alloc_error(layout),
+    }
+}
+
+// Returns a chunk of `len` slots to the allocator
+unsafe fn dealloc_chunk<T, A: Allocator>(alloc: &A, start: NonNull<Slot<T>>, len: usize) {
+    // `len` lies between the requested and the returned size, so the layout fits the chunk
+    let layout = Layout::array::<Slot<T>>(len).unwrap_unchecked();
+    alloc.deallocate(start.cast(), layout);
+}
@@ -0,0 +1,453 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A compact, versioned binary snapshot format for `LruCache`, available unless the `no_std`
+//! feature is enabled.
+//!
+//! All integers are little-endian. A snapshot consists of:
+//!
+//! - the magic bytes `LRUSNAP\0`,
+//! - the format version as a `u16`,
+//! - the kind of limiter as a `u8` followed by its limit as a `u64`,
+//! - the number of entries as a `u64`,
+//! - one record per entry in LRU to MRU order: the encoded key and the encoded value, each
+//!   prefixed by its length as a `u32`,
+//! - a CRC-32 of everything before it as a `u32`.
+//!
+//! Keys and values are turned into bytes by a user-provided `Codec`, so the format itself does not
+//! depend on any serialization framework.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use std::error::Error;
+use std::io::{self, Read, Write};
+
+use allocator_api2::alloc::Allocator;
+
+#[cfg(target_has_atomic = "ptr")]
+use CostLimited;
+use {GroupLimited, Limiter, LocalCostLimited, LruCache, SizeLimited, Unlimited};
+
+const MAGIC: [u8; 8] = *b"LRUSNAP\0";
+const VERSION: u16 = 1;
+
+/// Converts keys and values to and from the bytes stored in snapshots and journals.
+pub trait Codec<K, V> {
+    /// Appends the encoding of `key` to `buf`.
+    fn encode_key(&self, key: &K, buf: &mut Vec<u8>);
+
+    /// Appends the encoding of `value` to `buf`.
+    fn encode_value(&self, value: &V, buf: &mut Vec<u8>);
+
+    /// Decodes a key previously encoded by `encode_key`, or returns `None` if `bytes` are invalid.
+    fn decode_key(&self, bytes: &[u8]) -> Option<K>;
+
+    /// Decodes a value previously encoded by `encode_value`, or returns `None` if `bytes` are
+    /// invalid.
+    fn decode_value(&self, bytes: &[u8]) -> Option<V>;
+}
+
+/// The kind of limiter a snapshot was taken with, along with its limit.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LimiterKind {
+    /// An `Unlimited` limiter.
+    Unlimited,
+    /// A `SizeLimited` limiter with the given max len.
+    Size(u64),
+    /// A `CostLimited` or `LocalCostLimited` limiter with the given max cost.
+    Cost(u64),
+    /// A `GroupLimited` limiter with the given max number of entries per group.
+    Group(u64),
+}
+
+impl LimiterKind {
+    fn to_bytes(self) -> [u8; 9] {
+        let (tag, limit) = match self {
+            LimiterKind::Unlimited => (0, 0),
+            LimiterKind::Size(limit) => (1, limit),
+            LimiterKind::Cost(limit) => (2, limit),
+            LimiterKind::Group(limit) => (3, limit),
+        };
+        let mut bytes = [tag; 9];
+        bytes[1..].copy_from_slice(&limit.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: [u8; 9]) -> Result<Self, SnapshotError> {
+        let mut limit = [0; 8];
+        limit.copy_from_slice(&bytes[1..]);
+        let limit = u64::from_le_bytes(limit);
+        match bytes[0] {
+            0 => Ok(LimiterKind::Unlimited),
+            1 => Ok(LimiterKind::Size(limit)),
+            2 => Ok(LimiterKind::Cost(limit)),
+            3 => Ok(LimiterKind::Group(limit)),
+            tag => Err(SnapshotError::UnknownLimiter(tag)),
+        }
+    }
+}
+
+/// A `Limiter` which can describe itself in a snapshot header.
+pub trait SnapshotLimiter {
+    /// Returns the kind of this limiter and its limit.
+    fn limiter_kind(&self) -> LimiterKind;
+}
+
+impl SnapshotLimiter for Unlimited {
+    fn limiter_kind(&self) -> LimiterKind {
+        LimiterKind::Unlimited
+    }
+}
+
+impl SnapshotLimiter for SizeLimited {
+    fn limiter_kind(&self) -> LimiterKind {
+        LimiterKind::Size(self.limit() as u64)
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<F> SnapshotLimiter for CostLimited<F> {
+    fn limiter_kind(&self) -> LimiterKind {
+        LimiterKind::Cost(self.limit() as u64)
+    }
+}
+
+impl<F> SnapshotLimiter for LocalCostLimited<F> {
+    fn limiter_kind(&self) -> LimiterKind {
+        LimiterKind::Cost(self.limit() as u64)
+    }
+}
+
+impl<G: Hash + Eq, L, F> SnapshotLimiter for GroupLimited<G, L, F> {
+    fn limiter_kind(&self) -> LimiterKind {
+        LimiterKind::Group(self.group_limit() as u64)
+    }
+}
+
+/// The header of a snapshot, returned by `LruCache::read_snapshot`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SnapshotInfo {
+    /// The kind of limiter the snapshot was taken with.
+    pub limiter: LimiterKind,
+    /// The number of entries in the snapshot.
+    pub len: u64,
+}
+
+/// An error which occurred while reading a snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The underlying reader failed.
+    Io(io::Error),
+    /// The input does not start with the snapshot magic bytes.
+    BadMagic,
+    /// The snapshot was written in a format version this crate does not understand.
+    UnsupportedVersion(u16),
+    /// The header names a limiter kind this crate does not understand.
+    UnknownLimiter(u8),
+    /// The input ended before the end of the snapshot.
+    Truncated,
+    /// The checksum at the end of the snapshot does not match its contents.
+    ChecksumMismatch {
+        /// The checksum stored in the snapshot.
+        expected: u32,
+        /// The checksum of the data which was actually read.
+        actual: u32,
+    },
+    /// The codec could not decode the key or value of the entry at the given index.
+    Decode(u64),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "failed to read snapshot: {}", err),
+            SnapshotError::BadMagic => f.write_str("not an lru snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::UnknownLimiter(tag) => write!(f, "unknown limiter kind {}", tag),
+            SnapshotError::Truncated => f.write_str("snapshot is truncated"),
+            SnapshotError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "snapshot checksum mismatch: expected {:08x}, got {:08x}",
+                expected, actual
+            ),
+            SnapshotError::Decode(index) => write!(f, "failed to decode entry {}", index),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            SnapshotError::Truncated
+        } else {
+            SnapshotError::Io(err)
+        }
+    }
+}
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table();
+
+// An incremental CRC-32 (IEEE) checksum
+#[derive(Copy, Clone)]
+pub(crate) struct Crc32(u32);
+
+impl Crc32 {
+    pub(crate) fn new() -> Self {
+        Crc32(!0)
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = CRC32_TABLE[((self.0 ^ byte as u32) & 0xff) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub(crate) fn finish(self) -> u32 {
+        !self.0
+    }
+}
+
+// Wraps a writer, checksumming everything written through it
+struct ChecksumWriter<'a, W: ?Sized> {
+    inner: &'a mut W,
+    crc: Crc32,
+}
+
+impl<'a, W: Write + ?Sized> ChecksumWriter<'a, W> {
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.crc.update(bytes);
+        self.inner.write_all(bytes)
+    }
+
+    fn write_record(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(bytes.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "snapshot record too large")
+        })?;
+        self.write_all(&len.to_le_bytes())?;
+        self.write_all(bytes)
+    }
+}
+
+// Wraps a reader, checksumming everything read through it
+struct ChecksumReader<'a, R: ?Sized> {
+    inner: &'a mut R,
+    crc: Crc32,
+}
+
+impl<'a, R: Read + ?Sized> ChecksumReader<'a, R> {
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        let mut bytes = [0; N];
+        self.inner.read_exact(&mut bytes)?;
+        self.crc.update(&bytes);
+        Ok(bytes)
+    }
+
+    // Appends a length-prefixed record to `buf`. The buffer only grows as data actually arrives so
+    // that a corrupted length cannot trigger a huge allocation.
+    fn read_record(&mut self, buf: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        let len = u32::from_le_bytes(self.read_array()?) as u64;
+        let start = buf.len();
+        if (&mut *self.inner).take(len).read_to_end(buf)? as u64 != len {
+            return Err(SnapshotError::Truncated);
+        }
+        self.crc.update(&buf[start..]);
+        Ok(())
+    }
+}
+
+impl<K: Hash + Eq, V, L: Limiter<K, V, S, A>, S: BuildHasher, A: Allocator>
+    LruCache<K, V, L, S, A>
+{
+    /// Writes a snapshot of the cache to `writer`, streaming the entries from least to most
+    /// recently used and encoding them with `codec`. The cache is left untouched, in particular
+    /// its recency order is not updated.
+    ///
+    /// See the `snapshot` module documentation for the format.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{Codec, LimiterKind, LruCache};
+    /// use std::convert::TryInto;
+    ///
+    /// struct Le;
+    ///
+    /// impl Codec<u32, u32> for Le {
+    ///     fn encode_key(&self, key: &u32, buf: &mut Vec<u8>) {
+    ///         buf.extend_from_slice(&key.to_le_bytes());
+    ///     }
+    ///     fn encode_value(&self, value: &u32, buf: &mut Vec<u8>) {
+    ///         buf.extend_from_slice(&value.to_le_bytes());
+    ///     }
+    ///     fn decode_key(&self, bytes: &[u8]) -> Option<u32> {
+    ///         Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    ///     }
+    ///     fn decode_value(&self, bytes: &[u8]) -> Option<u32> {
+    ///         Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    ///     }
+    /// }
+    ///
+    /// let mut cache = LruCache::new(3);
+    /// cache.put(1, 10);
+    /// cache.put(2, 20);
+    /// cache.put(3, 30);
+    ///
+    /// let mut snapshot = Vec::new();
+    /// cache.write_snapshot(&mut snapshot, &Le).unwrap();
+    ///
+    /// // Loading into a smaller cache keeps the most recently used entries
+    /// let mut small = LruCache::new(2);
+    /// let info = small.read_snapshot(&mut &snapshot[..], &Le).unwrap();
+    /// assert_eq!(info.limiter, LimiterKind::Size(3));
+    /// assert_eq!(info.len, 3);
+    /// assert_eq!(small.iter().collect::<Vec<_>>(), [(&3, &30), (&2, &20)]);
+    /// ```
+    pub fn write_snapshot(
+        &self,
+        writer: &mut impl Write,
+        codec: &impl Codec<K, V>,
+    ) -> io::Result<()>
+    where
+        L: SnapshotLimiter,
+    {
+        let mut writer = ChecksumWriter {
+            inner: writer,
+            crc: Crc32::new(),
+        };
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.limiter.limiter_kind().to_bytes())?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+
+        let mut buf = Vec::new();
+        for (key, value) in self.iter_lru() {
+            buf.clear();
+            codec.encode_key(key, &mut buf);
+            writer.write_record(&buf)?;
+            buf.clear();
+            codec.encode_value(value, &mut buf);
+            writer.write_record(&buf)?;
+        }
+
+        let checksum = writer.crc.finish();
+        writer.inner.write_all(&checksum.to_le_bytes())
+    }
+
+    /// Reads a snapshot written by `write_snapshot` from `reader`, decoding the entries with
+    /// `codec`, and returns its header.
+    ///
+    /// The entries are added to the cache from least to most recently used, as if by `put`. They
+    /// end up more recent than any entries already in the cache and the cache's own limiter
+    /// applies, so loading a snapshot into a cache with a smaller limit keeps only the most
+    /// recently used entries. The limiter recorded in the snapshot is only informative.
+    ///
+    /// The whole snapshot is decoded and its checksum verified before the cache is modified, so on
+    /// error the cache is left untouched. Nothing is read past the checksum.
+    pub fn read_snapshot(
+        &mut self,
+        reader: &mut impl Read,
+        codec: &impl Codec<K, V>,
+    ) -> Result<SnapshotInfo, SnapshotError> {
+        let mut reader = ChecksumReader {
+            inner: reader,
+            crc: Crc32::new(),
+        };
+        if reader.read_array()? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = u16::from_le_bytes(reader.read_array()?);
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let limiter = LimiterKind::from_bytes(reader.read_array()?)?;
+        let len = u64::from_le_bytes(reader.read_array()?);
+
+        // The records are only decoded once the checksum has been verified, so that corruption is
+        // reported as such rather than as an entry which fails to decode.
+        let mut records = Vec::new();
+        let mut ends = Vec::new();
+        for _ in 0..len {
+            reader.read_record(&mut records)?;
+            ends.push(records.len());
+            reader.read_record(&mut records)?;
+            ends.push(records.len());
+        }
+
+        let actual = reader.crc.finish();
+        let mut expected = [0; 4];
+        reader.inner.read_exact(&mut expected)?;
+        let expected = u32::from_le_bytes(expected);
+        if expected != actual {
+            return Err(SnapshotError::ChecksumMismatch { expected, actual });
+        }
+
+        let mut entries = Vec::with_capacity(ends.len() / 2);
+        let mut start = 0;
+        for (index, ends) in ends.chunks(2).enumerate() {
+            let key = codec.decode_key(&records[start..ends[0]]);
+            let value = codec.decode_value(&records[ends[0]..ends[1]]);
+            start = ends[1];
+            match (key, value) {
+                (Some(key), Some(value)) => entries.push((key, value)),
+                _ => return Err(SnapshotError::Decode(index as u64)),
+            }
+        }
+
+        for (key, value) in entries {
+            self.put(key, value);
+        }
+        Ok(SnapshotInfo { limiter, len })
+    }
+}
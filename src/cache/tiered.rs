@@ -0,0 +1,484 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A two-tier cache which spills entries evicted from memory to a file, available unless the
+//! `no_std` feature is enabled.
+
+use alloc::borrow::Borrow;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use {Codec, CostFn, CostLimited, DefaultHasher, Limiter, LruCache, MaybeCached, SizeLimited};
+
+// The location of an encoded value in the disk tier's file
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    offset: u64,
+    len: usize,
+}
+
+// Charges disk tier entries for the bytes of their encoded value
+#[derive(Debug, Default)]
+struct SlotCost;
+
+impl<K> CostFn<K, Slot> for SlotCost {
+    fn key_cost(&self, _key: &K) -> usize {
+        0
+    }
+
+    fn value_cost(&self, slot: &Slot) -> usize {
+        slot.len
+    }
+}
+
+/// The error returned when a `TieredCache` fails to read or write its file.
+///
+/// An entry whose value could not be read stays in the disk tier. Entries which were evicted from
+/// the memory tier but could not be spilled are no longer in the cache, so they are handed back
+/// here instead of being lost, along with the old value which a failed `put` replaced.
+#[derive(Debug)]
+pub struct SpillError<K, V> {
+    /// The first I/O error.
+    pub error: io::Error,
+    /// The entries which could not be spilled to the disk tier.
+    pub entries: Vec<(K, V)>,
+    /// The old value of the key which `put` replaced, if any.
+    pub old: Option<V>,
+}
+
+impl<K, V> From<io::Error> for SpillError<K, V> {
+    fn from(error: io::Error) -> Self {
+        SpillError {
+            error,
+            entries: Vec::new(),
+            old: None,
+        }
+    }
+}
+
+impl<K, V> fmt::Display for SpillError<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "disk tier I/O failed, {} entries could not be spilled: {}",
+            self.entries.len(),
+            self.error
+        )
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> Error for SpillError<K, V> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An in-memory `LruCache` backed by a second, file-based LRU tier for the entries it evicts.
+///
+/// Entries evicted from the memory tier, or rejected by its limiter, are encoded with a `Codec`
+/// and appended to a file, where they are kept in their own LRU list limited to a budget of
+/// encoded bytes. Looking up an entry which is only on disk decodes it and promotes it back to the
+/// memory tier. An entry lives in at most one tier at a time; only its key stays in memory while it
+/// is on disk.
+///
+/// The file only holds data for the lifetime of the cache: it is truncated when the cache is
+/// created and removed when it is dropped. Space left behind by entries which were removed from
+/// the disk tier is reclaimed by rewriting the file once it exceeds the budget. I/O errors are
+/// returned in a `SpillError`, which holds any entries that left the cache because of them.
+///
+/// # Example
+///
+/// ```
+/// use lru::{Codec, LruCache, TieredCache};
+/// use std::convert::TryInto;
+///
+/// struct Le;
+///
+/// impl Codec<u32, u64> for Le {
+///     fn encode_key(&self, key: &u32, buf: &mut Vec<u8>) {
+///         buf.extend_from_slice(&key.to_le_bytes());
+///     }
+///     fn encode_value(&self, value: &u64, buf: &mut Vec<u8>) {
+///         buf.extend_from_slice(&value.to_le_bytes());
+///     }
+///     fn decode_key(&self, bytes: &[u8]) -> Option<u32> {
+///         Some(u32::from_le_bytes(bytes.try_into().ok()?))
+///     }
+///     fn decode_value(&self, bytes: &[u8]) -> Option<u64> {
+///         Some(u64::from_le_bytes(bytes.try_into().ok()?))
+///     }
+/// }
+///
+/// let path = std::env::temp_dir().join(format!("lru-tiered-doc-{}", std::process::id()));
+///
+/// // Two entries in memory and up to 16 bytes, that is two entries, on disk
+/// let mut cache = TieredCache::new(&path, LruCache::new(2), 16, Le).unwrap();
+/// for i in 0..5 {
+///     cache.put(i, u64::from(i) * 10).unwrap();
+/// }
+/// assert_eq!(cache.memory().len(), 2);
+/// assert_eq!(cache.disk_len(), 2);
+/// assert_eq!(cache.disk_bytes(), 16);
+///
+/// // 0 was evicted from both tiers, 1 is promoted back from disk
+/// assert_eq!(cache.get(&0).unwrap(), None);
+/// assert_eq!(cache.get(&1).unwrap().as_deref(), Some(&10));
+/// assert!(cache.memory().contains(&1));
+/// ```
+pub struct TieredCache<K, V, C, L = SizeLimited, S = DefaultHasher> {
+    memory: LruCache<K, V, L, S>,
+    disk: LruCache<K, Slot, CostLimited<SlotCost>>,
+    codec: C,
+    path: PathBuf,
+    file: File,
+    file_len: u64,
+    buf: Vec<u8>,
+}
+
+impl<K, V, C, L, S> TieredCache<K, V, C, L, S>
+where
+    K: Hash + Eq,
+    C: Codec<K, V>,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+{
+    /// Creates a tiered cache on top of the `memory` cache, whose evicted entries are spilled to
+    /// the file at `path` within a budget of `disk_budget` encoded bytes. Any existing file at
+    /// `path` is truncated.
+    pub fn new(
+        path: impl AsRef<Path>,
+        memory: LruCache<K, V, L, S>,
+        disk_budget: usize,
+        codec: C,
+    ) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        Ok(TieredCache {
+            memory,
+            disk: LruCache::with_limiter(CostLimited::with_func(disk_budget, SlotCost)),
+            codec,
+            path,
+            file,
+            file_len: 0,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Puts a key-value pair into the memory tier, spilling any entries this evicts to the disk
+    /// tier. Returns the previous value of the key from either tier, if any.
+    ///
+    /// If the memory tier's limiter rejects the entry, it goes straight to the disk tier. If the
+    /// old value of the key cannot be read, the cache is left unchanged and the new entry is
+    /// returned in the error.
+    pub fn put(&mut self, k: K, v: V) -> Result<Option<V>, SpillError<K, V>> {
+        let old = match self.memory.pop(&k) {
+            Some(old) => Some(old),
+            None => match self.pop_disk(&k) {
+                Ok(old) => old,
+                Err(error) => {
+                    return Err(SpillError {
+                        error,
+                        entries: vec![(k, v)],
+                        old: None,
+                    })
+                }
+            },
+        };
+        let spilled = match self.insert_memory(k, v) {
+            Ok(None) => Ok(()),
+            Ok(Some((k, v))) => self.spill(k, v).map_err(|(error, k, v)| SpillError {
+                error,
+                entries: vec![(k, v)],
+                old: None,
+            }),
+            Err(err) => Err(err),
+        };
+        match spilled {
+            Ok(()) => Ok(old),
+            Err(err) => Err(SpillError { old, ..err }),
+        }
+    }
+
+    /// Returns a reference to the value of the key, moving it to the head of the memory tier.
+    /// Entries found on disk are decoded and promoted back to memory, spilling the entries this
+    /// evicts in turn.
+    ///
+    /// Entries which the memory tier's limiter rejects stay on disk, where they become the most
+    /// recently used entry, and are decoded and returned by value on every access. An entry whose
+    /// value cannot be read stays on disk.
+    pub fn get<Q>(&mut self, k: &Q) -> Result<Option<MaybeCached<'_, V>>, SpillError<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.memory.contains(k) {
+            return Ok(self.memory.get(k).map(MaybeCached::Cached));
+        }
+        let value = match self.read_disk(k)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let (key, slot) = self.disk.pop_entry(k).expect("key is in the disk tier");
+        match self.insert_memory(key, value)? {
+            None => Ok(self.memory.peek(k).map(MaybeCached::Cached)),
+            Some((key, value)) => {
+                self.disk.put(key, slot);
+                Ok(Some(MaybeCached::Uncached(value)))
+            }
+        }
+    }
+
+    /// Returns a reference to the value of the key if it is in the memory tier, without updating
+    /// either tier.
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.memory.peek(k)
+    }
+
+    /// Returns a bool indicating whether the key is in either tier.
+    pub fn contains<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.memory.contains(k) || self.disk.contains(k)
+    }
+
+    /// Removes the key from whichever tier holds it and returns its value. An entry whose value
+    /// cannot be read stays on disk.
+    pub fn pop<Q>(&mut self, k: &Q) -> io::Result<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.memory.pop(k) {
+            Some(v) => Ok(Some(v)),
+            None => self.pop_disk(k),
+        }
+    }
+
+    /// Returns the number of entries in both tiers.
+    pub fn len(&self) -> usize {
+        self.memory.len() + self.disk.len()
+    }
+
+    /// Returns a bool indicating whether both tiers are empty.
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty() && self.disk.is_empty()
+    }
+
+    /// Returns the memory tier.
+    pub fn memory(&self) -> &LruCache<K, V, L, S> {
+        &self.memory
+    }
+
+    /// Returns the number of entries in the disk tier.
+    pub fn disk_len(&self) -> usize {
+        self.disk.len()
+    }
+
+    /// Returns the number of encoded bytes held by the disk tier.
+    pub fn disk_bytes(&self) -> usize {
+        self.disk.limiter().current()
+    }
+
+    /// Returns the budget of encoded bytes of the disk tier.
+    pub fn disk_budget(&self) -> usize {
+        self.disk.limiter().limit()
+    }
+
+    /// Returns the path of the disk tier's file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Inserts an entry which is in neither tier into the memory tier, spilling any entries this
+    // evicts. Returns the entry if the memory tier rejects it. Entries which could not be spilled
+    // are returned in the error, after every other one was tried.
+    fn insert_memory(&mut self, k: K, v: V) -> Result<Option<(K, V)>, SpillError<K, V>> {
+        let mut evicted = Vec::new();
+        match self.memory.entry(k).try_insert(v) {
+            Ok(mut entry) => {
+                while let Some(entry) = entry.take_evicted() {
+                    evicted.push(entry);
+                }
+            }
+            Err((k, v)) => return Ok(Some((k.expect("key is not in the memory tier"), v))),
+        }
+        let mut error = None;
+        let mut entries = Vec::new();
+        for (k, v) in evicted {
+            if let Err((err, k, v)) = self.spill(k, v) {
+                error.get_or_insert(err);
+                entries.push((k, v));
+            }
+        }
+        match error {
+            None => Ok(None),
+            Some(error) => Err(SpillError {
+                error,
+                entries,
+                old: None,
+            }),
+        }
+    }
+
+    // Appends an entry to the disk tier, handing it back if this fails. Entries larger than the
+    // whole budget are dropped.
+    fn spill(&mut self, k: K, v: V) -> Result<(), (io::Error, K, V)> {
+        match self.write_value(&v) {
+            Ok(Some(slot)) => {
+                // evicted slots simply become unused space in the file
+                self.disk.put(k, slot);
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(err) => Err((err, k, v)),
+        }
+    }
+
+    // Appends an encoded value to the file, unless it is larger than the whole budget
+    fn write_value(&mut self, v: &V) -> io::Result<Option<Slot>> {
+        self.buf.clear();
+        self.codec.encode_value(v, &mut self.buf);
+        if self.buf.len() > self.disk_budget() {
+            return Ok(None);
+        }
+        if self.file_len - self.disk_bytes() as u64 > self.disk_budget() as u64 {
+            self.compact()?;
+        }
+
+        self.file.seek(SeekFrom::Start(self.file_len))?;
+        self.file.write_all(&self.buf)?;
+        let slot = Slot {
+            offset: self.file_len,
+            len: self.buf.len(),
+        };
+        self.file_len += slot.len as u64;
+        Ok(Some(slot))
+    }
+
+    // Decodes the value of the key from the disk tier, and then removes the key
+    fn pop_disk<Q>(&mut self, k: &Q) -> io::Result<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let value = self.read_disk(k)?;
+        self.disk.pop(k);
+        Ok(value)
+    }
+
+    // Decodes the value of the key from the disk tier without updating it
+    fn read_disk<Q>(&mut self, k: &Q) -> io::Result<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.disk.peek(k) {
+            Some(&slot) => self.read(&slot).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn read(&mut self, slot: &Slot) -> io::Result<V> {
+        self.buf.resize(slot.len, 0);
+        self.file.seek(SeekFrom::Start(slot.offset))?;
+        self.file.read_exact(&mut self.buf)?;
+        self.codec.decode_value(&self.buf).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "failed to decode disk tier value",
+            )
+        })
+    }
+
+    // Rewrites the file with only the values which are still in the disk tier
+    fn compact(&mut self) -> io::Result<()> {
+        let mut tmp_path = OsString::from(self.path.clone());
+        tmp_path.push(".compact");
+        let mut tmp = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+
+        // slots are only updated once the new file is in place
+        let mut offsets = Vec::with_capacity(self.disk.len());
+        let mut offset = 0;
+        // `self.buf` holds the value being spilled
+        let mut buf = Vec::new();
+        for (_, slot) in self.disk.iter() {
+            buf.resize(slot.len, 0);
+            self.file.seek(SeekFrom::Start(slot.offset))?;
+            self.file.read_exact(&mut buf)?;
+            tmp.write_all(&buf)?;
+            offsets.push(offset);
+            offset += slot.len as u64;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+
+        for ((_, slot), offset) in self.disk.iter_mut().zip(offsets) {
+            slot.offset = offset;
+        }
+        self.file = tmp;
+        self.file_len = offset;
+        Ok(())
+    }
+}
+
+impl<K, V, C, L, S> Drop for TieredCache<K, V, C, L, S> {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl<K, V, C, L, S> fmt::Debug for TieredCache<K, V, C, L, S>
+where
+    K: Hash + Eq,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TieredCache")
+            .field("path", &self.path)
+            .field("memory_len", &self.memory.len())
+            .field("disk_len", &self.disk.len())
+            .finish()
+    }
+}
@@ -0,0 +1,376 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Composition of two caches into a two-level hierarchy.
+
+use alloc::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+
+use allocator_api2::alloc::Allocator;
+
+use {Limiter, LruCache};
+
+/// A cache which can be used as a level of a `TwoLevel` cache.
+///
+/// This is implemented for `LruCache` and for mutable references to levels, so that a level can
+/// be borrowed, for example from the guard of a lock around a cache shared between threads.
+pub trait CacheLevel {
+    /// The type of the keys in the level.
+    type Key: Hash + Eq;
+    /// The type of the values in the level.
+    type Value;
+
+    /// Returns a reference to the value of the key, marking it as most recently used.
+    fn get<Q>(&mut self, k: &Q) -> Option<&Self::Value>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    /// Returns a reference to the value of the key without updating its recency.
+    fn peek<Q>(&self, k: &Q) -> Option<&Self::Value>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    /// Returns a bool indicating whether the key is in the level.
+    fn contains<Q>(&self, k: &Q) -> bool
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    /// Removes the key from the level and returns its entry.
+    fn pop_entry<Q>(&mut self, k: &Q) -> Option<(Self::Key, Self::Value)>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    /// Puts a key-value pair into the level as the most recently used entry and returns the old
+    /// value of the key, if any. Every entry which the insertion evicts is passed to `on_evict`,
+    /// as is the new entry itself if the level rejects it.
+    fn put_evicting<F: FnMut(Self::Key, Self::Value)>(
+        &mut self,
+        k: Self::Key,
+        v: Self::Value,
+        on_evict: F,
+    ) -> Option<Self::Value>;
+}
+
+impl<K, V, L, S, A> CacheLevel for LruCache<K, V, L, S, A>
+where
+    K: Hash + Eq,
+    L: Limiter<K, V, S, A>,
+    S: BuildHasher,
+    A: Allocator,
+{
+    type Key = K;
+    type Value = V;
+
+    fn get<Q>(&mut self, k: &Q) -> Option<&Self::Value>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        LruCache::get(self, k)
+    }
+
+    fn peek<Q>(&self, k: &Q) -> Option<&Self::Value>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        LruCache::peek(self, k)
+    }
+
+    fn contains<Q>(&self, k: &Q) -> bool
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        LruCache::contains(self, k)
+    }
+
+    fn pop_entry<Q>(&mut self, k: &Q) -> Option<(Self::Key, Self::Value)>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        LruCache::pop_entry(self, k)
+    }
+
+    fn put_evicting<F: FnMut(Self::Key, Self::Value)>(
+        &mut self,
+        k: Self::Key,
+        v: Self::Value,
+        mut on_evict: F,
+    ) -> Option<Self::Value> {
+        // removing the old entry first makes the insertion go through the vacant entry path,
+        // which hands out every entry it evicts
+        let old = LruCache::pop(self, &k);
+        match self.entry(k).try_insert(v) {
+            Ok(mut entry) => {
+                while let Some((k, v)) = entry.take_evicted() {
+                    on_evict(k, v);
+                }
+            }
+            Err((k, v)) => on_evict(k.expect("key was removed before insertion"), v),
+        }
+        old
+    }
+}
+
+impl<C: CacheLevel> CacheLevel for &mut C {
+    type Key = C::Key;
+    type Value = C::Value;
+
+    fn get<Q>(&mut self, k: &Q) -> Option<&Self::Value>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        (**self).get(k)
+    }
+
+    fn peek<Q>(&self, k: &Q) -> Option<&Self::Value>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        (**self).peek(k)
+    }
+
+    fn contains<Q>(&self, k: &Q) -> bool
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        (**self).contains(k)
+    }
+
+    fn pop_entry<Q>(&mut self, k: &Q) -> Option<(Self::Key, Self::Value)>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        (**self).pop_entry(k)
+    }
+
+    fn put_evicting<F: FnMut(Self::Key, Self::Value)>(
+        &mut self,
+        k: Self::Key,
+        v: Self::Value,
+        on_evict: F,
+    ) -> Option<Self::Value> {
+        (**self).put_evicting(k, v, on_evict)
+    }
+}
+
+/// Specifies how the levels of a `TwoLevel` cache share entries.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum InclusionPolicy {
+    /// Every entry in L1 is also in L2. Insertions and L2 hits copy the entry into L1, and entries
+    /// evicted from L2 are removed from L1 as well.
+    Inclusive,
+    /// An entry is in at most one level. L2 hits move the entry into L1, and entries evicted from
+    /// L1 move to L2.
+    Exclusive,
+}
+
+/// A two-level cache, with a usually small and fast L1 cache in front of a larger L2 cache.
+///
+/// Lookups check L1 first, and hits in L2 are promoted into L1. How entries move between the
+/// levels is decided by the `InclusionPolicy`. Each level's limiter applies to its own entries:
+/// entries which L1 rejects stay in L2, and entries which L2 rejects or evicts are dropped.
+///
+/// # Example
+///
+/// ```
+/// use lru::{InclusionPolicy, LruCache, TwoLevel};
+/// use std::sync::Mutex;
+///
+/// let shared = Mutex::new(LruCache::new(4));
+/// let mut local = LruCache::new(1);
+///
+/// let mut guard = shared.lock().unwrap();
+/// let mut cache = TwoLevel::new(&mut local, &mut *guard, InclusionPolicy::Exclusive);
+/// cache.put(1, "a");
+/// cache.put(2, "b");
+/// assert_eq!(cache.l1().peek(&2), Some(&"b"));
+/// assert_eq!(cache.l2().peek(&1), Some(&"a"));
+///
+/// // An L2 hit moves the entry into L1, and L1's LRU entry down to L2
+/// assert_eq!(cache.get(&1), Some(&"a"));
+/// assert_eq!(cache.l1().peek(&1), Some(&"a"));
+/// assert_eq!(cache.l2().peek(&2), Some(&"b"));
+///
+/// assert_eq!(cache.invalidate(&2), Some("b"));
+/// assert!(!cache.contains(&2));
+/// ```
+#[derive(Debug)]
+pub struct TwoLevel<C1, C2> {
+    l1: C1,
+    l2: C2,
+    policy: InclusionPolicy,
+}
+
+impl<C1, C2> TwoLevel<C1, C2> {
+    /// Creates a two-level cache from its levels.
+    ///
+    /// The levels are used as they are, so with the `Inclusive` policy, L1 should start out empty
+    /// or only hold entries which are also in L2.
+    pub fn new(l1: C1, l2: C2, policy: InclusionPolicy) -> Self {
+        TwoLevel { l1, l2, policy }
+    }
+
+    /// Returns the inclusion policy.
+    pub fn policy(&self) -> InclusionPolicy {
+        self.policy
+    }
+
+    /// Returns the L1 cache.
+    pub fn l1(&self) -> &C1 {
+        &self.l1
+    }
+
+    /// Returns the L2 cache.
+    pub fn l2(&self) -> &C2 {
+        &self.l2
+    }
+
+    /// Returns the levels.
+    pub fn into_inner(self) -> (C1, C2) {
+        (self.l1, self.l2)
+    }
+}
+
+impl<K, V, C1, C2> TwoLevel<C1, C2>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    C1: CacheLevel<Key = K, Value = V>,
+    C2: CacheLevel<Key = K, Value = V>,
+{
+    /// Returns a reference to the value of the key, promoting it into L1 if it is only in L2.
+    ///
+    /// With the `Exclusive` policy, the entry moves from L2 to L1, and the entries this evicts from
+    /// L1 move to L2. With the `Inclusive` policy, the entry is copied into L1, and hits in L1 also
+    /// mark the entry as most recently used in L2, so that L2 does not evict entries L1 is using.
+    pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.l1.contains(k) {
+            if self.policy == InclusionPolicy::Inclusive {
+                self.l2.get(k);
+            }
+            return self.l1.get(k);
+        }
+
+        let (key, value) = self.l2.pop_entry(k)?;
+        let l1 = &mut self.l1;
+        let l2 = &mut self.l2;
+        match self.policy {
+            InclusionPolicy::Exclusive => {
+                l1.put_evicting(key, value, |k, v| {
+                    l2.put_evicting(k, v, |_, _| {});
+                });
+            }
+            InclusionPolicy::Inclusive => {
+                // put the entry back as L2's most recently used one before copying it into L1
+                let copy = (key.clone(), value.clone());
+                l2.put_evicting(key, value, |evicted, _| {
+                    // without the turbofish, the `K: Borrow<Q>` bound would be picked
+                    l1.pop_entry::<K>(&evicted);
+                });
+                if l2.contains(k) {
+                    l1.put_evicting(copy.0, copy.1, |_, _| {});
+                }
+            }
+        }
+
+        if self.l1.contains(k) {
+            self.l1.peek(k)
+        } else {
+            self.l2.peek(k)
+        }
+    }
+
+    /// Returns a reference to the value of the key without updating either level.
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.l1.peek(k).or_else(|| self.l2.peek(k))
+    }
+
+    /// Returns a bool indicating whether the key is in either level.
+    pub fn contains<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.l1.contains(k) || self.l2.contains(k)
+    }
+
+    /// Puts a key-value pair into the cache and returns the old value of the key, if any.
+    ///
+    /// With the `Exclusive` policy, the entry goes into L1, the entries this evicts move to L2 and
+    /// any old entry for the key is removed from L2. With the `Inclusive` policy, the entry goes
+    /// into L2 and is copied into L1; if L2 rejects it, it is not cached at all.
+    pub fn put(&mut self, k: K, v: V) -> Option<V> {
+        let l1 = &mut self.l1;
+        let l2 = &mut self.l2;
+        match self.policy {
+            InclusionPolicy::Exclusive => {
+                let old = l2.pop_entry(&k).map(|(_, v)| v);
+                l1.put_evicting(k, v, |k, v| {
+                    l2.put_evicting(k, v, |_, _| {});
+                })
+                .or(old)
+            }
+            InclusionPolicy::Inclusive => {
+                let old = l1.pop_entry(&k).map(|(_, v)| v);
+                let old = l2
+                    .put_evicting(k.clone(), v.clone(), |evicted: K, _| {
+                        l1.pop_entry::<K>(&evicted);
+                    })
+                    .or(old);
+                if l2.contains(&k) {
+                    l1.put_evicting(k, v, |_, _| {});
+                }
+                old
+            }
+        }
+    }
+
+    /// Removes the key from both levels and returns its value, if it was in either.
+    pub fn invalidate<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let v1 = self.l1.pop_entry(k).map(|(_, v)| v);
+        let v2 = self.l2.pop_entry(k).map(|(_, v)| v);
+        v1.or(v2)
+    }
+}
@@ -0,0 +1,357 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A write-back cache which flushes dirty entries before dropping them.
+
+use alloc::borrow::Borrow;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use {CacheLevel, DefaultHasher, Limiter, LruCache, SizeLimited};
+
+/// Writes dirty entries of a `WriteBackCache` back to the store the cache is in front of.
+///
+/// It is implemented for closures taking a key and a value.
+pub trait Flusher<K, V> {
+    /// The error returned when writing an entry fails.
+    type Error;
+
+    /// Writes an entry to the store.
+    fn flush(&mut self, key: &K, value: &V) -> Result<(), Self::Error>;
+}
+
+impl<K, V, E, F: FnMut(&K, &V) -> Result<(), E>> Flusher<K, V> for F {
+    type Error = E;
+
+    fn flush(&mut self, key: &K, value: &V) -> Result<(), E> {
+        self(key, value)
+    }
+}
+
+/// The error returned when dirty entries which an insertion evicted from a `WriteBackCache` could
+/// not be flushed.
+///
+/// The insertion itself succeeded. The entries are no longer in the cache, so they are handed back
+/// here instead of being lost.
+#[derive(Debug)]
+pub struct FlushError<K, V, E> {
+    /// The first error returned by the flusher.
+    pub error: E,
+    /// The evicted dirty entries which could not be flushed.
+    pub entries: Vec<(K, V)>,
+}
+
+impl<K, V, E: fmt::Display> fmt::Display for FlushError<K, V, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to flush {} evicted dirty entries: {}",
+            self.entries.len(),
+            self.error
+        )
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, E: Error + 'static> Error for FlushError<K, V, E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An `LruCache` in front of a slow store, which defers writes to the store until entries are
+/// evicted or flushed.
+///
+/// Entries inserted with `put` are clean, meaning that the store already holds them, while entries
+/// inserted with `put_dirty` or marked with `mark_dirty` are dirty. Every dirty entry is offered to
+/// the `Flusher` before it leaves the cache, whether it is evicted, popped, cleared or dropped with
+/// the cache, and becomes clean once it has been flushed. Dirty entries can also be flushed
+/// explicitly with `flush_all`, or once they have been dirty for some time with
+/// `flush_older_than`.
+///
+/// A dirty entry which the flusher fails on stays in the cache, except when it was evicted by an
+/// insertion, in which case it is returned in a `FlushError`. Errors while the cache is dropped
+/// are ignored.
+///
+/// # Example
+///
+/// ```
+/// use lru::{LruCache, WriteBackCache};
+/// use std::collections::HashMap;
+///
+/// let mut store = HashMap::new();
+/// {
+///     let flusher = |k: &u32, v: &&'static str| -> Result<(), ()> {
+///         store.insert(*k, *v);
+///         Ok(())
+///     };
+///     let mut cache = WriteBackCache::new(LruCache::new(2), flusher);
+///
+///     cache.put_dirty(1, "a").unwrap();
+///     cache.put(2, "b").unwrap();
+///     assert!(cache.is_dirty(&1));
+///
+///     // 1 is evicted and written back, 2 was clean
+///     cache.put(3, "c").unwrap();
+///     assert!(!cache.contains(&1));
+///
+///     *cache.get_mut(&3).unwrap() = "gamma";
+///     cache.flush_all().unwrap();
+///     assert_eq!(cache.dirty_len(), 0);
+/// }
+/// assert_eq!(store.len(), 2);
+/// assert_eq!(store[&1], "a");
+/// assert_eq!(store[&3], "gamma");
+/// ```
+pub struct WriteBackCache<K, V, F, L = SizeLimited, S = DefaultHasher>
+where
+    K: Hash + Eq + Clone,
+    F: Flusher<K, V>,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+{
+    cache: LruCache<K, V, L, S>,
+    // The dirty keys and when they became dirty
+    dirty: HashMap<K, Instant>,
+    flusher: F,
+}
+
+impl<K, V, F, L, S> WriteBackCache<K, V, F, L, S>
+where
+    K: Hash + Eq + Clone,
+    F: Flusher<K, V>,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+{
+    /// Creates a write-back cache from a cache, whose entries are considered clean, and a flusher.
+    pub fn new(cache: LruCache<K, V, L, S>, flusher: F) -> Self {
+        WriteBackCache {
+            cache,
+            dirty: HashMap::new(),
+            flusher,
+        }
+    }
+
+    /// Puts a clean entry into the cache, returning the old value of the key, if any. An old
+    /// value which was dirty is returned without being flushed, since the new value replaces it.
+    pub fn put(&mut self, k: K, v: V) -> Result<Option<V>, FlushError<K, V, F::Error>> {
+        self.dirty.remove(&k);
+        self.insert(k, v)
+    }
+
+    /// Puts a dirty entry into the cache, returning the old value of the key, if any. An entry
+    /// which is too large for the cache is flushed right away.
+    pub fn put_dirty(&mut self, k: K, v: V) -> Result<Option<V>, FlushError<K, V, F::Error>> {
+        // an entry which was already dirty keeps its age, so it is not flushed later than before
+        self.dirty.entry(k.clone()).or_insert_with(Instant::now);
+        self.insert(k, v)
+    }
+
+    /// Marks the entry of a key as dirty, returning false if the key is not in the cache.
+    pub fn mark_dirty(&mut self, k: &K) -> bool {
+        if !self.cache.contains(k) {
+            return false;
+        }
+        self.dirty.entry(k.clone()).or_insert_with(Instant::now);
+        true
+    }
+
+    /// Returns true if the entry of a key is dirty.
+    pub fn is_dirty<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.dirty.contains_key(k)
+    }
+
+    /// Returns the number of dirty entries.
+    pub fn dirty_len(&self) -> usize {
+        self.dirty.len()
+    }
+
+    /// Returns the value of a key and marks it as the most recently used.
+    pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.get(k)
+    }
+
+    /// Returns a mutable reference to the value of a key and marks it as the most recently used.
+    /// The entry is marked as dirty, since it may be modified.
+    pub fn get_mut(&mut self, k: &K) -> Option<&mut V> {
+        if self.mark_dirty(k) {
+            self.cache.get_mut(k)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value of a key without updating its recency.
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.peek(k)
+    }
+
+    /// Returns true if the cache contains the key, without updating its recency.
+    pub fn contains<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.contains(k)
+    }
+
+    /// Removes the entry of a key and returns its value. A dirty entry is flushed first, and stays
+    /// in the cache if that fails.
+    pub fn pop<Q>(&mut self, k: &Q) -> Result<Option<V>, F::Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some((key, _)) = self.dirty.get_key_value(k) {
+            let value = self.cache.peek(k).expect("dirty entries are cached");
+            self.flusher.flush(key, value)?;
+            self.dirty.remove(k);
+        }
+        Ok(self.cache.pop(k))
+    }
+
+    /// Flushes all dirty entries, oldest first, and stops at the first error.
+    pub fn flush_all(&mut self) -> Result<(), F::Error> {
+        self.flush_older_than(Duration::from_secs(0))
+    }
+
+    /// Flushes the entries which have been dirty for at least `age`, oldest first, and stops at
+    /// the first error.
+    pub fn flush_older_than(&mut self, age: Duration) -> Result<(), F::Error> {
+        let now = Instant::now();
+        let mut keys: Vec<(Instant, K)> = self
+            .dirty
+            .iter()
+            .filter(|&(_, &since)| now.saturating_duration_since(since) >= age)
+            .map(|(k, &since)| (since, k.clone()))
+            .collect();
+        keys.sort_by_key(|&(since, _)| since);
+        for (_, k) in keys {
+            let value = self.cache.peek(&k).expect("dirty entries are cached");
+            self.flusher.flush(&k, value)?;
+            self.dirty.remove(&k);
+        }
+        Ok(())
+    }
+
+    /// Flushes all dirty entries and then removes all entries. If flushing fails, the cache is left
+    /// unchanged apart from the entries which were already flushed.
+    pub fn clear(&mut self) -> Result<(), F::Error> {
+        self.flush_all()?;
+        self.cache.clear();
+        Ok(())
+    }
+
+    /// Returns the number of entries in the cache.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns true if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns the underlying cache.
+    pub fn cache(&self) -> &LruCache<K, V, L, S> {
+        &self.cache
+    }
+
+    /// Returns the flusher.
+    pub fn flusher(&self) -> &F {
+        &self.flusher
+    }
+
+    /// Returns a mutable reference to the flusher.
+    pub fn flusher_mut(&mut self) -> &mut F {
+        &mut self.flusher
+    }
+
+    // Inserts an entry whose dirtiness is already recorded, and flushes the dirty entries which it
+    // evicts, including itself if the cache rejects it
+    fn insert(&mut self, k: K, v: V) -> Result<Option<V>, FlushError<K, V, F::Error>> {
+        let dirty = &mut self.dirty;
+        let flusher = &mut self.flusher;
+        let mut error = None;
+        let mut entries = Vec::new();
+        let old = self.cache.put_evicting(k, v, |k, v| {
+            if dirty.remove(&k).is_none() {
+                return;
+            }
+            if let Err(err) = flusher.flush(&k, &v) {
+                error.get_or_insert(err);
+                entries.push((k, v));
+            }
+        });
+        match error {
+            None => Ok(old),
+            Some(error) => Err(FlushError { error, entries }),
+        }
+    }
+}
+
+impl<K, V, F, L, S> Drop for WriteBackCache<K, V, F, L, S>
+where
+    K: Hash + Eq + Clone,
+    F: Flusher<K, V>,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        // unlike `flush_all`, every dirty entry is offered even after an error
+        for (k, _) in self.dirty.drain() {
+            if let Some(value) = self.cache.peek(&k) {
+                let _ = self.flusher.flush(&k, value);
+            }
+        }
+    }
+}
+
+impl<K, V, F, L, S> fmt::Debug for WriteBackCache<K, V, F, L, S>
+where
+    K: Hash + Eq + Clone,
+    F: Flusher<K, V>,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteBackCache")
+            .field("len", &self.cache.len())
+            .field("dirty_len", &self.dirty.len())
+            .finish()
+    }
+}
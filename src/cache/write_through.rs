@@ -0,0 +1,256 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A write-through cache in front of a backing store.
+
+use alloc::borrow::Borrow;
+#[cfg(any(feature = "hashbrown", not(feature = "no_std")))]
+use core::convert::Infallible;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+#[cfg(not(feature = "no_std"))]
+use std::collections::HashMap;
+
+use {DefaultHasher, Entry, Limiter, LruCache, MaybeCached, SizeLimited};
+
+/// The store behind a `WriteThroughCache`, which holds every entry.
+///
+/// It is implemented for `std::collections::HashMap` and, with the `hashbrown` feature,
+/// `hashbrown::HashMap`, which are useful as in-memory stores in tests.
+pub trait BackingStore<K, V> {
+    /// The error returned when accessing the store fails.
+    type Error;
+
+    /// Reads the value of a key, or returns `None` if the store does not hold it.
+    fn load(&mut self, key: &K) -> Result<Option<V>, Self::Error>;
+
+    /// Writes an entry, replacing the old value of its key.
+    fn store(&mut self, key: &K, value: &V) -> Result<(), Self::Error>;
+
+    /// Deletes the entry of a key, if the store holds it.
+    fn remove(&mut self, key: &K) -> Result<(), Self::Error>;
+}
+
+#[cfg(not(feature = "no_std"))]
+impl<K, V, S> BackingStore<K, V> for HashMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    type Error = Infallible;
+
+    fn load(&mut self, key: &K) -> Result<Option<V>, Infallible> {
+        Ok(self.get(key).cloned())
+    }
+
+    fn store(&mut self, key: &K, value: &V) -> Result<(), Infallible> {
+        self.insert(key.clone(), value.clone());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &K) -> Result<(), Infallible> {
+        HashMap::remove(self, key);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "hashbrown")]
+impl<K, V, S> BackingStore<K, V> for ::hashbrown::HashMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    type Error = Infallible;
+
+    fn load(&mut self, key: &K) -> Result<Option<V>, Infallible> {
+        Ok(self.get(key).cloned())
+    }
+
+    fn store(&mut self, key: &K, value: &V) -> Result<(), Infallible> {
+        self.insert(key.clone(), value.clone());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &K) -> Result<(), Infallible> {
+        ::hashbrown::HashMap::remove(self, key);
+        Ok(())
+    }
+}
+
+/// An `LruCache` in front of a `BackingStore`, which writes every change to the store before
+/// applying it to the cache.
+///
+/// `put` only caches an entry once the store has accepted it, and `remove` deletes it from both,
+/// so the cache never holds a value which the store does not. A lookup which misses the cache
+/// reads the value from the store and caches it. Errors of the store are returned as is, and leave
+/// the cache unchanged.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(feature = "no_std"))]
+/// # fn main() {
+/// use lru::{LruCache, WriteThroughCache};
+/// use std::collections::HashMap;
+///
+/// let mut cache = WriteThroughCache::new(LruCache::new(1), HashMap::new());
+/// cache.put(1, "a").unwrap();
+/// cache.put(2, "b").unwrap();
+/// assert!(!cache.contains(&1));
+/// assert_eq!(cache.store().len(), 2);
+///
+/// // 1 is read back from the store and cached again
+/// assert_eq!(cache.get(&1).unwrap().as_deref(), Some(&"a"));
+/// assert!(cache.contains(&1));
+///
+/// cache.remove(&1).unwrap();
+/// assert_eq!(cache.get(&1).unwrap(), None);
+/// # }
+/// # #[cfg(feature = "no_std")]
+/// # fn main() {}
+/// ```
+pub struct WriteThroughCache<K, V, B, L = SizeLimited, S = DefaultHasher> {
+    cache: LruCache<K, V, L, S>,
+    store: B,
+}
+
+impl<K, V, B, L, S> WriteThroughCache<K, V, B, L, S>
+where
+    K: Hash + Eq + Clone,
+    B: BackingStore<K, V>,
+    L: Limiter<K, V, S>,
+    S: BuildHasher,
+{
+    /// Creates a write-through cache from a cache, whose entries must match the store, and a
+    /// store.
+    pub fn new(cache: LruCache<K, V, L, S>, store: B) -> Self {
+        WriteThroughCache { cache, store }
+    }
+
+    /// Writes an entry to the store and then caches it, returning the old cached value of the
+    /// key, if any.
+    pub fn put(&mut self, k: K, v: V) -> Result<Option<V>, B::Error> {
+        self.store.store(&k, &v)?;
+        Ok(self.cache.put(k, v))
+    }
+
+    /// Returns the value of a key, reading it from the store and caching it if it is not cached.
+    /// A value which the cache's limiter rejects is returned by value, without being cached.
+    pub fn get(&mut self, k: &K) -> Result<Option<MaybeCached<'_, V>>, B::Error> {
+        if self.cache.contains(k) {
+            return Ok(self.cache.get(k).map(MaybeCached::Cached));
+        }
+        let v = match self.store.load(k)? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        match self.cache.entry(k.clone()) {
+            Entry::Occupied(entry) => Ok(Some(MaybeCached::Cached(entry.into_mut()))),
+            Entry::Vacant(entry) => match entry.try_insert(v) {
+                Ok(v) => Ok(Some(MaybeCached::Cached(v))),
+                Err((_, v)) => Ok(Some(MaybeCached::Uncached(v))),
+            },
+        }
+    }
+
+    /// Returns the cached value of a key without updating its recency or reading the store.
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.peek(k)
+    }
+
+    /// Returns true if the key is cached, without updating its recency or reading the store.
+    pub fn contains<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.contains(k)
+    }
+
+    /// Deletes the entry of a key from the store and then from the cache, returning the cached
+    /// value, if any.
+    pub fn remove(&mut self, k: &K) -> Result<Option<V>, B::Error> {
+        self.store.remove(k)?;
+        Ok(self.cache.pop(k))
+    }
+
+    /// Removes the entry of a key from the cache only, so that the next lookup reads the store.
+    pub fn invalidate<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.pop(k)
+    }
+
+    /// Returns the number of cached entries.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns true if no entries are cached.
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns the cache.
+    pub fn cache(&self) -> &LruCache<K, V, L, S> {
+        &self.cache
+    }
+
+    /// Returns the store.
+    pub fn store(&self) -> &B {
+        &self.store
+    }
+
+    /// Returns a mutable reference to the store. Entries changed through it directly must be
+    /// invalidated.
+    pub fn store_mut(&mut self) -> &mut B {
+        &mut self.store
+    }
+
+    /// Returns the cache and the store.
+    pub fn into_inner(self) -> (LruCache<K, V, L, S>, B) {
+        (self.cache, self.store)
+    }
+}
+
+impl<K, V, B, L, S> fmt::Debug for WriteThroughCache<K, V, B, L, S>
+where
+    K: Hash + Eq,
+    B: fmt::Debug,
+    L: Limiter<K, V, S> + fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteThroughCache")
+            .field("cache", &self.cache)
+            .field("store", &self.store)
+            .finish()
+    }
+}
@@ -0,0 +1,741 @@
+// MIT License
+
+// Copyright (c) 2016 Jerome Froelich
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A fixed-capacity LRU cache which never allocates. Only depends on `core`.
+
+use core::borrow::Borrow;
+use core::fmt;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::iter::FusedIterator;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+
+// Sentinel for "no entry" in links and index slots
+const NONE: u32 = u32::MAX;
+
+// An entry of the cache, linked to its neighbours in the LRU list by index. Vacant entries which
+//  are on the free list only use `next`.
+struct Node<K, V> {
+    key: K,
+    val: V,
+    prev: u32,
+    next: u32,
+}
+
+// A slot in the open-addressing index. Caches the hash of the entry's key, so probing and
+//  shifting entries around never needs to rehash keys.
+#[derive(Copy, Clone)]
+struct Slot {
+    entry: u32,
+    hash: u32,
+}
+
+impl Slot {
+    const EMPTY: Slot = Slot {
+        entry: NONE,
+        hash: 0,
+    };
+}
+
+/// A `BuildHasher` for the FNV-1a hash function. Used as the default hasher of
+/// `HeaplessLruCache`, because it can be created in const contexts and needs no source of
+/// randomness.
+///
+/// FNV-1a is not resistant to hash flooding, so use a keyed hasher if the keys can be chosen by
+/// an attacker.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FnvBuildHasher;
+
+impl BuildHasher for FnvBuildHasher {
+    type Hasher = FnvHasher;
+
+    fn build_hasher(&self) -> FnvHasher {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+/// The `Hasher` created by `FnvBuildHasher`.
+#[derive(Debug, Copy, Clone)]
+pub struct FnvHasher(u64);
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+/// A fixed-capacity LRU cache which stores up to `N` entries inline and never allocates, for
+/// targets without an allocator.
+///
+/// Entries are kept in an array and linked into the LRU list by index. Keys are looked up through
+/// an inline open-addressing index with two slots per entry, so lookups stay fast even when the
+/// cache is full. Unlike `LruCache`, the capacity is always the number of entries and there is no
+/// `Limiter`.
+///
+/// The cache can be created in const contexts, e.g. to put it in a static. It is the only cache
+/// which is available without the `alloc` feature, so that it can be used on targets without a
+/// global allocator with `default-features = false`.
+///
+/// # Example
+///
+/// ```
+/// use lru::HeaplessLruCache;
+///
+/// static EMPTY: HeaplessLruCache<&str, u32, 2> = HeaplessLruCache::new();
+///
+/// let mut cache: HeaplessLruCache<&str, u32, 2> = HeaplessLruCache::new();
+/// cache.put("apple", 3);
+/// cache.put("banana", 2);
+///
+/// assert_eq!(cache.get(&"apple"), Some(&3));
+/// assert_eq!(cache.put("pear", 5), None);
+/// assert_eq!(cache.get(&"banana"), None);
+/// assert!(EMPTY.is_empty());
+/// ```
+pub struct HeaplessLruCache<K, V, const N: usize, S = FnvBuildHasher> {
+    nodes: MaybeUninit<[Node<K, V>; N]>,
+    // two slots per entry keeps the load factor of the index at most 1/2
+    index: [[Slot; 2]; N],
+    // most and least recently used entries
+    head: u32,
+    tail: u32,
+    // vacant entries which were used before, threaded through `next`
+    free: u32,
+    // entries at or past `used` have never been used and are not on the free list
+    used: u32,
+    len: u32,
+    hash_builder: S,
+}
+
+impl<K, V, const N: usize> HeaplessLruCache<K, V, N> {
+    /// Creates a new, empty cache which holds at most `N` items.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 10> = HeaplessLruCache::new();
+    /// ```
+    pub const fn new() -> Self {
+        Self::with_hasher(FnvBuildHasher)
+    }
+}
+
+impl<K, V, const N: usize, S> HeaplessLruCache<K, V, N, S> {
+    /// Creates a new, empty cache which holds at most `N` items and uses the given hash builder
+    /// to hash keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::{FnvBuildHasher, HeaplessLruCache};
+    /// let mut cache: HeaplessLruCache<isize, &str, 10> =
+    ///     HeaplessLruCache::with_hasher(FnvBuildHasher);
+    /// ```
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        assert!(N < NONE as usize, "HeaplessLruCache capacity is too large");
+        HeaplessLruCache {
+            nodes: MaybeUninit::uninit(),
+            index: [[Slot::EMPTY; 2]; N],
+            head: NONE,
+            tail: NONE,
+            free: NONE,
+            used: 0,
+            len: 0,
+            hash_builder,
+        }
+    }
+
+    /// Returns the maximum number of key-value pairs the cache can hold, which is always `N`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let cache: HeaplessLruCache<isize, &str, 2> = HeaplessLruCache::new();
+    /// assert_eq!(cache.cap(), 2);
+    /// ```
+    pub const fn cap(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of key-value pairs that are currently in the the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 2> = HeaplessLruCache::new();
+    /// assert_eq!(cache.len(), 0);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    /// assert_eq!(cache.len(), 2);
+    /// ```
+    pub const fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns a bool indicating whether the cache is empty or not.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 2> = HeaplessLruCache::new();
+    /// assert!(cache.is_empty());
+    ///
+    /// cache.put(1, "a");
+    /// assert!(!cache.is_empty());
+    /// ```
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the key and value of the least recently used item or `None` if the cache is
+    /// empty. Does not update the LRU list.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 2> = HeaplessLruCache::new();
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// assert_eq!(cache.peek_lru(), Some((&1, &"a")));
+    /// ```
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        if self.tail == NONE {
+            return None;
+        }
+        let node = unsafe { &*self.node(self.tail) };
+        Some((&node.key, &node.val))
+    }
+
+    /// An iterator visiting all entries in most-recently used order. The iterator element type is
+    /// `(&K, &V)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<&str, u32, 3> = HeaplessLruCache::new();
+    /// cache.put("a", 1);
+    /// cache.put("b", 2);
+    /// cache.put("c", 3);
+    ///
+    /// let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, ["c", "b", "a"]);
+    /// ```
+    pub fn iter(&self) -> HeaplessIter<'_, K, V, N, S> {
+        HeaplessIter {
+            cache: self,
+            front: self.head,
+            back: self.tail,
+            len: self.len(),
+        }
+    }
+
+    fn node(&self, i: u32) -> *const Node<K, V> {
+        debug_assert!((i as usize) < N);
+        unsafe { (self.nodes.as_ptr() as *const Node<K, V>).add(i as usize) }
+    }
+
+    fn node_mut(&mut self, i: u32) -> *mut Node<K, V> {
+        debug_assert!((i as usize) < N);
+        unsafe { (self.nodes.as_mut_ptr() as *mut Node<K, V>).add(i as usize) }
+    }
+
+    fn slot(&self, pos: usize) -> Slot {
+        self.index[pos / 2][pos % 2]
+    }
+
+    fn set_slot(&mut self, pos: usize, slot: Slot) {
+        self.index[pos / 2][pos % 2] = slot;
+    }
+
+    fn detach(&mut self, i: u32) {
+        let (prev, next) = unsafe {
+            let node = self.node(i);
+            ((*node).prev, (*node).next)
+        };
+        match prev {
+            NONE => self.head = next,
+            prev => unsafe { (*self.node_mut(prev)).next = next },
+        }
+        match next {
+            NONE => self.tail = prev,
+            next => unsafe { (*self.node_mut(next)).prev = prev },
+        }
+    }
+
+    // Attaches `i` as the most recently used entry
+    fn attach(&mut self, i: u32) {
+        let head = self.head;
+        unsafe {
+            let node = self.node_mut(i);
+            (*node).prev = NONE;
+            (*node).next = head;
+        }
+        match head {
+            NONE => self.tail = i,
+            head => unsafe { (*self.node_mut(head)).prev = i },
+        }
+        self.head = i;
+    }
+
+    // Removes the index slot at `pos` by shifting back the entries which probed past it
+    fn remove_slot(&mut self, pos: usize) {
+        let size = 2 * N;
+        let mut hole = pos;
+        let mut cur = pos;
+        loop {
+            cur = (cur + 1) % size;
+            let slot = self.slot(cur);
+            if slot.entry == NONE {
+                break;
+            }
+            let ideal = slot.hash as usize % size;
+            // the slot may move to the hole if the hole lies on its probe path
+            if (cur + size - ideal) % size >= (cur + size - hole) % size {
+                self.set_slot(hole, slot);
+                hole = cur;
+            }
+        }
+        self.set_slot(hole, Slot::EMPTY);
+    }
+
+    // Unlinks entry `i`, whose index slot is at `pos`, and moves its key and value out
+    fn take(&mut self, pos: usize, i: u32) -> (K, V) {
+        self.remove_slot(pos);
+        self.detach(i);
+        self.len -= 1;
+        let node = self.node_mut(i);
+        let entry = unsafe { (ptr::read(&(*node).key), ptr::read(&(*node).val)) };
+        unsafe { (*node).next = self.free };
+        self.free = i;
+        entry
+    }
+}
+
+impl<K: Hash + Eq, V, const N: usize, S: BuildHasher> HeaplessLruCache<K, V, N, S> {
+    /// Puts a key-value pair into cache. If the key already exists in the cache, then it updates
+    /// the key's value and returns the old value. Otherwise, `None` is returned. If the cache is
+    /// full, the least recently used entry is evicted and dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 2> = HeaplessLruCache::new();
+    ///
+    /// assert_eq!(None, cache.put(1, "a"));
+    /// assert_eq!(None, cache.put(2, "b"));
+    /// assert_eq!(Some("b"), cache.put(2, "beta"));
+    ///
+    /// assert_eq!(cache.get(&1), Some(&"a"));
+    /// assert_eq!(cache.get(&2), Some(&"beta"));
+    /// ```
+    pub fn put(&mut self, k: K, v: V) -> Option<V> {
+        if let Some(value) = self.get_mut(&k) {
+            return Some(mem::replace(value, v));
+        }
+        match self.insert(k, v) {
+            Ok(_) => None,
+            Err((_, v)) => Some(v),
+        }
+    }
+
+    /// Pushes a key-value pair into the cache. If an entry with key `k` already exists in the
+    /// cache or another cache entry is evicted (due to the cache's capacity), then it returns the
+    /// old entry's key-value pair. Otherwise, returns `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 2> = HeaplessLruCache::new();
+    ///
+    /// assert_eq!(None, cache.push(1, "a"));
+    /// assert_eq!(None, cache.push(2, "b"));
+    /// assert_eq!(Some((2, "b")), cache.push(2, "beta"));
+    /// assert_eq!(Some((1, "a")), cache.push(3, "alpha"));
+    /// ```
+    pub fn push(&mut self, k: K, v: V) -> Option<(K, V)> {
+        if let Some((pos, i)) = self.find(&k) {
+            let old = self.take(pos, i);
+            let _ = self.insert(k, v);
+            return Some(old);
+        }
+        match self.insert(k, v) {
+            Ok(evicted) => evicted,
+            Err(rejected) => Some(rejected),
+        }
+    }
+
+    /// Returns a reference to the value of the key in the cache or `None` if it is not present
+    /// in the cache. Moves the key to the head of the LRU list if it exists.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 2> = HeaplessLruCache::new();
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    ///
+    /// assert_eq!(cache.get(&1), None);
+    /// assert_eq!(cache.get(&2), Some(&"b"));
+    /// ```
+    pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Some(self.get_mut(k)?)
+    }
+
+    /// Returns a mutable reference to the value of the key in the cache or `None` if it is not
+    /// present in the cache. Moves the key to the head of the LRU list if it exists.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<&str, u32, 2> = HeaplessLruCache::new();
+    ///
+    /// cache.put("apple", 8);
+    /// *cache.get_mut(&"apple").unwrap() += 1;
+    /// assert_eq!(cache.get(&"apple"), Some(&9));
+    /// ```
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (_, i) = self.find(k)?;
+        if self.head != i {
+            self.detach(i);
+            self.attach(i);
+        }
+        Some(unsafe { &mut (*self.node_mut(i)).val })
+    }
+
+    /// Returns a reference to the value corresponding to the key in the cache or `None` if it is
+    /// not present in the cache. Unlike `get`, `peek` does not update the LRU list so the key's
+    /// position will be unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 2> = HeaplessLruCache::new();
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// assert_eq!(cache.peek(&1), Some(&"a"));
+    /// assert_eq!(cache.peek_lru(), Some((&1, &"a")));
+    /// ```
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (_, i) = self.find(k)?;
+        Some(unsafe { &(*self.node(i)).val })
+    }
+
+    /// Returns a bool indicating whether the given key is in the cache. Does not update the
+    /// LRU list.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 2> = HeaplessLruCache::new();
+    ///
+    /// cache.put(1, "a");
+    /// assert!(cache.contains(&1));
+    /// assert!(!cache.contains(&2));
+    /// ```
+    pub fn contains<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(k).is_some()
+    }
+
+    /// Removes and returns the value corresponding to the key from the cache or `None` if it
+    /// does not exist.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 2> = HeaplessLruCache::new();
+    ///
+    /// cache.put(2, "a");
+    ///
+    /// assert_eq!(cache.pop(&1), None);
+    /// assert_eq!(cache.pop(&2), Some("a"));
+    /// assert_eq!(cache.pop(&2), None);
+    /// ```
+    pub fn pop<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (pos, i) = self.find(k)?;
+        Some(self.take(pos, i).1)
+    }
+
+    /// Removes and returns the key and value corresponding to the least recently used item or
+    /// `None` if the cache is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 3> = HeaplessLruCache::new();
+    ///
+    /// cache.put(2, "a");
+    /// cache.put(3, "b");
+    /// cache.put(4, "c");
+    /// cache.get(&3);
+    ///
+    /// assert_eq!(cache.pop_lru(), Some((2, "a")));
+    /// assert_eq!(cache.pop_lru(), Some((4, "c")));
+    /// ```
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        if self.tail == NONE {
+            return None;
+        }
+        let i = self.tail;
+        let pos = self.find_slot(i);
+        Some(self.take(pos, i))
+    }
+
+    /// Clears the contents of the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lru::HeaplessLruCache;
+    /// let mut cache: HeaplessLruCache<isize, &str, 2> = HeaplessLruCache::new();
+    ///
+    /// cache.put(1, "a");
+    /// cache.clear();
+    /// assert!(cache.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        while self.pop_lru().is_some() {}
+    }
+
+    fn hash<Q: Hash + ?Sized>(&self, k: &Q) -> u32 {
+        let mut hasher = self.hash_builder.build_hasher();
+        k.hash(&mut hasher);
+        let hash = hasher.finish();
+        (hash ^ (hash >> 32)) as u32
+    }
+
+    // Finds the index slot and entry of the given key
+    fn find<Q>(&self, k: &Q) -> Option<(usize, u32)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        let size = 2 * N;
+        let hash = self.hash(k);
+        let mut pos = hash as usize % size;
+        loop {
+            let slot = self.slot(pos);
+            if slot.entry == NONE {
+                return None;
+            }
+            if slot.hash == hash && unsafe { (*self.node(slot.entry)).key.borrow() } == k {
+                return Some((pos, slot.entry));
+            }
+            pos = (pos + 1) % size;
+        }
+    }
+
+    // Finds the index slot of entry `i`
+    fn find_slot(&self, i: u32) -> usize {
+        let key = unsafe { &(*self.node(i)).key };
+        match self.find(key) {
+            Some((pos, entry)) if entry == i => pos,
+            _ => unreachable!("Entry is missing from the index"),
+        }
+    }
+
+    // Inserts a key which is not in the cache, evicting the LRU entry if the cache is full.
+    //  Returns the key-value pair if the cache has zero capacity.
+    #[allow(clippy::type_complexity)]
+    fn insert(&mut self, k: K, v: V) -> Result<Option<(K, V)>, (K, V)> {
+        if N == 0 {
+            return Err((k, v));
+        }
+        let evicted = if self.len() == N {
+            self.pop_lru()
+        } else {
+            None
+        };
+        let i = if self.free != NONE {
+            let i = self.free;
+            self.free = unsafe { (*self.node(i)).next };
+            i
+        } else {
+            self.used += 1;
+            self.used - 1
+        };
+        unsafe {
+            let node = self.node_mut(i);
+            ptr::write(ptr::addr_of_mut!((*node).key), k);
+            ptr::write(ptr::addr_of_mut!((*node).val), v);
+        }
+        self.attach(i);
+        self.len += 1;
+
+        let size = 2 * N;
+        let hash = self.hash(unsafe { &(*self.node(i)).key });
+        let mut pos = hash as usize % size;
+        while self.slot(pos).entry != NONE {
+            pos = (pos + 1) % size;
+        }
+        self.set_slot(pos, Slot { entry: i, hash });
+        Ok(evicted)
+    }
+}
+
+impl<K, V, const N: usize> Default for HeaplessLruCache<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize, S> Drop for HeaplessLruCache<K, V, N, S> {
+    fn drop(&mut self) {
+        let mut i = self.head;
+        while i != NONE {
+            unsafe {
+                let node = self.node_mut(i);
+                i = (*node).next;
+                ptr::drop_in_place(ptr::addr_of_mut!((*node).key));
+                ptr::drop_in_place(ptr::addr_of_mut!((*node).val));
+            }
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, const N: usize, S> fmt::Debug for HeaplessLruCache<K, V, N, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, V, const N: usize, S> IntoIterator for &'a HeaplessLruCache<K, V, N, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = HeaplessIter<'a, K, V, N, S>;
+
+    fn into_iter(self) -> HeaplessIter<'a, K, V, N, S> {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a `HeaplessLruCache`.
+///
+/// This `struct` is created by the [`iter`] method on [`HeaplessLruCache`][`HeaplessLruCache`].
+/// See its documentation for more.
+///
+/// [`iter`]: struct.HeaplessLruCache.html#method.iter
+/// [`HeaplessLruCache`]: struct.HeaplessLruCache.html
+pub struct HeaplessIter<'a, K, V, const N: usize, S> {
+    cache: &'a HeaplessLruCache<K, V, N, S>,
+    front: u32,
+    back: u32,
+    len: usize,
+}
+
+impl<'a, K, V, const N: usize, S> Iterator for HeaplessIter<'a, K, V, N, S> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = unsafe { &*self.cache.node(self.front) };
+        self.front = node.next;
+        self.len -= 1;
+        Some((&node.key, &node.val))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    fn count(self) -> usize {
+        self.len
+    }
+}
+
+impl<'a, K, V, const N: usize, S> DoubleEndedIterator for HeaplessIter<'a, K, V, N, S> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = unsafe { &*self.cache.node(self.back) };
+        self.back = node.prev;
+        self.len -= 1;
+        Some((&node.key, &node.val))
+    }
+}
+
+impl<'a, K, V, const N: usize, S> ExactSizeIterator for HeaplessIter<'a, K, V, N, S> {}
+impl<'a, K, V, const N: usize, S> FusedIterator for HeaplessIter<'a, K, V, N, S> {}
+
+impl<'a, K, V, const N: usize, S> Clone for HeaplessIter<'a, K, V, N, S> {
+    fn clone(&self) -> Self {
+        HeaplessIter {
+            cache: self.cache,
+            front: self.front,
+            back: self.back,
+            len: self.len,
+        }
+    }
+}
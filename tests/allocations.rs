@@ -0,0 +1,138 @@
+#![cfg(feature = "alloc")]
+
+extern crate lru;
+extern crate stats_alloc;
+
+use lru::{CostLimited, Entry, LruCache};
+use stats_alloc::{Region, StatsAlloc, INSTRUMENTED_SYSTEM};
+use std::alloc::System;
+
+#[global_allocator]
+static GLOBAL: &StatsAlloc<System> = &INSTRUMENTED_SYSTEM;
+
+// The allocation counters are global, so everything is checked from a single test to keep other
+// tests from allocating concurrently.
+#[test]
+fn test_allocations() {
+    // filling a presized cache doesn't allocate
+    let region = Region::new(GLOBAL);
+    let mut cache = LruCache::with_capacity(100);
+    let allocations = region.change().allocations;
+    for i in 0..100 {
+        cache.put(i, i);
+    }
+    assert_eq!(region.change().allocations, allocations);
+
+    // once warm, evicting and popping entries reuses their memory
+    for i in 100..1000 {
+        cache.put(i, i);
+    }
+    let region = Region::new(GLOBAL);
+    for i in 1000..10000 {
+        cache.put(i, i);
+        if i % 3 == 0 {
+            cache.pop(&i);
+        }
+    }
+    assert_eq!(region.change().allocations, 0);
+
+    // entries which grow the cache are allocated in chunks, rather than one by one
+    let mut cache = LruCache::unbounded();
+    let region = Region::new(GLOBAL);
+    for i in 0..10000 {
+        cache.put(i, i);
+    }
+    assert!(region.change().allocations < 100);
+    cache.clear();
+    let region = Region::new(GLOBAL);
+    for i in 0..10000 {
+        cache.put(i, i);
+    }
+    assert_eq!(region.change().allocations, 0);
+
+    // reserved entries don't allocate either
+    let mut cache = LruCache::unbounded();
+    cache.reserve(1000);
+    let region = Region::new(GLOBAL);
+    for i in 0..1000 {
+        cache.put(i, i);
+    }
+    assert_eq!(region.change().allocations, 0);
+
+    let limiter = CostLimited::with_func(500, (|_: &u32| 0, |value: &u32| *value as usize + 1));
+    let mut cache = LruCache::with_capacity_and_limiter(100, limiter);
+    for i in 0..1000 {
+        cache.put(i, i % 10);
+    }
+    let region = Region::new(GLOBAL);
+    for i in 1000..10000 {
+        cache.put(i, i % 10);
+    }
+    assert_eq!(region.change().allocations, 0);
+
+    // every way of inserting into a full cache reuses the LRU node, and every way of removing
+    //  entries keeps their nodes for later inserts
+    let mut cache = LruCache::with_capacity(100);
+    let churn = |cache: &mut LruCache<u32, u32>, i: u32| match i % 5 {
+        0 => drop(cache.put(i, i)),
+        1 => drop(cache.push(i, i)),
+        2 => drop(cache.entry(i).or_insert(i)),
+        3 => {
+            if let Entry::Occupied(entry) = cache.entry(i - 3) {
+                entry.remove();
+            }
+            cache.put(i, i);
+        }
+        _ => {
+            cache.pop_lru();
+            cache.pop(&(i - 2));
+            cache.put(i, i);
+            cache.put(i + 100_000, i);
+        }
+    };
+    // the hash table may still grow once to make up for removed entries while warming up
+    for i in 0..1000 {
+        churn(&mut cache, i);
+    }
+    let region = Region::new(GLOBAL);
+    for i in 1000..10000 {
+        churn(&mut cache, i);
+    }
+    let change = region.change();
+    assert_eq!((change.allocations, change.deallocations), (0, 0));
+
+    // a bounded free list releases the nodes of popped entries
+    let mut cache = LruCache::unbounded();
+    cache.set_max_free_nodes(64);
+    for i in 0..10000 {
+        cache.put(i, i);
+    }
+    let region = Region::new(GLOBAL);
+    while cache.pop_lru().is_some() {}
+    // the chunks were released as they became empty, without allocating anything to do so
+    let change = region.change();
+    assert!(change.bytes_deallocated > 9000 * std::mem::size_of::<(u32, u32)>());
+    assert_eq!(change.allocations, 0);
+
+    // one empty chunk is kept, so that churning across the end of a chunk doesn't allocate and
+    //  release the next one every time
+    let mut cache = LruCache::unbounded();
+    cache.set_max_free_nodes(0);
+    cache.reserve(100);
+    for i in 0..=100 {
+        cache.put(i, i);
+    }
+    cache.pop(&100);
+    // the hash table may still grow once to make up for removed entries while warming up
+    for i in 101..1000 {
+        cache.put(i, i);
+        cache.pop(&i);
+    }
+    let region = Region::new(GLOBAL);
+    for i in 1000..10000 {
+        cache.put(i, i);
+        cache.pop(&i);
+    }
+    let change = region.change();
+    assert_eq!((change.allocations, change.deallocations), (0, 0));
+}
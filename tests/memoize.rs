@@ -0,0 +1,104 @@
+#![cfg(feature = "macros")]
+
+extern crate lru;
+
+use lru::lru_memoize;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+thread_local! {
+    static CALLS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn calls() -> usize {
+    CALLS.with(Cell::get)
+}
+
+fn count_call() {
+    CALLS.with(|calls| calls.set(calls.get() + 1));
+}
+
+#[lru_memoize(cap = 2)]
+fn concat(a: String, mut b: u32) -> String {
+    count_call();
+    b += 1;
+    format!("{}{}", a, b)
+}
+
+#[test]
+fn test_memoize() {
+    let start = calls();
+    assert_eq!(concat("a".to_string(), 1), "a2");
+    assert_eq!(concat("a".to_string(), 1), "a2");
+    assert_eq!(concat("b".to_string(), 1), "b2");
+    assert_eq!(calls() - start, 2);
+
+    // ("a", 1) is the least recently used, so it is evicted and computed again
+    assert_eq!(concat("c".to_string(), 1), "c2");
+    assert_eq!(concat("a".to_string(), 1), "a2");
+    assert_eq!(calls() - start, 4);
+}
+
+#[lru_memoize(cap = 100)]
+fn fibonacci(n: u64) -> u64 {
+    count_call();
+    if n < 2 {
+        n
+    } else {
+        fibonacci(n - 1) + fibonacci(n - 2)
+    }
+}
+
+#[test]
+fn test_memoize_recursive() {
+    let start = calls();
+    assert_eq!(fibonacci(90), 2880067194370816120);
+    assert_eq!(calls() - start, 91);
+    assert_eq!(fibonacci(90), 2880067194370816120);
+    assert_eq!(calls() - start, 91);
+}
+
+#[lru_memoize(cap = 4)]
+fn parse(input: &'static str) -> Result<u32, std::num::ParseIntError> {
+    count_call();
+    input.parse()
+}
+
+#[test]
+fn test_memoize_result() {
+    let start = calls();
+    assert_eq!(parse("12"), Ok(12));
+    assert_eq!(parse("12"), Ok(12));
+    assert_eq!(calls() - start, 1);
+
+    // errors are not cached
+    assert!(parse("twelve").is_err());
+    assert!(parse("twelve").is_err());
+    assert_eq!(calls() - start, 3);
+}
+
+static SHARED_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[lru_memoize(cap = 16, sync)]
+fn shared(x: u32) -> u32 {
+    SHARED_CALLS.fetch_add(1, Ordering::SeqCst);
+    x * 2
+}
+
+#[test]
+fn test_memoize_sync() {
+    assert_eq!(shared(1), 2);
+    let handles: Vec<_> = (0..4)
+        .map(|_| thread::spawn(|| (0..8).map(shared).sum::<u32>()))
+        .collect();
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), 56);
+    }
+    // every thread saw the result for 1 computed on this one, and each other result is computed
+    // at most once per thread
+    let calls = SHARED_CALLS.load(Ordering::SeqCst);
+    assert!((8..=1 + 7 * 4).contains(&calls));
+    assert_eq!(shared(1), 2);
+    assert_eq!(SHARED_CALLS.load(Ordering::SeqCst), calls);
+}